@@ -0,0 +1,25 @@
+/* C interface to libcsv2vviz (built with `cargo build --release`). */
+
+#ifndef CSV2VVIZ_H
+#define CSV2VVIZ_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Converts the archive at `path` to .vviz. `options_json` is a JSON object of `convert`
+ * flags, e.g. {"rotate": "0 0 90", "out_dir": "shows"}, or NULL for the defaults.
+ *
+ * Returns {"ok":true,"outputs":[...]} or {"ok":false,"error":"...","exitCode":N}. The
+ * string must be released with csv2vviz_free_string().
+ */
+char *csv2vviz_convert(const char *path, const char *options_json);
+
+void csv2vviz_free_string(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{archive, error::{Error, Result}, input, progress, read_drones, track, ConvertOptions, Drone};
+
+/// When one act of a merged show plays, in seconds of show time, and the input folder it was
+/// read from; the merged show's metadata lists them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Act {
+    pub name: String,
+    pub start: f32,
+    pub end: f32,
+    pub source: String
+}
+
+/// Folders of the input holding drone CSVs or logs, in the order they first appear. An
+/// archive with more than one is a multi-show archive (`ActOne/Drone 1.csv`,
+/// `ActTwo/Drone 1.csv`, ...); macOS resource forks are ignored.
+pub fn detect(input: archive::Input) -> Result<Vec<String>> {
+    let mut folders: Vec<String> = vec![];
+    input.for_each_entry(|name, _| {
+        let folder = archive::parent(name);
+        let lower = archive::basename(name).to_ascii_lowercase();
+        if (lower.ends_with(".csv") || lower.ends_with(".ulg"))
+            && !folder.starts_with("__MACOSX")
+            && !folders.iter().any(|known| known == folder) {
+            folders.push(folder.to_string());
+        }
+        Ok(())
+    })?;
+    Ok(folders)
+}
+
+/// A show to write: its name, drones and the acts merged into it.
+pub type Named = (String, Vec<Drone>, Vec<Act>);
+
+/// The shows to write from `input`, named after their folder: one unnamed show for an
+/// ordinary archive, one per folder for a multi-show archive, or a single unnamed show of
+/// every act back to back with `--merge-acts`, along with the acts it is made of. Fails on
+/// a show left without drones, when none were found or every one was skipped.
+pub fn read(input: archive::Input, options: &ConvertOptions) -> Result<Vec<Named>> {
+    let shows = read_shows(input, options)?;
+    if let Some((name, _, _)) = shows.iter().find(|(_, drones, _)| drones.is_empty()) {
+        let source = match input {
+            archive::Input::Path(fname) => fname.display().to_string(),
+            archive::Input::Bytes(_) => "the archive".to_string()
+        };
+        let show = if name.is_empty() { source } else { format!("{} of {}", name, source) };
+        return Err(Error::Validation(format!("No drones with samples in {}", show)));
+    }
+    Ok(shows)
+}
+
+fn read_shows(input: archive::Input, options: &ConvertOptions) -> Result<Vec<Named>> {
+    let format = input::detect(input, options);
+    let folders = format.acts(input)?;
+    if folders.len() <= 1 {
+        return Ok(vec![(String::new(), read_drones(&*format, input, None, options)?, vec![])]);
+    }
+    let names: Vec<&str> = folders.iter().map(|folder| archive::basename(folder)).collect();
+    println!("Found {} shows: {}", folders.len(), names.join(", "));
+
+    let mut acts = vec![];
+    for (folder, name) in folders.iter().zip(names) {
+        println!("Reading {}", name);
+        acts.push((name.to_string(), read_drones(&*format, input, Some(folder), options)?));
+    }
+    if options.merge_acts {
+        let sources: Vec<String> = folders.iter().map(|folder| match input {
+            archive::Input::Path(fname) => fname.join(folder).display().to_string(),
+            archive::Input::Bytes(_) => folder.clone()
+        }).collect();
+        let (drones, acts) = merge(acts, &sources);
+        return Ok(vec![(String::new(), drones, acts)]);
+    }
+    Ok(acts.into_iter().map(|(name, drones)| (name, drones, vec![])).collect())
+}
+
+/// The acts playing between `from` and `to` seconds of show time, cut to that window and
+/// timed from its start.
+pub fn clip(acts: &[Act], from: f32, to: f32) -> Vec<Act> {
+    acts.iter()
+        .filter(|act| act.end > from && act.start < to)
+        .map(|act| Act { start: act.start.max(from) - from, end: act.end.min(to) - from, ..act.clone() })
+        .collect()
+}
+
+/// Distance a drone may jump between the end of one act and the start of the next without
+/// a warning (meters).
+const SEAM_TOLERANCE: f32 = 0.1;
+
+/// Plays the acts one after another: each starts when the last drone of the one before has
+/// finished. A drone missing from an act holds its last position through it, and one that
+/// only joins in a later act waits at its first position from the start of the show.
+/// Returns the drones and when each act (read from `sources`) plays.
+pub fn merge(acts: Vec<(String, Vec<Drone>)>, sources: &[String]) -> (Vec<Drone>, Vec<Act>) {
+    let mut merged: Vec<Drone> = vec![];
+    let mut played_acts = vec![];
+    let (mut show_start, mut end) = (None, None);
+    for ((name, drones), source) in acts.into_iter().zip(sources) {
+        let Some(start) = drones.iter().map(|(_, samples)| samples[0].t).min_by(f64::total_cmp) else {
+            continue;
+        };
+        let offset = end.map_or(0.0, |end| end - start);
+        let show_start = *show_start.get_or_insert(start);
+        let mut act_end = start + offset;
+        for (drone_id, samples) in drones {
+            let index = match merged.iter().position(|(id, _)| *id == drone_id) {
+                Some(index) => index,
+                None => {
+                    merged.push((drone_id, vec![samples[0].with_time(show_start)]));
+                    merged.len() - 1
+                }
+            };
+            let played = &mut merged[index].1;
+            let gap = track::distance(played.last().unwrap().pos, samples[0].pos);
+            if gap > SEAM_TOLERANCE && played.len() > 1 {
+                progress::warning(format!("drone {} jumps {:.2} m at the start of {}", drone_id, gap, name));
+            }
+            for sample in samples {
+                let time = sample.t + offset;
+                // An act picks up exactly where the previous one ended; its first sample wins.
+                if played.last().is_some_and(|last| last.t >= time) {
+                    played.pop();
+                }
+                played.push(sample.with_time(time));
+            }
+            act_end = act_end.max(played.last().unwrap().t);
+        }
+        played_acts.push(Act {
+            name,
+            start: ((start + offset - show_start) / 1000.0) as f32,
+            end: ((act_end - show_start) / 1000.0) as f32,
+            source: source.clone()
+        });
+        end = Some(act_end);
+    }
+    merged.sort_by_key(|(drone_id, _)| *drone_id);
+    (merged, played_acts)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use crate::{convert_bytes, error::Error};
+
+    fn zip(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        for (name, contents) in entries {
+            writer.start_file(*name, zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored)).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn header_only_csv_leaves_no_drones() {
+        let archive = zip(&[("Drone 1.csv", "Time [msec],x [m],y [m],z [m],Red,Green,Blue\n")]);
+        match convert_bytes(&archive, r#"{"lenient": true}"#) {
+            Err(Error::Validation(message)) => assert_eq!(message, "No drones with samples in the archive"),
+            other => panic!("expected a validation error, got {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn archive_without_drone_csvs_leaves_no_drones() {
+        let archive = zip(&[("readme.txt", "hello\n")]);
+        assert!(matches!(convert_bytes(&archive, ""), Err(Error::Validation(_))));
+    }
+}
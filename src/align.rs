@@ -0,0 +1,318 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use clap::Args;
+
+use crate::{error::{self, Error, Result}, field, read_show, write_show, Show};
+
+#[derive(Args, Debug)]
+pub struct AlignArgs {
+
+    /// Converted show whose home positions are the designed pad layout
+    designed: PathBuf,
+
+    /// CSV of `id, x, y, z` surveyed pad positions, in the same axis layout as the trajectory CSVs
+    surveyed: PathBuf,
+
+    /// Also solve for a uniform scale factor
+    #[arg(long)]
+    scale: bool,
+
+    /// Output file (defaults to `<designed>.aligned.vviz`)
+    #[arg(short, long)]
+    output: Option<PathBuf>
+}
+
+type Vec3 = [f64; 3];
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn centroid(points: &[Vec3]) -> Vec3 {
+    let n = points.len() as f64;
+    let sum = points.iter().fold([0.0; 3], |acc, p| [acc[0] + p[0], acc[1] + p[1], acc[2] + p[2]]);
+    [sum[0] / n, sum[1] / n, sum[2] / n]
+}
+
+/// A similarity transform `p -> scale * rotation * p + translation`.
+#[derive(Debug, Clone, Copy)]
+pub struct RigidTransform {
+    pub rotation: [[f64; 3]; 3],
+    pub translation: Vec3,
+    pub scale: f64
+}
+
+impl RigidTransform {
+
+    pub fn rotate(&self, p: Vec3) -> Vec3 {
+        let r = &self.rotation;
+        [
+            self.scale * dot(r[0], p),
+            self.scale * dot(r[1], p),
+            self.scale * dot(r[2], p)
+        ]
+    }
+
+    pub fn apply(&self, p: Vec3) -> Vec3 {
+        let rotated = self.rotate(p);
+        [rotated[0] + self.translation[0], rotated[1] + self.translation[1], rotated[2] + self.translation[2]]
+    }
+
+    /// Rotation angle in degrees about the transform's axis.
+    pub fn angle(&self) -> f64 {
+        let r = &self.rotation;
+        ((r[0][0] + r[1][1] + r[2][2] - 1.0) / 2.0).clamp(-1.0, 1.0).acos().to_degrees()
+    }
+}
+
+/// Eigen-decomposition of a symmetric 4x4 matrix by cyclic Jacobi rotations. Returns the
+/// eigenvector of the largest eigenvalue.
+#[allow(clippy::needless_range_loop)]
+fn dominant_eigenvector(mut a: [[f64; 4]; 4]) -> [f64; 4] {
+    let mut v = [[0.0; 4]; 4];
+    for (i, row) in v.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+    for _ in 0..100 {
+        let off: f64 = (0..4).flat_map(|i| (0..4).filter(move |j| *j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[i][j] * a[i][j]).sum();
+        if off < 1e-24 {
+            break;
+        }
+        for p in 0..4 {
+            for q in p + 1..4 {
+                if a[p][q].abs() < 1e-30 {
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for k in 0..4 {
+                    let (akp, akq) = (a[k][p], a[k][q]);
+                    a[k][p] = c * akp - s * akq;
+                    a[k][q] = s * akp + c * akq;
+                }
+                for k in 0..4 {
+                    let (apk, aqk) = (a[p][k], a[q][k]);
+                    a[p][k] = c * apk - s * aqk;
+                    a[q][k] = s * apk + c * aqk;
+                }
+                for row in v.iter_mut() {
+                    let (vkp, vkq) = (row[p], row[q]);
+                    row[p] = c * vkp - s * vkq;
+                    row[q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+    let best = (0..4).max_by(|i, j| a[*i][*i].total_cmp(&a[*j][*j])).unwrap();
+    [v[0][best], v[1][best], v[2][best], v[3][best]]
+}
+
+/// Least-squares transform mapping `from[i]` onto `to[i]` (Horn's quaternion method).
+pub fn register(from: &[Vec3], to: &[Vec3], with_scale: bool) -> RigidTransform {
+    let (from_center, to_center) = (centroid(from), centroid(to));
+    let mut s = [[0.0; 3]; 3];
+    for (p, q) in from.iter().zip(to) {
+        let (p, q) = (sub(*p, from_center), sub(*q, to_center));
+        for i in 0..3 {
+            for j in 0..3 {
+                s[i][j] += p[i] * q[j];
+            }
+        }
+    }
+
+    let n = [
+        [s[0][0] + s[1][1] + s[2][2], s[1][2] - s[2][1], s[2][0] - s[0][2], s[0][1] - s[1][0]],
+        [s[1][2] - s[2][1], s[0][0] - s[1][1] - s[2][2], s[0][1] + s[1][0], s[2][0] + s[0][2]],
+        [s[2][0] - s[0][2], s[0][1] + s[1][0], -s[0][0] + s[1][1] - s[2][2], s[1][2] + s[2][1]],
+        [s[0][1] - s[1][0], s[2][0] + s[0][2], s[1][2] + s[2][1], -s[0][0] - s[1][1] + s[2][2]]
+    ];
+    let [w, x, y, z] = dominant_eigenvector(n);
+    let norm = (w * w + x * x + y * y + z * z).sqrt();
+    let (w, x, y, z) = (w / norm, x / norm, y / norm, z / norm);
+    let rotation = [
+        [w * w + x * x - y * y - z * z, 2.0 * (x * y - w * z), 2.0 * (x * z + w * y)],
+        [2.0 * (x * y + w * z), w * w - x * x + y * y - z * z, 2.0 * (y * z - w * x)],
+        [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), w * w - x * x - y * y + z * z]
+    ];
+
+    fit(rotation, from, to, with_scale)
+}
+
+/// Least-squares transform mapping `from[i]` onto `to[i]` that only turns about the vertical
+/// (y) axis, for pads in a line, where any tilt about the line would fit as well.
+pub fn register_yaw(from: &[Vec3], to: &[Vec3], with_scale: bool) -> RigidTransform {
+    let (from_center, to_center) = (centroid(from), centroid(to));
+    let (mut cos, mut sin) = (0.0, 0.0);
+    for (p, q) in from.iter().zip(to) {
+        let (p, q) = (sub(*p, from_center), sub(*q, to_center));
+        cos += q[0] * p[0] + q[2] * p[2];
+        sin += q[0] * p[2] - q[2] * p[0];
+    }
+    let angle = sin.atan2(cos);
+    let (sin, cos) = angle.sin_cos();
+    let rotation = [
+        [cos, 0.0, sin],
+        [0.0, 1.0, 0.0],
+        [-sin, 0.0, cos]
+    ];
+    fit(rotation, from, to, with_scale)
+}
+
+/// Whether the points lie on one line (or on one spot), within a thousandth of their extent.
+pub fn collinear(points: &[Vec3]) -> bool {
+    let center = centroid(points);
+    let offsets: Vec<Vec3> = points.iter().map(|p| sub(*p, center)).collect();
+    let Some(far) = offsets.iter().copied().max_by(|a, b| dot(*a, *a).total_cmp(&dot(*b, *b))) else {
+        return true;
+    };
+    let extent = dot(far, far).sqrt();
+    if extent == 0.0 {
+        return true;
+    }
+    let axis = far.map(|v| v / extent);
+    offsets.iter().all(|p| {
+        let along = dot(*p, axis);
+        let across = sub(*p, axis.map(|v| v * along));
+        dot(across, across).sqrt() <= extent * 1e-3
+    })
+}
+
+/// Completes `rotation` with the scale (when asked for) and translation best mapping `from`
+/// onto `to`.
+fn fit(rotation: [[f64; 3]; 3], from: &[Vec3], to: &[Vec3], with_scale: bool) -> RigidTransform {
+    let (from_center, to_center) = (centroid(from), centroid(to));
+    let from_spread: f64 = from.iter().map(|p| sub(*p, from_center)).map(|p| dot(p, p)).sum();
+    let mut transform = RigidTransform { rotation, translation: [0.0; 3], scale: 1.0 };
+    if with_scale && from_spread > 0.0 {
+        let projected: f64 = from.iter().zip(to)
+            .map(|(p, q)| dot(sub(*q, to_center), transform.rotate(sub(*p, from_center))))
+            .sum();
+        transform.scale = projected / from_spread;
+    }
+    transform.translation = sub(to_center, transform.rotate(from_center));
+    transform
+}
+
+/// Reads surveyed pad positions keyed by drone number, converted to vviz axes (y up).
+pub fn read_surveyed(fname: &std::path::Path) -> Result<HashMap<usize, Vec3>> {
+    let mut reader = csv::Reader::from_path(fname)
+        .map_err(error::csv("Failed to open surveyed homes file"))?;
+    reader.records().map(|record| {
+        let record = record.map_err(error::csv("Failed to read surveyed homes file"))?;
+        let file = fname.display().to_string();
+        let value = |i: usize, column: &str| {
+            let cell = field::cell(&record, i, column, &file)?;
+            field::parse::<f64>(cell)
+                .map_err(|_| Error::Parse(format!("Invalid value '{}' in surveyed homes file.", cell)))
+        };
+        let id = field::cell(&record, 0, "id", &file)?;
+        let id = field::parse::<usize>(id)
+            .map_err(|_| Error::Parse(format!("Invalid drone id '{}' in surveyed homes file.", id)))?;
+        Ok((id, [value(1, "x")?, value(3, "z")?, value(2, "y")?]))
+    }).collect()
+}
+
+/// Computes the transform best mapping the show's homes onto the surveyed pads and applies
+/// it to every performance. Traversal deltas only need the rotation and scale.
+pub fn align(args: AlignArgs) -> Result<()> {
+    let mut show: Show = read_show(&args.designed)?;
+    let surveyed = read_surveyed(&args.surveyed)?;
+
+    let mut designed_homes = vec![];
+    let mut surveyed_homes = vec![];
+    for performance in &show.performances {
+        let description = &performance.description;
+        match surveyed.get(&(performance.id + 1)) {
+            Some(pad) => {
+                designed_homes.push([description.home_x as f64, description.home_y as f64, description.home_z as f64]);
+                surveyed_homes.push(*pad);
+            }
+            None => println!("Warning: no surveyed position for drone {}", performance.id + 1)
+        }
+    }
+    for id in surveyed.keys() {
+        if !show.performances.iter().any(|p| p.id + 1 == *id) {
+            println!("Warning: surveyed position for drone {} which is not in the show", id);
+        }
+    }
+    if designed_homes.len() < 3 {
+        return Err(Error::Validation("At least three drones need surveyed positions to compute an alignment.".into()));
+    }
+
+    // Homes in a line pin down the heading but not a tilt about the line, which a full fit
+    // would pick arbitrarily, up to turning the show upside down.
+    let transform = if collinear(&designed_homes) || collinear(&surveyed_homes) {
+        println!("Warning: the homes lie in a line; fitting the heading only");
+        register_yaw(&designed_homes, &surveyed_homes, args.scale)
+    } else {
+        register(&designed_homes, &surveyed_homes, args.scale)
+    };
+
+    let residuals: Vec<f64> = designed_homes.iter().zip(&surveyed_homes)
+        .map(|(p, q)| {
+            let d = sub(transform.apply(*p), *q);
+            dot(d, d)
+        }).collect();
+    println!(
+        "Rotation {:.3} deg, translation ({:.3}, {:.3}, {:.3}), scale {:.5}",
+        transform.angle(), transform.translation[0], transform.translation[1], transform.translation[2], transform.scale
+    );
+    println!(
+        "RMS residual {:.3} m, max {:.3} m over {} drones",
+        (residuals.iter().sum::<f64>() / residuals.len() as f64).sqrt(),
+        residuals.iter().cloned().fold(0.0, f64::max).sqrt(),
+        residuals.len()
+    );
+
+    for performance in show.performances.iter_mut() {
+        let description = &mut performance.description;
+        let home = transform.apply([description.home_x as f64, description.home_y as f64, description.home_z as f64]);
+        description.home_x = home[0] as f32;
+        description.home_y = home[1] as f32;
+        description.home_z = home[2] as f32;
+        for traversal in description.traversals.0.iter_mut() {
+            let delta = transform.rotate([traversal.dx as f64, traversal.dy as f64, traversal.dz as f64]);
+            traversal.dx = delta[0] as f32;
+            traversal.dy = delta[1] as f32;
+            traversal.dz = delta[2] as f32;
+            if let Some(points) = traversal.control_points.as_mut() {
+                *points = points.map(|point| transform.rotate(point.map(f64::from)).map(|v| v as f32));
+            }
+        }
+    }
+
+    let output = args.output.unwrap_or_else(|| args.designed.with_extension("aligned.vviz"));
+    println!("Generating {}", output.to_str().unwrap());
+    write_show(&show, &output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collinear, register_yaw};
+
+    #[test]
+    fn pads_in_a_row_keep_the_show_upright() {
+        let designed = [[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [4.0, 0.0, 0.0], [6.0, 0.0, 0.0]];
+        let surveyed = designed.map(|[x, y, z]| [10.0 - z, y, 5.0 + x]);
+        assert!(collinear(&designed));
+        let transform = register_yaw(&designed, &surveyed, false);
+        let up = transform.rotate([0.0, 0.5, 0.0]);
+        assert!((up[1] - 0.5).abs() < 1e-9);
+        for (p, q) in designed.iter().zip(&surveyed) {
+            let fitted = transform.apply(*p);
+            assert!((0..3).all(|i| (fitted[i] - q[i]).abs() < 1e-9), "{:?} != {:?}", fitted, q);
+        }
+    }
+
+    #[test]
+    fn a_grid_is_not_collinear() {
+        assert!(!collinear(&[[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 0.0, 2.0]]));
+    }
+}
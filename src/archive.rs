@@ -0,0 +1,215 @@
+use std::{fs::File, io::{BufReader, Read, Seek}, path::{Path, PathBuf}};
+
+use crate::error::{self, Error, Result};
+
+mod mmap;
+mod tar;
+
+/// Supported inputs: archives, a directory of drone CSVs (or `.ulg` logs), or a single drone
+/// CSV or `.ulg` log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Zip,
+    Tar,
+    TarGz,
+    Directory,
+    Csv,
+    Ulog
+}
+
+impl Format {
+
+    /// Detects the container from its leading bytes, falling back to the file extension.
+    pub fn detect(fname: &Path) -> Option<Format> {
+        if fname.is_dir() {
+            return Some(Format::Directory);
+        }
+        let mut magic = [0u8; 262];
+        let read = File::open(fname).and_then(|mut file| {
+            let mut total = 0;
+            while total < magic.len() {
+                match file.read(&mut magic[total..])? {
+                    0 => break,
+                    n => total += n
+                }
+            }
+            Ok(total)
+        }).unwrap_or(0);
+        if let Some(format) = Format::sniff(&magic[..read]) {
+            return Some(format);
+        }
+        if magic[..read].starts_with(b"ULog") {
+            return Some(Format::Ulog);
+        }
+        let name = fname.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(Format::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Format::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Format::Tar)
+        } else if name.ends_with(".csv") {
+            Some(Format::Csv)
+        } else if name.ends_with(".ulg") {
+            Some(Format::Ulog)
+        } else {
+            None
+        }
+    }
+
+    /// Detects the container from the first 262 bytes (or fewer) of its contents.
+    pub fn sniff(magic: &[u8]) -> Option<Format> {
+        if magic.starts_with(b"PK\x03\x04") || magic.starts_with(b"PK\x05\x06") {
+            Some(Format::Zip)
+        } else if magic.starts_with(&[0x1f, 0x8b]) {
+            Some(Format::TarGz)
+        } else if magic.len() >= 262 && &magic[257..262] == b"ustar" {
+            Some(Format::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// The last path component of an entry name, so `ShowName/Drone 1.csv` matches like
+/// `Drone 1.csv`. Both `/` and `\` separators are accepted.
+pub fn basename(name: &str) -> &str {
+    name.rsplit(['/', '\\']).next().unwrap_or(name)
+}
+
+/// Everything before the last path component (`ActOne` for `ActOne/Drone 1.csv`), or
+/// an empty string for entries at the top level.
+pub fn parent(name: &str) -> &str {
+    name.rfind(['/', '\\']).map_or("", |index| &name[..index])
+}
+
+/// `show.zip`, `show.tar`, `show.tar.gz` and a `show` directory all become `show.vviz`.
+pub fn output_path(fname: &Path) -> PathBuf {
+    let name = fname.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    let lower = name.to_ascii_lowercase();
+    for suffix in [".tar.gz", ".tgz", ".tar", ".zip"] {
+        if lower.ends_with(suffix) {
+            return fname.with_file_name(format!("{}.vviz", &name[..name.len() - suffix.len()]));
+        }
+    }
+    fname.with_extension("vviz")
+}
+
+/// Where a show's drone CSVs are read from.
+#[derive(Debug, Clone, Copy)]
+pub enum Input<'a> {
+    Path(&'a Path),
+    /// An archive held in memory.
+    Bytes(&'a [u8])
+}
+
+impl Input<'_> {
+
+    pub fn for_each_entry(self, f: impl FnMut(&str, &mut dyn Read) -> Result<()>) -> Result<()> {
+        match self {
+            Input::Path(fname) => for_each_entry(fname, f),
+            Input::Bytes(bytes) => {
+                let format = Format::sniff(&bytes[..bytes.len().min(262)])
+                    .ok_or_else(|| Error::Format("Invalid file format: not a zip or tar archive".into()))?;
+                for_each_entry_in(std::io::Cursor::new(bytes), format, "archive", f)
+            }
+        }
+    }
+}
+
+/// Calls `f` with the name and a streaming reader for every file entry of the archive at
+/// `fname`, in archive order, stopping at the first error. Directory entries are skipped.
+/// Entries are decompressed as they are read rather than buffered, and ZIP64 archives (more
+/// than 65535 entries or entries over 4 GiB) are supported.
+///
+/// A directory is read like an archive of its `.csv` and `.ulg` files, in name order, and a
+/// single file like an archive holding only that file. Plain files are memory-mapped.
+pub fn for_each_entry(fname: &Path, mut f: impl FnMut(&str, &mut dyn Read) -> Result<()>) -> Result<()> {
+    let Some(format) = Format::detect(fname) else {
+        return Err(Error::Format(format!("Invalid file format: {}", fname.display())));
+    };
+    match format {
+        Format::Directory => {
+            for path in directory_files(fname)? {
+                for_each_mapped(&path, &mut f)?;
+            }
+            Ok(())
+        }
+        Format::Csv | Format::Ulog => for_each_mapped(fname, &mut f),
+        format => {
+            let file = File::open(fname).map_err(error::io("Failed to open archive"))?;
+            for_each_entry_in(BufReader::new(file), format, &fname.display().to_string(), f)
+        }
+    }
+}
+
+/// The `.csv` and `.ulg` files of the directory, in name order.
+fn directory_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir).map_err(error::io("Failed to read input directory"))? {
+        let path = entry.map_err(error::io("Failed to read input directory"))?.path();
+        let is_log = path.extension().is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("csv") || extension.eq_ignore_ascii_case("ulg")
+        });
+        if is_log && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// How many file entries [`for_each_entry`] will find in the input at `fname`, when that is
+/// known without reading it through: tarballs have no index.
+pub fn count_entries(fname: &Path) -> Option<usize> {
+    match Format::detect(fname)? {
+        Format::Directory => directory_files(fname).ok().map(|files| files.len()),
+        Format::Csv | Format::Ulog => Some(1),
+        Format::Zip => {
+            let archive = zip::ZipArchive::new(BufReader::new(File::open(fname).ok()?)).ok()?;
+            Some(archive.file_names().filter(|name| !name.ends_with('/')).count())
+        }
+        Format::Tar | Format::TarGz => None
+    }
+}
+
+fn for_each_mapped(fname: &Path, f: &mut dyn FnMut(&str, &mut dyn Read) -> Result<()>) -> Result<()> {
+    let map = mmap::Mmap::open(fname)
+        .map_err(|e| Error::Io(format!("Failed to read {}: {}", fname.display(), e)))?;
+    let name = fname.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    f(&name, &mut &map[..])
+}
+
+/// [`for_each_entry`] over an archive already open as `reader`; `label` names it in errors.
+pub fn for_each_entry_in(
+    mut reader: impl Read + Seek, format: Format, label: &str, mut f: impl FnMut(&str, &mut dyn Read) -> Result<()>
+) -> Result<()> {
+    match format {
+        Format::Zip => for_each_zip_entry(reader, label, &mut f),
+        Format::Tar => tar::for_each_entry(&mut reader, &mut f),
+        Format::TarGz => {
+            let mut decoder = flate2::read::MultiGzDecoder::new(reader);
+            tar::for_each_entry(&mut decoder, &mut f)
+        }
+        Format::Directory | Format::Csv | Format::Ulog => unreachable!("{:?} inputs are not read from a stream", format)
+    }
+}
+
+fn for_each_zip_entry(
+    reader: impl Read + Seek, label: &str, f: &mut dyn FnMut(&str, &mut dyn Read) -> Result<()>
+) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(reader)
+        .map_err(error::zip("Failed to read zip archive"))?;
+
+    for file_index in 0..archive.len() {
+        let context = format!("Failed to read entry {} of {}", file_index, label);
+        let mut file = archive.by_index(file_index)
+            .map_err(error::zip(&context))?;
+        if file.is_dir() {
+            continue;
+        }
+        let name = file.name().to_string();
+        f(&name, &mut file)?;
+    }
+    Ok(())
+}
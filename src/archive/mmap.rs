@@ -0,0 +1,85 @@
+//! Read-only memory maps for plain CSV inputs, so multi-hundred-MB logs are parsed straight
+//! from the page cache instead of being copied into memory first. The file must not be
+//! truncated while it is mapped.
+
+use std::{io, ops::Deref, path::Path};
+
+#[cfg(unix)]
+pub struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize
+}
+
+#[cfg(unix)]
+impl Mmap {
+
+    pub fn open(fname: &Path) -> io::Result<Mmap> {
+        use std::os::unix::io::AsRawFd;
+
+        let file = std::fs::File::open(fname)?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "file too large to map"))?;
+        // Zero-length mappings are rejected by the kernel.
+        if len == 0 {
+            return Ok(Mmap { ptr: std::ptr::null_mut(), len: 0 });
+        }
+        // SAFETY: a fresh private read-only mapping of an open file; the descriptor may be
+        // closed once mmap returns.
+        let ptr = unsafe {
+            libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `ptr` and `len` describe the mapping created above. The hint is advisory.
+        unsafe {
+            libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+        }
+        Ok(Mmap { ptr, len })
+    }
+}
+
+#[cfg(unix)]
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        // SAFETY: the mapping stays valid and readable until `drop`.
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            // SAFETY: unmaps exactly the region mapped in `open`.
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
+
+/// Elsewhere the file is read into memory.
+#[cfg(not(unix))]
+pub struct Mmap(Vec<u8>);
+
+#[cfg(not(unix))]
+impl Mmap {
+    pub fn open(fname: &Path) -> io::Result<Mmap> {
+        std::fs::read(fname).map(Mmap)
+    }
+}
+
+#[cfg(not(unix))]
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
@@ -0,0 +1,97 @@
+//! Minimal streaming reader for ustar/GNU/pax tar archives: regular files only, with GNU
+//! long names and pax `path` records honoured.
+
+use std::io::{self, Read};
+
+use crate::error::{Error, Result};
+
+const BLOCK: usize = 512;
+
+fn field(header: &[u8]) -> String {
+    let end = header.iter().position(|b| *b == 0).unwrap_or(header.len());
+    String::from_utf8_lossy(&header[..end]).into_owned()
+}
+
+fn size(header: &[u8]) -> io::Result<u64> {
+    let raw = &header[124..136];
+    // GNU base-256 encoding for sizes of 8 GiB and over.
+    if raw[0] & 0x80 != 0 {
+        return Ok(raw[1..].iter().fold(0u64, |acc, b| (acc << 8) | *b as u64));
+    }
+    let text = field(raw);
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("invalid tar entry size '{}'", text)))
+}
+
+fn read_data(reader: &mut dyn Read, size: u64) -> io::Result<Vec<u8>> {
+    let mut data = vec![];
+    reader.take(size).read_to_end(&mut data)?;
+    skip_padding(reader, size)?;
+    Ok(data)
+}
+
+fn skip_padding(reader: &mut dyn Read, size: u64) -> io::Result<()> {
+    let padding = (BLOCK as u64 - size % BLOCK as u64) % BLOCK as u64;
+    io::copy(&mut reader.take(padding), &mut io::sink())?;
+    Ok(())
+}
+
+/// `path` from a pax extended header (`<len> path=<value>\n` records).
+fn pax_path(data: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(data);
+    text.lines().find_map(|line| {
+        let (_, record) = line.split_once(' ')?;
+        record.strip_prefix("path=").map(str::to_string)
+    })
+}
+
+/// Malformed headers are format errors; anything else went wrong reading the file.
+fn tar_error(e: io::Error) -> Error {
+    if e.kind() == io::ErrorKind::InvalidData {
+        Error::Format(format!("Invalid tar archive: {}", e))
+    } else {
+        Error::Io(format!("Failed to read tar archive: {}", e))
+    }
+}
+
+pub fn for_each_entry(reader: &mut dyn Read, f: &mut dyn FnMut(&str, &mut dyn Read) -> Result<()>) -> Result<()> {
+    let mut long_name: Option<String> = None;
+    let mut header = [0u8; BLOCK];
+    loop {
+        if let Err(e) = reader.read_exact(&mut header) {
+            // Some writers omit the trailing zero blocks.
+            return if e.kind() == io::ErrorKind::UnexpectedEof { Ok(()) } else { Err(tar_error(e)) };
+        }
+        if header.iter().all(|b| *b == 0) {
+            return Ok(());
+        }
+        let size = size(&header).map_err(tar_error)?;
+        match header[156] {
+            b'L' => long_name = Some(field(&read_data(reader, size).map_err(tar_error)?)),
+            b'x' => long_name = pax_path(&read_data(reader, size).map_err(tar_error)?).or(long_name),
+            b'0' | 0 | b'7' => {
+                let name = long_name.take().unwrap_or_else(|| {
+                    let (name, prefix) = (field(&header[..100]), field(&header[345..500]));
+                    if &header[257..262] == b"ustar" && !prefix.is_empty() {
+                        format!("{}/{}", prefix, name)
+                    } else {
+                        name
+                    }
+                });
+                let mut entry = reader.take(size);
+                f(&name, &mut entry)?;
+                io::copy(&mut entry, &mut io::sink()).map_err(tar_error)?;
+                skip_padding(reader, size).map_err(tar_error)?;
+            }
+            _ => {
+                long_name = None;
+                io::copy(&mut reader.take(size), &mut io::sink()).map_err(tar_error)?;
+                skip_padding(reader, size).map_err(tar_error)?;
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::{error::{self, Result}, ColorAction, Sample};
+
+/// One drone's keyframes, in seconds from the start of its performance.
+#[derive(Serialize)]
+pub struct DroneKeys {
+    name: String,
+    /// `[t, x, y, z]`, Blender's Z-up axes (east, north, up).
+    locations: Vec<[f32; 4]>,
+    /// `[t, r, g, b]` with 8-bit channels, held until the next key.
+    colors: Vec<[f32; 4]>
+}
+
+impl DroneKeys {
+
+    /// Keys for a drone's transformed samples. Colors come from its light payload when it has
+    /// one, else from the CSV color columns.
+    pub fn new(drone_id: usize, samples: &[Sample], actions: Option<&[ColorAction]>, color_rate: f32) -> Self {
+        let start = samples.first().map(|sample| sample.t).unwrap_or_default();
+        let seconds = |sample: &Sample| ((sample.t - start) / 1000.0) as f32;
+        let locations = samples.iter()
+            .map(|sample| {
+                let [x, y, z] = sample.pos;
+                [seconds(sample), x, y, z]
+            })
+            .collect();
+
+        let mut colors: Vec<[f32; 4]> = vec![];
+        match actions {
+            Some(actions) => {
+                let mut frame = 0;
+                for action in actions {
+                    colors.push([frame as f32 / color_rate, action.r as f32, action.g as f32, action.b as f32]);
+                    frame += action.frames.unwrap_or(1);
+                }
+            }
+            None => for sample in samples {
+                let color = sample.rgb.map(f32::from);
+                if colors.last().is_none_or(|last| last[1..] != color) {
+                    colors.push([seconds(sample), color[0], color[1], color[2]]);
+                }
+            }
+        }
+        DroneKeys { name: format!("Drone {}", drone_id), locations, colors }
+    }
+}
+
+const SCRIPT: &str = r#"
+import bmesh
+import bpy
+
+FPS = 24
+RADIUS = 0.15
+STRENGTH = 5.0
+
+scene = bpy.context.scene
+scene.render.fps = FPS
+collection = bpy.data.collections.new("Drones")
+scene.collection.children.link(collection)
+
+bm = bmesh.new()
+bmesh.ops.create_uvsphere(bm, u_segments=12, v_segments=8, radius=RADIUS)
+mesh = bpy.data.meshes.new("Drone")
+bm.to_mesh(mesh)
+bm.free()
+mesh.materials.append(None)
+
+preferences = bpy.context.preferences.edit
+interpolation = preferences.keyframe_new_interpolation_type
+end = 0
+for drone in DRONES:
+    obj = bpy.data.objects.new(drone["name"], mesh)
+    collection.objects.link(obj)
+
+    material = bpy.data.materials.new(drone["name"])
+    material.use_nodes = True
+    nodes = material.node_tree.nodes
+    nodes.clear()
+    emission = nodes.new("ShaderNodeEmission")
+    emission.inputs["Strength"].default_value = STRENGTH
+    output = nodes.new("ShaderNodeOutputMaterial")
+    material.node_tree.links.new(emission.outputs[0], output.inputs[0])
+    obj.material_slots[0].link = "OBJECT"
+    obj.material_slots[0].material = material
+
+    preferences.keyframe_new_interpolation_type = "LINEAR"
+    for t, x, y, z in drone["locations"]:
+        obj.location = (x, y, z)
+        obj.keyframe_insert("location", frame=t * FPS)
+        end = max(end, t * FPS)
+
+    preferences.keyframe_new_interpolation_type = "CONSTANT"
+    color = emission.inputs["Color"]
+    for t, r, g, b in drone["colors"]:
+        # 8-bit sRGB to Blender's linear color.
+        color.default_value = tuple((c / 255) ** 2.2 for c in (r, g, b)) + (1.0,)
+        color.keyframe_insert("default_value", frame=t * FPS)
+
+preferences.keyframe_new_interpolation_type = interpolation
+scene.frame_start = 0
+scene.frame_end = int(end) + 1
+"#;
+
+/// Writes a Blender Python script that builds an emissive sphere per drone with its location
+/// and color keyframes. Run it with `blender --python show.py` or from Blender's text editor.
+pub fn write(drones: &[DroneKeys], fname: &Path) -> Result<()> {
+    let data = serde_json::to_string(drones).expect("Failed to serialize Blender keyframes.");
+    let script = format!("# Generated by csv2vviz: one emissive sphere per drone.\n\nDRONES = {}\n{}", data, SCRIPT);
+    std::fs::write(fname, script).map_err(error::io("Failed to write Blender script"))?;
+    let keys: usize = drones.iter().map(|drone| drone.locations.len() + drone.colors.len()).sum();
+    println!("Wrote {} keyframes for {} drones to {}", keys, drones.len(), fname.display());
+    Ok(())
+}
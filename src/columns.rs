@@ -0,0 +1,416 @@
+//! Which trajectory CSV column holds which field, for exports that don't write Skybrush's
+//! `t, x, y, z, r, g, b` order.
+
+use std::{fmt, str::FromStr};
+
+use clap::{Args, ValueEnum};
+use csv::StringRecord;
+
+use crate::{error::{Error, Result}, field, Sample};
+
+/// Field names in the order records are converted to.
+const FIELDS: [&str; 7] = ["t", "x", "y", "z", "r", "g", "b"];
+
+/// Header names (units stripped, lowercase) that identify each field.
+const HEADER_NAMES: [&[&str]; 7] = [
+    &["t", "time"],
+    &["x"],
+    &["y"],
+    &["z"],
+    &["r", "red"],
+    &["g", "green"],
+    &["b", "blue"]
+];
+
+/// Rows looked at when inferring the layout from the data.
+const SAMPLE_ROWS: usize = 100;
+
+/// Input column of each field, in [`FIELDS`] order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Columns([usize; 7]);
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns([0, 1, 2, 3, 4, 5, 6])
+    }
+}
+
+/// `t,x,y,z,r,g,b` naming every input column in order; `_` skips one.
+impl FromStr for Columns {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut indices = [None; 7];
+        for (column, name) in s.split(',').enumerate() {
+            let name = name.trim().to_ascii_lowercase();
+            if name == "_" {
+                continue;
+            }
+            let field = HEADER_NAMES.iter().position(|names| names.contains(&name.as_str()))
+                .ok_or_else(|| format!("unknown column '{}' (expected t, x, y, z, r, g, b or _)", name))?;
+            if indices[field].replace(column).is_some() {
+                return Err(format!("column '{}' is given twice", FIELDS[field]));
+            }
+        }
+        match indices.iter().position(Option::is_none) {
+            Some(missing) => Err(format!("no '{}' column", FIELDS[missing])),
+            None => Ok(Columns(indices.map(Option::unwrap)))
+        }
+    }
+}
+
+impl fmt::Display for Columns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = (0..self.width())
+            .map(|column| self.0.iter().position(|&index| index == column).map_or("_", |field| FIELDS[field]))
+            .collect();
+        f.write_str(&names.join(","))
+    }
+}
+
+/// Column orders written by the exporter's schema versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum CsvLayout {
+    /// `t,x,y,z,r,g,b`
+    V1,
+    /// `t,x,y,z,yaw,b,g,r`; the yaw is not used
+    V2,
+    /// From the header row if it names the columns, otherwise from the data
+    #[default]
+    Auto
+}
+
+impl CsvLayout {
+
+    const VERSIONS: [CsvLayout; 2] = [CsvLayout::V1, CsvLayout::V2];
+
+    pub fn columns(&self) -> Option<Columns> {
+        match self {
+            CsvLayout::V1 => Some(Columns::default()),
+            CsvLayout::V2 => Some(Columns([0, 1, 2, 3, 7, 6, 5])),
+            CsvLayout::Auto => None
+        }
+    }
+
+    /// The version the data fits, preferring one exactly as wide as the rows.
+    pub fn matching(records: &[StringRecord]) -> Option<CsvLayout> {
+        let width = records.iter().map(StringRecord::len).min()?;
+        let fitting: Vec<CsvLayout> = Self::VERSIONS.into_iter()
+            .filter(|version| version.columns().unwrap().fits(records))
+            .collect();
+        fitting.iter().copied().find(|version| version.columns().unwrap().width() == width)
+            .or(fitting.first().copied())
+    }
+
+    /// `columns`, naming the version it matches.
+    pub fn describe(columns: &Columns) -> String {
+        match Self::VERSIONS.into_iter().find(|version| version.columns().as_ref() == Some(columns)) {
+            Some(version) => format!("{} (layout {})", columns, version.to_possible_value().unwrap().get_name()),
+            None => columns.to_string()
+        }
+    }
+}
+
+/// A layout worked out from the data, and whether anything else would have fitted as well.
+pub struct Inference {
+    pub columns: Columns,
+    pub ambiguous: bool
+}
+
+/// Rows of a trajectory CSV still to be read.
+pub type Rows<'a> = Box<dyn Iterator<Item = csv::Result<StringRecord>> + 'a>;
+
+/// How the column order of trajectory CSVs is found.
+#[derive(Args, Debug, Clone, Default)]
+pub struct ColumnChoice {
+
+    /// Trajectory CSV column order, naming each column (`t,x,y,z,_,r,g,b`; `_` skips one).
+    /// Without it the layout, the header row or, failing that, the data decides
+    #[arg(long, conflicts_with = "layout")]
+    columns: Option<Columns>,
+
+    /// Trajectory CSV schema of our exporter's versions
+    #[arg(long, value_enum, default_value_t)]
+    layout: CsvLayout,
+
+    /// Use the inferred column layout even when the data leaves it ambiguous
+    #[arg(long)]
+    yes: bool
+}
+
+/// The column order [`ColumnChoice::choose`] settled on.
+pub enum Chosen {
+    /// Given with `--columns` or `--layout`, or named by the header row.
+    Known(Columns),
+    /// Worked out from the data.
+    Inferred(Columns),
+    /// The data fits more than one layout and `--yes` wasn't given; the best guess.
+    Ambiguous(Columns),
+    /// Nothing fits the data.
+    Unknown
+}
+
+impl ColumnChoice {
+
+    /// Column order of a CSV: `--columns`, `--layout`, the header row or, failing all three,
+    /// the first rows of data, which are put back in front of `rows`.
+    pub fn choose<'a>(&self, headers: Option<&StringRecord>, rows: &mut Rows<'a>) -> Chosen {
+        if let Some(columns) = self.columns.clone().or(self.layout.columns()).or(headers.and_then(Columns::from_headers)) {
+            return Chosen::Known(columns);
+        }
+        let peeked: Vec<_> = rows.by_ref().take(SAMPLE_ROWS).collect();
+        let sampled: Vec<StringRecord> = peeked.iter().filter_map(|record| record.as_ref().ok().cloned()).collect();
+        let rest = std::mem::replace(rows, Box::new(std::iter::empty()));
+        *rows = Box::new(peeked.into_iter().chain(rest));
+        // A known layout stands whenever the data allows it.
+        match (Columns::infer(&sampled), CsvLayout::matching(&sampled)) {
+            (Some(inference), _) if !inference.ambiguous => Chosen::Inferred(inference.columns),
+            (_, Some(profile)) => profile.columns().map_or(Chosen::Unknown, Chosen::Inferred),
+            (Some(inference), None) if self.yes => Chosen::Inferred(inference.columns),
+            (Some(inference), None) => Chosen::Ambiguous(inference.columns),
+            (None, None) => Chosen::Unknown
+        }
+    }
+}
+
+impl Columns {
+
+    /// Number of input columns the layout describes.
+    fn width(&self) -> usize {
+        self.0.iter().max().unwrap() + 1
+    }
+
+    /// Finds every field by header name, ignoring units (`x [m]`) and unknown columns.
+    pub fn from_headers(headers: &StringRecord) -> Option<Columns> {
+        let mut indices = [None; 7];
+        for (column, header) in headers.iter().enumerate() {
+            let name = header.split(['[', '(']).next().unwrap_or_default().trim().to_ascii_lowercase();
+            if let Some(field) = HEADER_NAMES.iter().position(|names| names.contains(&name.as_str())) {
+                indices[field].get_or_insert(column);
+            }
+        }
+        indices.iter().all(Option::is_some).then(|| Columns(indices.map(Option::unwrap)))
+    }
+
+    /// Guesses the layout from the values: the increasing column is time, three columns of
+    /// whole numbers from 0 to 255 are the color, and the rest is the position, each in
+    /// column order. A ramp that could be a color only counts as time when nothing else
+    /// increases. `None` when not even a guess fits.
+    pub fn infer(records: &[StringRecord]) -> Option<Inference> {
+        let values = Values::new(records)?;
+        let times: Vec<usize> = (0..values.width()).filter(|&column| values.is_time(column)).collect();
+        let time = times.iter().copied().find(|&column| !values.is_color(column)).or(times.first().copied())?;
+        let colors: Vec<usize> = (0..values.width())
+            .filter(|&column| column != time && values.is_color(column))
+            .collect();
+        if colors.len() < 3 {
+            return None;
+        }
+        let color = &colors[colors.len() - 3..];
+        let positions: Vec<usize> = (0..values.width())
+            .filter(|column| *column != time && !color.contains(column) && values.is_number(*column))
+            .collect();
+        if positions.len() < 3 {
+            return None;
+        }
+        Some(Inference {
+            columns: Columns([time, positions[0], positions[1], positions[2], color[0], color[1], color[2]]),
+            ambiguous: times.iter().filter(|column| !color.contains(column)).count() > 1
+                || colors.len() > 3 || positions.len() > 3
+        })
+    }
+
+    /// Whether the values are consistent with this layout, by the same rules as [`infer`].
+    ///
+    /// [`infer`]: Columns::infer
+    pub fn fits(&self, records: &[StringRecord]) -> bool {
+        let [time, x, y, z, r, g, b] = self.0;
+        Values::new(records).is_some_and(|values| {
+            values.width() >= self.width()
+                && values.is_time(time)
+                && [x, y, z].iter().all(|&column| values.is_number(column))
+                && [r, g, b].iter().all(|&column| values.is_color(column))
+        })
+    }
+
+    /// Parses `record` into a sample, reading each field from its column. The color is only
+    /// used by scripts, exports and repairs, so here it is read leniently: values are rounded
+    /// and clamped to 0-255, and unreadable ones are white. Those that use it read samples
+    /// with [`colored_sample`] instead.
+    ///
+    /// [`colored_sample`]: Columns::colored_sample
+    pub fn sample(&self, record: &StringRecord, name: &str) -> Result<Sample> {
+        self.read(record, name, false)
+    }
+
+    /// [`sample`], failing on a color that can't be read rather than making it white.
+    ///
+    /// [`sample`]: Columns::sample
+    pub fn colored_sample(&self, record: &StringRecord, name: &str) -> Result<Sample> {
+        self.read(record, name, true)
+    }
+
+    fn read(&self, record: &StringRecord, name: &str, strict_color: bool) -> Result<Sample> {
+        let line = record.position().map_or(0, |position| position.line());
+        let width = self.width();
+        if record.len() < width {
+            return Err(Error::Parse(format!("{} line {}: expected {} columns, found {}", name, line, width, record.len())));
+        }
+        let field = |i: usize| &record[self.0[i]];
+        let number = |i: usize| field::parse::<f64>(field(i))
+            .map_err(|_| Error::Parse(format!("{} line {}: invalid number '{}'", name, line, field(i))));
+        let channel = |i: usize| match number(i) {
+            Ok(value) => Ok(value.round().clamp(0.0, 255.0) as u8),
+            Err(e) if strict_color => Err(e),
+            Err(_) => Ok(255)
+        };
+        Ok(Sample {
+            t: number(0)?,
+            pos: [number(1)? as f32, number(2)? as f32, number(3)? as f32],
+            rgb: [channel(4)?, channel(5)?, channel(6)?]
+        })
+    }
+
+    /// `template` with the fields numbered in `fields` (`0` for t up to `6` for b) taken from
+    /// `sample`; its other columns are kept.
+    pub fn fill(&self, template: &StringRecord, sample: &Sample, fields: &[usize]) -> StringRecord {
+        let [x, y, z] = sample.pos;
+        let [r, g, b] = sample.rgb;
+        let values = [sample.t.to_string(), x.to_string(), y.to_string(), z.to_string(), r.to_string(), g.to_string(), b.to_string()];
+        template.iter().enumerate()
+            .map(|(column, cell)| match fields.iter().find(|&&field| self.0[field] == column) {
+                Some(&field) => values[field].as_str(),
+                None => cell
+            })
+            .collect()
+    }
+}
+
+/// The sampled rows column by column, unparseable cells as NaN.
+struct Values(Vec<Vec<f64>>);
+
+impl Values {
+
+    fn new(records: &[StringRecord]) -> Option<Values> {
+        let width = records.iter().map(StringRecord::len).min()?;
+        Some(Values((0..width)
+            .map(|column| records.iter().map(|record| field::parse::<f64>(&record[column]).unwrap_or(f64::NAN)).collect())
+            .collect()))
+    }
+
+    fn width(&self) -> usize {
+        self.0.len()
+    }
+
+    fn is_number(&self, column: usize) -> bool {
+        self.0[column].iter().all(|value| value.is_finite())
+    }
+
+    fn is_time(&self, column: usize) -> bool {
+        self.0[column].len() > 1 && self.is_number(column) && self.0[column].windows(2).all(|w| w[1] > w[0])
+    }
+
+    fn is_color(&self, column: usize) -> bool {
+        self.0[column].iter().all(|value| value.fract() == 0.0 && (0.0..=255.0).contains(value))
+    }
+}
+
+/// Header units of time (to milliseconds) and length (to meters).
+const TIME_UNITS: &[(&[&str], f64)] = &[
+    (&["ms", "msec", "millisecond", "milliseconds"], 1.0),
+    (&["s", "sec", "secs", "second", "seconds"], 1000.0),
+    (&["min", "minute", "minutes"], 60_000.0),
+    (&["us", "µs", "microsecond", "microseconds"], 0.001)
+];
+const LENGTH_UNITS: &[(&[&str], f64)] = &[
+    (&["m", "meter", "meters", "metre", "metres"], 1.0),
+    (&["cm"], 0.01),
+    (&["mm"], 0.001),
+    (&["ft", "feet", "foot"], 0.3048),
+    (&["in", "inch", "inches"], 0.0254)
+];
+
+/// Parses a length for the command line (`1cm`, `5 mm`, `0.5ft`), in meters when it has no
+/// unit.
+pub fn parse_length(s: &str) -> Result<f32, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+    let value = s[..split].parse::<f64>().map_err(|_| format!("invalid length '{}'", s))?;
+    let unit = s[split..].trim().to_lowercase();
+    let scale = match unit.as_str() {
+        "" => 1.0,
+        unit => LENGTH_UNITS.iter().find(|(names, _)| names.contains(&unit)).map(|(_, scale)| *scale)
+            .ok_or_else(|| format!("unknown unit '{}' in '{}'; use m, cm, mm, ft or in", unit, s))?
+    };
+    Ok((value * scale) as f32)
+}
+
+/// Scale of the time and position columns to milliseconds and meters, from the units in
+/// their headers (`x [ft]`, `t (s)`). Columns without a unit are taken as ms and m.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Units {
+    /// `(unit, scale)` for t, x, y and z.
+    scales: [(String, f64); 4]
+}
+
+impl Units {
+
+    /// Units of the columns `columns` reads, and any unit tokens that weren't recognized
+    /// (those columns are read unscaled).
+    pub fn from_headers(headers: &StringRecord, columns: &Columns) -> (Units, Vec<String>) {
+        let mut unknown = vec![];
+        let scales = [0, 1, 2, 3].map(|field| {
+            let header = headers.get(columns.0[field]).unwrap_or_default();
+            let Some(unit) = unit(header) else {
+                return (String::new(), 1.0);
+            };
+            let table = if field == 0 { TIME_UNITS } else { LENGTH_UNITS };
+            match table.iter().find(|(names, _)| names.contains(&unit.to_lowercase().as_str())) {
+                Some((_, scale)) => (unit, *scale),
+                None => {
+                    unknown.push(header.trim().to_string());
+                    (String::new(), 1.0)
+                }
+            }
+        });
+        (Units { scales }, unknown)
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.scales.iter().all(|(_, scale)| *scale == 1.0)
+    }
+
+    /// `sample` with its time in milliseconds and its position in meters.
+    pub fn apply(&self, sample: Sample) -> Sample {
+        let [t, x, y, z] = self.scales.each_ref().map(|(_, scale)| *scale);
+        let [east, north, up] = sample.pos.map(f64::from);
+        Sample { t: sample.t * t, pos: [(east * x) as f32, (north * y) as f32, (up * z) as f32], ..sample }
+    }
+
+    /// The inverse of [`apply`]: `sample` back in the header units.
+    ///
+    /// [`apply`]: Units::apply
+    pub fn revert(&self, sample: Sample) -> Sample {
+        let [t, x, y, z] = self.scales.each_ref().map(|(_, scale)| *scale);
+        let [east, north, up] = sample.pos.map(f64::from);
+        Sample { t: sample.t / t, pos: [(east / x) as f32, (north / y) as f32, (up / z) as f32], ..sample }
+    }
+}
+
+/// `t, x [ft], y [ft], z [ft]` and the like.
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self.scales.iter().zip(FIELDS)
+            .map(|((unit, _), field)| if unit.is_empty() { field.to_string() } else { format!("{} [{}]", field, unit) })
+            .collect();
+        f.write_str(&names.join(", "))
+    }
+}
+
+/// The token inside the first `[...]` or `(...)` of a header.
+fn unit(header: &str) -> Option<String> {
+    let start = header.find(['[', '('])?;
+    let close = if header[start..].starts_with('[') { ']' } else { ')' };
+    let end = start + header[start..].find(close)?;
+    Some(header[start + 1..end].trim().to_string()).filter(|unit| !unit.is_empty())
+}
@@ -0,0 +1,286 @@
+//! `csv2vviz compare`: two shows checked against each other within tolerances, for CI.
+
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+
+use crate::{error::{self, Error, Result}, export::{color_at, Rgb}, plot::Figure, read_show, track::{distance, Track}, Performance, Show};
+
+#[derive(Args, Debug)]
+pub struct CompareArgs {
+
+    /// Reference show
+    a: PathBuf,
+
+    /// Show compared against it
+    b: PathBuf,
+
+    /// Largest distance (m) between a drone's positions in the two shows at the same time
+    #[arg(long, default_value_t = 0.01)]
+    pos_tol: f32,
+
+    /// Largest difference of any color channel (0-255) at the same time
+    #[arg(long, default_value_t = 0)]
+    color_tol: u8,
+
+    /// Report format
+    #[arg(long, value_enum, default_value_t)]
+    format: ReportFormat,
+
+    /// Write the report to this file instead of printing it
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Draw each drone's displacement between the shows, seen from above, to an .svg or .png
+    /// file
+    #[arg(long)]
+    plot: Option<PathBuf>,
+
+    /// Time (s) to draw the displacements at; repeat for side-by-side panels [default: when
+    /// positions differ most]
+    #[arg(long)]
+    at: Vec<f32>
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// One line per drone beyond tolerance and a summary
+    #[default]
+    Text,
+    /// JUnit XML with a test case per drone, for CI test reports
+    Junit
+}
+
+/// How one drone differs between the shows. Deviations are the largest found, with the time
+/// (s) they were found at.
+pub struct DroneDiff {
+    pub drone_id: usize,
+    pub position: (f32, f32),
+    pub color: Option<(u8, f32)>,
+    /// Why the drone couldn't be compared, when it is missing from a show or only one of them
+    /// lights it.
+    pub missing: Option<String>
+}
+
+/// Compares every drone of either show. Positions are compared at every sample time of both
+/// tracks, colors at every color frame of both.
+pub fn diff(a: &Show, b: &Show) -> Vec<DroneDiff> {
+    let mut ids: Vec<usize> = a.performances.iter().chain(&b.performances).map(|performance| performance.id).collect();
+    ids.sort();
+    ids.dedup();
+    ids.into_iter().map(|id| {
+        let mut diff = DroneDiff { drone_id: id + 1, position: (0.0, 0.0), color: None, missing: None };
+        let (Some(pa), Some(pb)) = (find(a, id), find(b, id)) else {
+            diff.missing = Some(format!("missing from the {} show", if find(a, id).is_some() { "second" } else { "first" }));
+            return diff;
+        };
+        let (ta, tb) = (Track::new(pa, a.default_position_rate), Track::new(pb, b.default_position_rate));
+        for t in union(ta.samples.iter().map(|(t, _)| *t), tb.samples.iter().map(|(t, _)| *t)) {
+            let deviation = distance(ta.position_at(t), tb.position_at(t));
+            if deviation > diff.position.0 {
+                diff.position = (deviation, t);
+            }
+        }
+
+        // Color frames are sampled half way through, away from float noise at their edges.
+        let frames = |rate: f32, duration: f32| (0..(duration * rate).ceil().max(1.0) as u32)
+            .map(move |frame| (frame as f32 + 0.5) / rate);
+        for t in union(frames(a.default_color_rate, ta.duration()), frames(b.default_color_rate, tb.duration())) {
+            match (color_at(pa, a.default_color_rate, t), color_at(pb, b.default_color_rate, t)) {
+                (Some(ca), Some(cb)) => {
+                    let deviation = [ca.0.abs_diff(cb.0), ca.1.abs_diff(cb.1), ca.2.abs_diff(cb.2)].into_iter().max().unwrap();
+                    if diff.color.is_none_or(|(worst, _)| deviation > worst) {
+                        diff.color = Some((deviation, t));
+                    }
+                }
+                (None, None) => {}
+                (lit, _) => {
+                    diff.missing = Some(format!("lit only in the {} show", if lit.is_some() { "first" } else { "second" }));
+                    break;
+                }
+            }
+        }
+        diff
+    }).collect()
+}
+
+fn find(show: &Show, id: usize) -> Option<&Performance> {
+    show.performances.iter().find(|performance| performance.id == id)
+}
+
+/// Sorted times of both sequences, without duplicates.
+fn union(a: impl Iterator<Item = f32>, b: impl Iterator<Item = f32>) -> Vec<f32> {
+    let mut times: Vec<f32> = a.chain(b).collect();
+    times.sort_by(f32::total_cmp);
+    times.dedup();
+    times
+}
+
+impl CompareArgs {
+
+    /// What is wrong with the drone, if anything.
+    fn failure(&self, diff: &DroneDiff) -> Option<String> {
+        if let Some(missing) = &diff.missing {
+            return Some(missing.clone());
+        }
+        let mut problems = vec![];
+        if diff.position.0 > self.pos_tol {
+            problems.push(format!("positions differ by {:.4} m at {:.2} s (tolerance {} m)", diff.position.0, diff.position.1, self.pos_tol));
+        }
+        if let Some((deviation, t)) = diff.color.filter(|(deviation, _)| *deviation > self.color_tol) {
+            problems.push(format!("colors differ by {} at {:.2} s (tolerance {})", deviation, t, self.color_tol));
+        }
+        (!problems.is_empty()).then(|| problems.join("; "))
+    }
+}
+
+pub fn run(args: CompareArgs) -> Result<()> {
+    if args.at.iter().any(|t| !(t.is_finite() && *t >= 0.0)) {
+        return Err(Error::Parse("--at times must be zero or more seconds".to_string()));
+    }
+    let (a, b) = (read_show(&args.a)?, read_show(&args.b)?);
+    let diffs = diff(&a, &b);
+    let failures: Vec<(usize, Option<String>)> = diffs.iter().map(|diff| (diff.drone_id, args.failure(diff))).collect();
+    let failed = failures.iter().filter(|(_, failure)| failure.is_some()).count();
+
+    let report = match args.format {
+        ReportFormat::Text => text(&args, &diffs, &failures),
+        ReportFormat::Junit => junit(&args, &failures)
+    };
+    match &args.output {
+        Some(output) => {
+            std::fs::write(output, report).map_err(error::io("Failed to write comparison report"))?;
+            println!("Wrote the comparison of {} drones to {}", diffs.len(), output.display());
+        }
+        None => print!("{}", report)
+    }
+    if let Some(path) = &args.plot {
+        let mut times = args.at.clone();
+        if times.is_empty() {
+            let worst = diffs.iter().filter(|diff| diff.missing.is_none()).max_by(|x, y| x.position.0.total_cmp(&y.position.0));
+            times.push(worst.map_or(0.0, |worst| worst.position.1));
+        }
+        plot(&a, &b, &times, args.pos_tol).save(path)?;
+        let times: Vec<String> = times.iter().map(|t| format!("{:.2} s", t)).collect();
+        println!("Drew the displacements at {} to {}", times.join(", "), path.display());
+    }
+
+    if failed > 0 {
+        return Err(Error::Validation(format!("{} of {} drones differ beyond tolerance", failed, diffs.len())));
+    }
+    Ok(())
+}
+
+fn text(args: &CompareArgs, diffs: &[DroneDiff], failures: &[(usize, Option<String>)]) -> String {
+    let mut report = String::new();
+    for (drone_id, failure) in failures {
+        if let Some(failure) = failure {
+            report.push_str(&format!("Drone {}: {}\n", drone_id, failure));
+        }
+    }
+    let worst_position = diffs.iter().filter(|diff| diff.missing.is_none()).max_by(|x, y| x.position.0.total_cmp(&y.position.0));
+    let worst_color = diffs.iter().filter_map(|diff| diff.color.map(|(deviation, _)| (deviation, diff.drone_id))).max();
+    report.push_str(&format!("Compared {} drones of {} and {}", diffs.len(), args.a.display(), args.b.display()));
+    if let Some(worst) = worst_position {
+        report.push_str(&format!(": positions within {:.4} m (drone {})", worst.position.0, worst.drone_id));
+    }
+    if let Some((deviation, drone_id)) = worst_color {
+        report.push_str(&format!(", colors within {} (drone {})", deviation, drone_id));
+    }
+    report.push('\n');
+    report
+}
+
+fn junit(args: &CompareArgs, failures: &[(usize, Option<String>)]) -> String {
+    let failed = failures.iter().filter(|(_, failure)| failure.is_some()).count();
+    let suite = escape(&format!("{} vs {}", args.a.display(), args.b.display()));
+    let mut report = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    report.push_str(&format!("<testsuites>\n  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n", suite, failures.len(), failed));
+    for (drone_id, failure) in failures {
+        match failure {
+            Some(failure) => report.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"Drone {}\">\n      <failure message=\"{}\"/>\n    </testcase>\n",
+                suite, drone_id, escape(failure)
+            )),
+            None => report.push_str(&format!("    <testcase classname=\"{}\" name=\"Drone {}\"/>\n", suite, drone_id))
+        }
+    }
+    report.push_str("  </testsuite>\n</testsuites>\n");
+    report
+}
+
+/// Pixels per panel side, and the margins around the panel's map.
+const PANEL: f32 = 520.0;
+const MARGIN: f32 = 30.0;
+const TOP: f32 = 40.0;
+
+/// One panel per time, mapping the first show's positions seen from above (east to the
+/// right, north up) with an arrow to the second show's, lengthened alike for every drone so
+/// that centimeters show at show scale. Dots are colored by the full deviation, height
+/// included: green within `tolerance`, through yellow, to red at twice it.
+fn plot(a: &Show, b: &Show, times: &[f32], tolerance: f32) -> Figure {
+    let pairs: Vec<(Track, Track)> = a.performances.iter()
+        .filter_map(|pa| find(b, pa.id).map(|pb| (Track::new(pa, a.default_position_rate), Track::new(pb, b.default_position_rate))))
+        .collect();
+    let positions: Vec<Vec<([f32; 3], [f32; 3])>> = times.iter()
+        .map(|t| pairs.iter().map(|(ta, tb)| (ta.position_at(*t), tb.position_at(*t))).collect())
+        .collect();
+
+    // The same square of the ground in every panel, so panels compare.
+    let (mut min, mut max) = ([f32::MAX; 2], [f32::MIN; 2]);
+    for (pa, _) in positions.iter().flatten() {
+        for (axis, value) in [pa[0], pa[2]].into_iter().enumerate() {
+            min[axis] = min[axis].min(value);
+            max[axis] = max[axis].max(value);
+        }
+    }
+    let center = if min[0] <= max[0] { [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0] } else { [0.0; 2] };
+    let span = ((max[0] - min[0]).max(max[1] - min[1]) * 1.2).max(1.0);
+    let largest = positions.iter().flatten().map(|(pa, pb)| (pb[0] - pa[0]).hypot(pb[2] - pa[2])).fold(0.0, f32::max);
+    let exaggeration = if largest > 0.0 { nice((0.08 * span / largest).max(1.0)) } else { 1.0 };
+
+    let size = PANEL - 2.0 * MARGIN;
+    let mut figure = Figure::new((PANEL * times.len() as f32) as u32, (TOP + size + 70.0) as u32);
+    for (panel, (t, positions)) in times.iter().zip(&positions).enumerate() {
+        let left = panel as f32 * PANEL + MARGIN;
+        let pixel = |east: f32, north: f32| [
+            left + size / 2.0 + (east - center[0]) / span * size,
+            TOP + size / 2.0 - (north - center[1]) / span * size
+        ];
+        figure.text([left, 12.0], &format!("At {:.2} s, {:.0} m across", t, span), (0, 0, 0));
+        let corners = [[left, TOP], [left + size, TOP], [left + size, TOP + size], [left, TOP + size]];
+        for side in 0..4 {
+            figure.line(corners[side], corners[(side + 1) % 4], 1.0, (190, 190, 190));
+        }
+        for (pa, pb) in positions {
+            let from = pixel(pa[0], pa[2]);
+            let to = pixel(pa[0] + (pb[0] - pa[0]) * exaggeration, pa[2] + (pb[2] - pa[2]) * exaggeration);
+            let color = ramp(if tolerance > 0.0 { distance(*pa, *pb) / tolerance } else if pa == pb { 0.0 } else { 2.0 });
+            figure.arrow(from, to, 1.5, color);
+            figure.dot(from, 3.0, color);
+        }
+        figure.text([left, TOP + size + 16.0], &format!("Arrows to the second show, {}x longer", exaggeration), (80, 80, 80));
+        figure.text([left, TOP + size + 38.0], &format!("Green within {} m, red from {} m", tolerance, 2.0 * tolerance), (80, 80, 80));
+    }
+    figure
+}
+
+/// The largest of 1, 2 and 5 times a power of ten not above `value`.
+fn nice(value: f32) -> f32 {
+    let power = 10f32.powf(value.log10().floor());
+    [5.0, 2.0, 1.0].into_iter().map(|step| step * power).find(|&nice| nice <= value).unwrap_or(power)
+}
+
+/// Green at 0, yellow at 1, red from 2.
+fn ramp(ratio: f32) -> Rgb {
+    let mix = |from: Rgb, to: Rgb, u: f32| {
+        let channel = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * u).round() as u8;
+        (channel(from.0, to.0), channel(from.1, to.1), channel(from.2, to.2))
+    };
+    let (green, yellow, red) = ((0, 160, 0), (230, 180, 0), (220, 0, 0));
+    if ratio <= 1.0 { mix(green, yellow, ratio.max(0.0)) } else { mix(yellow, red, (ratio - 1.0).min(1.0)) }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
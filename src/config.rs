@@ -0,0 +1,53 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{error::{Error, Result}, lint, F3D};
+
+mod toml;
+
+/// Transforms applied to a single drone after the global `--rotate`/`--translate`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DroneOverride {
+    pub rotate: Option<F3D>,
+    pub translate: Option<F3D>
+}
+
+/// Settings read from a TOML file passed with `--config`. `convert` reads the drone
+/// overrides and `lint` the rules, so one file can serve both.
+///
+/// ```toml
+/// # Drone 12 sits next to a lamp post; shift it 1.5 m along x.
+/// [drones.12]
+/// translate = "1.5 0 0"
+///
+/// [rules.separation]
+/// min_distance = 1.5
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Keyed by drone number, as in the `Drone N` entry names.
+    #[serde(default)]
+    pub drones: HashMap<usize, DroneOverride>,
+    #[serde(default)]
+    pub rules: lint::Rules
+}
+
+impl Config {
+
+    pub fn load(fname: &Path) -> Result<Self> {
+        load_toml(fname, "config file")
+    }
+}
+
+/// Reads a TOML file into `T`; `what` names the file in error messages.
+pub fn load_toml<T: DeserializeOwned>(fname: &Path, what: &str) -> Result<T> {
+    let text = std::fs::read_to_string(fname)
+        .map_err(|e| Error::Io(format!("Failed to read {}: {}", what, e)))?;
+    let value = toml::parse(&text)
+        .map_err(|e| Error::Parse(format!("Invalid {}: {}", what, e.error)))?;
+    serde_json::from_value(value)
+        .map_err(|e| Error::Parse(format!("Invalid {}: {}", what, e)))
+}
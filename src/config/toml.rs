@@ -0,0 +1,388 @@
+//! A small TOML reader covering the subset used by csv2vviz config files: tables, arrays of
+//! tables, dotted and quoted keys, basic and literal strings, integers, floats, booleans,
+//! arrays and inline tables. Dates and multi-line strings are not supported.
+//!
+//! Documents are parsed into a `serde_json::Value` so the config structs can be
+//! deserialized with the same serde derives used everywhere else.
+
+use serde_json::{Map, Number, Value};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseTomlError {
+    pub error: String
+}
+
+pub fn parse(input: &str) -> Result<Value, ParseTomlError> {
+    Parser { chars: input.chars().collect(), pos: 0, line: 1 }.document()
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize
+}
+
+impl Parser {
+
+    fn error<T>(&self, message: &str) -> Result<T, ParseTomlError> {
+        Err(ParseTomlError { error: format!("line {}: {}", self.line, message) })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseTomlError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", c))
+        }
+    }
+
+    /// Skips spaces and tabs on the current line.
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.bump();
+            }
+        }
+    }
+
+    /// Skips whitespace, comments and newlines (between statements or array elements).
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_whitespace();
+            self.skip_comment();
+            if !(self.eat('\n') || self.eat('\r')) {
+                break;
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), ParseTomlError> {
+        self.skip_whitespace();
+        self.skip_comment();
+        self.eat('\r');
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => self.error(&format!("unexpected '{}'", c))
+        }
+    }
+
+    fn document(mut self) -> Result<Value, ParseTomlError> {
+        let mut root = Map::new();
+        let mut current: Vec<String> = vec![];
+        loop {
+            self.skip_blank();
+            match self.peek() {
+                None => break,
+                Some('[') => {
+                    self.bump();
+                    let array = self.eat('[');
+                    self.skip_whitespace();
+                    let path = self.key()?;
+                    self.skip_whitespace();
+                    self.expect(']')?;
+                    if array {
+                        self.expect(']')?;
+                        let (last, parents) = path.split_last().unwrap();
+                        let table = self.table_at(&mut root, parents)?;
+                        match table.entry(last.clone()).or_insert_with(|| Value::Array(vec![])) {
+                            Value::Array(items) => items.push(Value::Object(Map::new())),
+                            _ => return self.error(&format!("'{}' is not an array of tables", last))
+                        }
+                    } else {
+                        self.table_at(&mut root, &path)?;
+                    }
+                    current = path;
+                }
+                Some(_) => {
+                    let path = self.key()?;
+                    self.skip_whitespace();
+                    self.expect('=')?;
+                    self.skip_whitespace();
+                    let value = self.value()?;
+                    let table = self.table_at(&mut root, &current)?;
+                    self.insert(table, &path, value)?;
+                }
+            }
+            self.end_of_line()?;
+        }
+        Ok(Value::Object(root))
+    }
+
+    /// Walks (creating as needed) to the table at `path`, descending into the last element
+    /// of arrays of tables.
+    fn table_at<'m>(&self, mut table: &'m mut Map<String, Value>, path: &[String]) -> Result<&'m mut Map<String, Value>, ParseTomlError> {
+        for key in path {
+            let entry = table.entry(key.clone()).or_insert_with(|| Value::Object(Map::new()));
+            let entry = match entry {
+                Value::Array(items) => match items.last_mut() {
+                    Some(last) => last,
+                    None => return self.error(&format!("'{}' is not a table", key))
+                },
+                other => other
+            };
+            table = match entry {
+                Value::Object(map) => map,
+                _ => return self.error(&format!("'{}' is not a table", key))
+            };
+        }
+        Ok(table)
+    }
+
+    fn insert(&self, table: &mut Map<String, Value>, path: &[String], value: Value) -> Result<(), ParseTomlError> {
+        let (last, parents) = path.split_last().unwrap();
+        let table = self.table_at(table, parents)?;
+        if table.contains_key(last) {
+            return self.error(&format!("duplicate key '{}'", last));
+        }
+        table.insert(last.clone(), value);
+        Ok(())
+    }
+
+    fn key(&mut self) -> Result<Vec<String>, ParseTomlError> {
+        let mut path = vec![];
+        loop {
+            self.skip_whitespace();
+            let part = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let mut part = String::new();
+                    while let Some(c) = self.peek().filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-') {
+                        part.push(c);
+                        self.bump();
+                    }
+                    if part.is_empty() {
+                        return self.error("expected a key");
+                    }
+                    part
+                }
+            };
+            path.push(part);
+            self.skip_whitespace();
+            if !self.eat('.') {
+                return Ok(path);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseTomlError> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.basic_string()?)),
+            Some('\'') => Ok(Value::String(self.literal_string()?)),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some('t') | Some('f') => {
+                let word = self.word();
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => self.error(&format!("invalid value '{}'", word))
+                }
+            }
+            Some(_) => self.number(),
+            None => self.error("expected a value")
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_alphanumeric() || "+-._".contains(*c)) {
+            word.push(c);
+            self.bump();
+        }
+        word
+    }
+
+    fn number(&mut self) -> Result<Value, ParseTomlError> {
+        let word = self.word().replace('_', "");
+        if let Ok(integer) = word.parse::<i64>() {
+            return Ok(Value::Number(integer.into()));
+        }
+        match word.parse::<f64>().ok().and_then(Number::from_f64) {
+            Some(float) if !word.is_empty() => Ok(Value::Number(float)),
+            _ => self.error(&format!("invalid value '{}'", word))
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, ParseTomlError> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            if matches!(self.peek(), None | Some('\n')) {
+                return self.error("unterminated string");
+            }
+            match self.bump() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.bump() {
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some('r') => string.push('\r'),
+                    Some('"') => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.bump()).collect();
+                        match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                            Some(c) => string.push(c),
+                            None => return self.error(&format!("invalid escape '\\u{}'", hex))
+                        }
+                    }
+                    _ => return self.error("invalid escape sequence")
+                },
+                Some(c) => string.push(c),
+                None => unreachable!()
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, ParseTomlError> {
+        self.expect('\'')?;
+        let mut string = String::new();
+        loop {
+            if matches!(self.peek(), None | Some('\n')) {
+                return self.error("unterminated string");
+            }
+            match self.bump() {
+                Some('\'') => return Ok(string),
+                Some(c) => string.push(c),
+                None => unreachable!()
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, ParseTomlError> {
+        self.expect('[')?;
+        let mut items = vec![];
+        loop {
+            self.skip_blank();
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_blank();
+            if !self.eat(',') {
+                self.skip_blank();
+                self.expect(']')?;
+                return Ok(Value::Array(items));
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value, ParseTomlError> {
+        self.expect('{')?;
+        let mut table = Map::new();
+        self.skip_whitespace();
+        if self.eat('}') {
+            return Ok(Value::Object(table));
+        }
+        loop {
+            let path = self.key()?;
+            self.skip_whitespace();
+            self.expect('=')?;
+            self.skip_whitespace();
+            let value = self.value()?;
+            self.insert(&mut table, &path, value)?;
+            self.skip_whitespace();
+            if !self.eat(',') {
+                self.expect('}')?;
+                return Ok(Value::Object(table));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::parse;
+
+    fn error(input: &str) -> String {
+        parse(input).unwrap_err().error
+    }
+
+    #[test]
+    fn tables_and_dotted_keys() {
+        let document = parse("title = 'show' # name\n\n[drones.12]\ntranslate = \"1.5 0 0\"\n[drones.\"7\"]\nrotate.z = 90\n").unwrap();
+        assert_eq!(document, json!({
+            "title": "show",
+            "drones": { "12": { "translate": "1.5 0 0" }, "7": { "rotate": { "z": 90 } } }
+        }));
+    }
+
+    #[test]
+    fn arrays_of_tables() {
+        let document = parse("[[pads]]\nid = 1\n[[pads]]\nid = 2\n[pads.offset]\nx = 0.5\n").unwrap();
+        assert_eq!(document, json!({ "pads": [{ "id": 1 }, { "id": 2, "offset": { "x": 0.5 } }] }));
+    }
+
+    #[test]
+    fn values() {
+        let document = parse(concat!(
+            "integers = [1, -2, 1_000]\n",
+            "floats = [0.5, -1e3, +2.5]\n",
+            "flags = [true, false]\n",
+            "nested = [\n  [1, 2], # comment\n  [],\n]\n",
+            "point = { x = 1, y = { z = 'up' } }\n"
+        )).unwrap();
+        assert_eq!(document, json!({
+            "integers": [1, -2, 1000],
+            "floats": [0.5, -1000.0, 2.5],
+            "flags": [true, false],
+            "nested": [[1, 2], []],
+            "point": { "x": 1, "y": { "z": "up" } }
+        }));
+    }
+
+    #[test]
+    fn string_escapes() {
+        let document = parse(r#"basic = "tab\tquote\"slash\\newline\nsnow\u2603"
+literal = 'C:\raw\n'"#).unwrap();
+        assert_eq!(document["basic"], "tab\tquote\"slash\\newline\nsnow\u{2603}");
+        assert_eq!(document["literal"], "C:\\raw\\n");
+    }
+
+    #[test]
+    fn errors_name_their_line() {
+        assert_eq!(error("a = 1\nb = \"open\n"), "line 2: unterminated string");
+        assert_eq!(error("a = 1\n\na = 2\n"), "line 3: duplicate key 'a'");
+        assert_eq!(error("a = \"\\q\"\n"), "line 1: invalid escape sequence");
+        assert_eq!(error("a = \"\\uZZZZ\"\n"), "line 1: invalid escape '\\uZZZZ'");
+        assert_eq!(error("a = 1 2\n"), "line 1: unexpected '2'");
+        assert_eq!(error("x\n"), "line 1: expected '='");
+        assert_eq!(error("a = nope\n"), "line 1: invalid value 'nope'");
+        assert_eq!(error("a = 1\n[a]\n"), "line 2: 'a' is not a table");
+        assert_eq!(error("a = [1, 2\n"), "line 2: expected ']'");
+    }
+}
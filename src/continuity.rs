@@ -0,0 +1,116 @@
+use std::{collections::HashMap, path::Path};
+
+use clap::ValueEnum;
+
+use crate::{error::{self, Error, Result}, progress, Drone, Show};
+
+/// What to do when several entries are numbered as the same drone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OnDuplicate {
+    /// Fail the conversion
+    #[default]
+    Error,
+    /// Keep the entry that comes first in the input
+    First,
+    /// Keep the entry that comes last in the input
+    Last,
+    /// Keep the first and give the others new numbers after the highest one
+    Renumber
+}
+
+/// Applies `policy` to drones sharing a number. `names` holds the entry name of each drone.
+pub fn resolve_duplicates(drones: &mut Vec<Drone>, names: &[String], policy: OnDuplicate) -> Result<()> {
+    let mut next_id = drones.iter().map(|(drone_id, _)| *drone_id).max().unwrap_or(0) + 1;
+    let mut seen: HashMap<usize, usize> = HashMap::new();
+    let mut keep = vec![true; drones.len()];
+    for index in 0..drones.len() {
+        let drone_id = drones[index].0;
+        let Some(&other) = seen.get(&drone_id) else {
+            seen.insert(drone_id, index);
+            continue;
+        };
+        match policy {
+            OnDuplicate::Error => return Err(Error::Format(format!(
+                "'{}' and '{}' are both drone {} (see --on-duplicate)", names[other], names[index], drone_id
+            ))),
+            OnDuplicate::First => {
+                progress::warning(format!("ignoring '{}': drone {} is already '{}'", names[index], drone_id, names[other]));
+                keep[index] = false;
+            }
+            OnDuplicate::Last => {
+                progress::warning(format!("ignoring '{}': drone {} is also '{}'", names[other], drone_id, names[index]));
+                keep[other] = false;
+                seen.insert(drone_id, index);
+            }
+            OnDuplicate::Renumber => {
+                progress::warning(format!("'{}' is also drone {}; numbering it {}", names[index], drone_id, next_id));
+                drones[index].0 = next_id;
+                next_id += 1;
+            }
+        }
+    }
+    let mut keep = keep.into_iter();
+    drones.retain(|_| keep.next().unwrap());
+    Ok(())
+}
+
+/// Consecutive ids from `ids` (sorted) as `7` or `10-12`.
+pub fn ranges(ids: &[usize]) -> String {
+    let mut parts: Vec<String> = vec![];
+    let mut i = 0;
+    while i < ids.len() {
+        let start = ids[i];
+        while i + 1 < ids.len() && ids[i + 1] == ids[i] + 1 {
+            i += 1;
+        }
+        parts.push(if ids[i] == start { start.to_string() } else { format!("{}-{}", start, ids[i]) });
+        i += 1;
+    }
+    parts.join(", ")
+}
+
+/// Warns about gaps in the drone numbering (counting from 1) and a drone count other than
+/// `expected`, and fails on a drone numbered 0, which is out of range. Duplicates are already
+/// resolved by [`resolve_duplicates`].
+pub fn check(drones: &[Drone], expected: Option<usize>) -> Result<()> {
+    let mut ids: Vec<usize> = drones.iter().map(|(drone_id, _)| *drone_id).collect();
+    ids.sort_unstable();
+    if ids.first() == Some(&0) {
+        return Err(Error::Validation("Drone 0 is out of range: drone numbers start at 1".to_string()));
+    }
+
+    let max = ids.last().copied().unwrap_or(0);
+    let missing: Vec<usize> = (1..=max).filter(|id| ids.binary_search(id).is_err()).collect();
+    if !missing.is_empty() {
+        progress::warning(format!("drone {} missing ({} of {} numbers used)", ranges(&missing), max - missing.len(), max));
+    }
+
+    if let Some(expected) = expected {
+        if drones.len() != expected {
+            progress::warning(format!("expected {} drones but found {}", expected, drones.len()));
+        }
+    }
+    Ok(())
+}
+
+/// Numbers the show's performances 0, 1, 2, ... in their current order, returning each
+/// renumbered performance's old and new id.
+pub fn renumber(show: &mut Show) -> Vec<(usize, usize)> {
+    let mut mapping = vec![];
+    for (index, performance) in show.performances.iter_mut().enumerate() {
+        mapping.push((performance.id, index));
+        performance.id = index;
+    }
+    let moved = mapping.iter().filter(|(old, new)| old != new).count();
+    println!("Renumbered {} of {} drones to ids 0-{}", moved, mapping.len(), mapping.len().saturating_sub(1));
+    mapping
+}
+
+/// Writes the `old_id,new_id` rows of every performance [`renumber`] numbered.
+pub fn write_mapping(mapping: &[(usize, usize)], fname: &Path) -> Result<()> {
+    let mut text = String::from("old_id,new_id\n");
+    for (old, new) in mapping {
+        text.push_str(&format!("{},{}\n", old, new));
+    }
+    std::fs::write(fname, text).map_err(error::io("Failed to write renumbering"))
+}
@@ -0,0 +1,77 @@
+use clap::ValueEnum;
+
+use crate::{acts::Act, lights::{push_frame, Rgb}, ColorAction, Sample};
+
+/// What the lights show while the drones wait on their pads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Pattern {
+    /// Blink once a second, lit for the first half of each second before launch
+    #[default]
+    Countdown,
+    /// Fade in and out every two seconds
+    Pulse,
+    /// Stay lit
+    Solid
+}
+
+/// A light-only hold of `length` seconds before the performance: every drone waits at its first
+/// position with its lights running `pattern` in `color`, then goes dark for launch unless it
+/// has a light program of its own.
+#[derive(Debug, Clone, Copy)]
+pub struct Countdown {
+    pub length: f32,
+    pub pattern: Pattern,
+    pub color: Rgb
+}
+
+const DARK: Rgb = Rgb { r: 0, g: 0, b: 0 };
+
+impl Countdown {
+
+    /// Length of the hold in color frames at `rate` frames per second.
+    pub fn frames(&self, rate: f32) -> u32 {
+        (self.length * rate).round().max(1.0) as u32
+    }
+
+    /// Length of the hold in seconds, rounded to whole color frames.
+    pub fn lead(&self, rate: f32) -> f32 {
+        self.frames(rate) as f32 / rate
+    }
+
+    /// Prepends the hold to a drone's samples.
+    pub fn hold(&self, samples: &mut Vec<Sample>, rate: f32) {
+        let first = samples[0];
+        samples.insert(0, first.with_time(first.t - self.frames(rate) as f64 * 1000.0 / rate as f64));
+    }
+
+    /// The hold's color frames followed by the drone's own light program, if it has one.
+    pub fn prepend(&self, program: Option<Vec<ColorAction>>, rate: f32) -> Vec<ColorAction> {
+        let frames = self.frames(rate);
+        let per_second = (rate.round() as u32).max(2);
+        let mut actions = vec![];
+        for frame in 0..frames {
+            let color = match self.pattern {
+                // Frames are counted back from launch so that the last blink ends on it.
+                Pattern::Countdown if (frames - 1 - frame) % per_second >= per_second / 2 => self.color,
+                Pattern::Countdown => DARK,
+                Pattern::Pulse => {
+                    let phase = frame as f32 / rate * std::f32::consts::PI;
+                    DARK.lerp(self.color, 0.5 - 0.5 * phase.cos())
+                }
+                Pattern::Solid => self.color
+            };
+            push_frame(&mut actions, color);
+        }
+        match program {
+            Some(program) => actions.extend(program),
+            None => push_frame(&mut actions, DARK)
+        }
+        actions
+    }
+}
+
+/// Delays the acts of a merged show by the countdown, if there is one.
+pub fn delay_acts(acts: Vec<Act>, countdown: Option<Countdown>, rate: f32) -> Vec<Act> {
+    let lead = countdown.map_or(0.0, |countdown| countdown.lead(rate));
+    acts.into_iter().map(|act| Act { start: act.start + lead, end: act.end + lead, ..act }).collect()
+}
@@ -0,0 +1,99 @@
+use std::{fmt, str::FromStr};
+
+use clap::ValueEnum;
+
+use crate::{continuity::ranges, error::{Error, Result}, progress, Drone};
+
+/// Box from `min` to `max` in east, north, up meters, after the coordinate transforms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropBox {
+    min: [f32; 3],
+    max: [f32; 3]
+}
+
+impl FromStr for CropBox {
+    type Err = String;
+
+    /// Two opposite corners, `x1 y1 z1, x2 y2 z2`, in either order.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<f32>().ok().filter(|value| value.is_finite())
+                .ok_or_else(|| format!("invalid coordinate '{}'", token)))
+            .collect::<Result<Vec<f32>, _>>()?;
+        let [x1, y1, z1, x2, y2, z2] = values[..] else {
+            return Err(format!("expected two corners of three coordinates, found {} numbers", values.len()));
+        };
+        Ok(CropBox { min: [x1.min(x2), y1.min(y2), z1.min(z2)], max: [x1.max(x2), y1.max(y2), z1.max(z2)] })
+    }
+}
+
+impl fmt::Display for CropBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [x1, y1, z1] = self.min;
+        let [x2, y2, z2] = self.max;
+        write!(f, "{} {} {}, {} {} {}", x1, y1, z1, x2, y2, z2)
+    }
+}
+
+impl CropBox {
+
+    /// How far (m) `position` is outside the box; zero inside it.
+    fn outside(&self, position: [f32; 3]) -> f32 {
+        (0..3).map(|axis| (self.min[axis] - position[axis]).max(position[axis] - self.max[axis]).max(0.0))
+            .map(|excess| excess * excess)
+            .sum::<f32>()
+            .sqrt()
+    }
+}
+
+/// What happens to drones whose trajectories leave the crop box.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum CropMode {
+    /// Leave them out of the show
+    #[default]
+    Drop,
+    /// Keep them, with a warning
+    Flag
+}
+
+/// Reports every drone with a sample outside `crop` (when it first leaves, how many samples
+/// and how far) and drops them in [`CropMode::Drop`]. Fails when no drone is left.
+pub fn apply(drones: &mut Vec<Drone>, crop: CropBox, mode: CropMode) -> Result<()> {
+    let mut leaving = vec![];
+    for (drone_id, samples) in drones.iter() {
+        let outside: Vec<(f64, f32)> = samples.iter()
+            .map(|sample| (sample.t, crop.outside(sample.pos)))
+            .filter(|(_, excess)| *excess > 0.0)
+            .collect();
+        let Some((first, _)) = outside.first() else {
+            continue;
+        };
+        let furthest = outside.iter().map(|(_, excess)| *excess).fold(0.0, f32::max);
+        let message = format!(
+            "drone {} leaves the crop box at {:.3} s ({} samples outside, up to {:.2} m)",
+            drone_id, first / 1000.0, outside.len(), furthest
+        );
+        match mode {
+            CropMode::Drop => println!("Cropped: {}", message),
+            CropMode::Flag => progress::warning(message)
+        }
+        leaving.push(*drone_id);
+    }
+    if leaving.is_empty() {
+        return Ok(());
+    }
+
+    leaving.sort_unstable();
+    match mode {
+        CropMode::Drop if leaving.len() == drones.len() => {
+            return Err(Error::Validation(format!("Every drone leaves the crop box {}", crop)));
+        }
+        CropMode::Drop => {
+            drones.retain(|(drone_id, _)| leaving.binary_search(drone_id).is_err());
+            println!("Dropped {} drones leaving the crop box ({}), {} left", leaving.len(), ranges(&leaving), drones.len());
+        }
+        CropMode::Flag => progress::warning(format!("{} drones leave the crop box ({})", leaving.len(), ranges(&leaving)))
+    }
+    Ok(())
+}
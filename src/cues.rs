@@ -0,0 +1,34 @@
+//! Named cues (`Act 2`, `Pyro cue`, `Landing`) kept in the show's metadata, so viewers and
+//! crews can jump to a moment of the show instead of scrubbing to it.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::{self, Error, Result}, timing::parse_duration};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cue {
+    /// Seconds of show time.
+    pub time: f32,
+    pub label: String
+}
+
+/// Reads a CSV of `time, label` rows, with times in seconds of show time or as durations
+/// (`2m15s`). Cues come back in time order; cues at the same time keep their file order.
+pub fn load(fname: &Path) -> Result<Vec<Cue>> {
+    let mut reader = csv::Reader::from_path(fname).map_err(error::csv("Failed to open events file"))?;
+    let mut cues = vec![];
+    for record in reader.records() {
+        let record = record.map_err(error::csv("Failed to read events file"))?;
+        let label = record.get(1).map(str::trim).unwrap_or_default();
+        if label.is_empty() {
+            return Err(Error::Parse(format!("Missing label for the event at '{}' in events file.", &record[0])));
+        }
+        let time = parse_duration(&record[0]).ok()
+            .ok_or_else(|| Error::Parse(format!("Invalid time '{}' for event '{}'.", &record[0], label)))?;
+        cues.push(Cue { time, label: label.to_string() });
+    }
+    cues.sort_by(|a, b| a.time.total_cmp(&b.time));
+    Ok(cues)
+}
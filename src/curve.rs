@@ -0,0 +1,113 @@
+use clap::ValueEnum;
+
+use crate::{track::distance, AgentTraversal, AgentTraversals, Sample};
+
+/// How traversals between samples are encoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Curve {
+    /// One straight traversal per CSV sample
+    #[default]
+    Linear,
+    /// Cubic Bézier traversals fitted to the samples, with control points
+    Bezier
+}
+
+type Vec3 = [f32; 3];
+
+fn add(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: Vec3, s: f32) -> Vec3 {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+/// Point at `u` in 0..=1 on the cubic with control points `p`.
+pub fn bezier(p: [Vec3; 4], u: f32) -> Vec3 {
+    let v = 1.0 - u;
+    let weights = [v * v * v, 3.0 * u * v * v, 3.0 * u * u * v, u * u * u];
+    (0..4).fold([0.0; 3], |sum, i| add(sum, scale(p[i], weights[i])))
+}
+
+/// A sample in seconds and vviz axes (y up).
+fn vviz(sample: &Sample) -> (f64, Vec3) {
+    let [east, north, up] = sample.pos;
+    (sample.t / 1000.0, [east, up, north])
+}
+
+/// Least-squares inner control points for the samples `first..=last`, with the curve
+/// parameterised by time so the viewer reproduces the timing as well as the path.
+/// Returns `None` when there are no inner samples to fit (the segment is straight).
+fn fit_segment(samples: &[(f64, Vec3)], first: usize, last: usize) -> Option<(Vec3, Vec3)> {
+    let ((t0, p0), (t3, p3)) = (samples[first], samples[last]);
+    let (mut a11, mut a12, mut a22) = (0.0, 0.0, 0.0);
+    let (mut r1, mut r2) = ([0.0; 3], [0.0; 3]);
+    for &(t, p) in &samples[first + 1..last] {
+        let u = ((t - t0) / (t3 - t0)) as f32;
+        let v = 1.0 - u;
+        let (b1, b2) = (3.0 * u * v * v, 3.0 * u * u * v);
+        let residual = sub(p, add(scale(p0, v * v * v), scale(p3, u * u * u)));
+        a11 += b1 * b1;
+        a12 += b1 * b2;
+        a22 += b2 * b2;
+        r1 = add(r1, scale(residual, b1));
+        r2 = add(r2, scale(residual, b2));
+    }
+    let det = a11 * a22 - a12 * a12;
+    if last - first < 2 || det.abs() < 1e-9 {
+        return None;
+    }
+    let p1 = scale(sub(scale(r1, a22), scale(r2, a12)), 1.0 / det);
+    let p2 = scale(sub(scale(r2, a11), scale(r1, a12)), 1.0 / det);
+    Some((p1, p2))
+}
+
+/// The inner sample furthest from the fitted segment, with its distance.
+fn worst(samples: &[(f64, Vec3)], first: usize, last: usize, control: Option<(Vec3, Vec3)>) -> Option<(usize, f32)> {
+    let ((t0, p0), (t3, p3)) = (samples[first], samples[last]);
+    let (p1, p2) = control.unwrap_or((add(p0, scale(sub(p3, p0), 1.0 / 3.0)), add(p0, scale(sub(p3, p0), 2.0 / 3.0))));
+    (first + 1..last)
+        .map(|i| {
+            let (t, p) = samples[i];
+            (i, distance(bezier([p0, p1, p2, p3], ((t - t0) / (t3 - t0)) as f32), p))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// Fits cubic segments to the samples, splitting at the worst-fitting sample until every
+/// sample is within `tolerance` metres of the curve at its own time. Control points are
+/// stored relative to each segment's start, like the deltas.
+pub fn fit(samples: &[Sample], tolerance: f32) -> AgentTraversals {
+    let samples: Vec<(f64, Vec3)> = samples.iter().map(vviz).collect();
+    let mut traversals = vec![];
+    let mut stack = vec![(0, samples.len().saturating_sub(1))];
+    while let Some((first, last)) = stack.pop() {
+        if last <= first {
+            continue;
+        }
+        let control = fit_segment(&samples, first, last);
+        if let Some((split, error)) = worst(&samples, first, last, control) {
+            if error > tolerance {
+                // Right half first so segments come off the stack in time order.
+                stack.push((split, last));
+                stack.push((first, split));
+                continue;
+            }
+        }
+        let ((t0, p0), (t3, p3)) = (samples[first], samples[last]);
+        let delta = sub(p3, p0);
+        traversals.push(AgentTraversal {
+            dx: delta[0],
+            dy: delta[1],
+            dz: delta[2],
+            dt: Some((t3 - t0) as f32),
+            frames: None,
+            control_points: control.map(|(p1, p2)| [sub(p1, p0), sub(p2, p0)])
+        });
+    }
+    AgentTraversals(traversals)
+}
@@ -0,0 +1,49 @@
+use std::{fmt, str::FromStr};
+
+/// Reference the input altitudes are measured from.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum AltDatum {
+    /// Above the launch site's ground level, as the show expects
+    #[default]
+    Agl,
+    /// Above mean sea level, with the ground at this elevation (m)
+    Msl(f32)
+}
+
+impl AltDatum {
+
+    /// Elevation to subtract to get altitudes above ground.
+    pub fn ground(&self) -> f32 {
+        match self {
+            AltDatum::Agl => 0.0,
+            AltDatum::Msl(elevation) => *elevation
+        }
+    }
+}
+
+impl FromStr for AltDatum {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("agl") {
+            return Ok(AltDatum::Agl);
+        }
+        let Some(elevation) = s.get(..4).filter(|prefix| prefix.eq_ignore_ascii_case("msl:")).map(|_| &s[4..]) else {
+            return Err(format!("invalid altitude datum '{}' (expected agl or msl:<ground elevation>)", s));
+        };
+        match elevation.trim().parse::<f32>() {
+            Ok(elevation) if elevation.is_finite() => Ok(AltDatum::Msl(elevation)),
+            _ => Err(format!("invalid ground elevation '{}'", elevation))
+        }
+    }
+}
+
+impl fmt::Display for AltDatum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AltDatum::Agl => f.write_str("agl"),
+            AltDatum::Msl(elevation) => write!(f, "msl:{}", elevation)
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::{fs::File, io::Write, path::Path};
+
+use crate::{error::{self, Result}, Drone};
+
+/// Derivative of `values` sampled at `times` (seconds): central differences inside the
+/// track, one-sided at the ends.
+fn gradient(times: &[f64], values: &[[f64; 3]]) -> Vec<[f64; 3]> {
+    let n = values.len();
+    (0..n).map(|i| {
+        let (a, b) = (i.saturating_sub(1), (i + 1).min(n - 1));
+        let dt = times[b] - times[a];
+        if dt <= 0.0 {
+            return [0.0; 3];
+        }
+        [0, 1, 2].map(|axis| (values[b][axis] - values[a][axis]) / dt)
+    }).collect()
+}
+
+fn norm(v: [f64; 3]) -> f64 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+/// Writes a zip with one `Drone N.csv` per drone of
+/// `t,vx,vy,vz,speed,ax,ay,az,acceleration` rows: time in ms on the input clock, velocity
+/// (m/s) and acceleration (m/s²) along the input CSV axes.
+pub fn export(drones: &[Drone], fname: &Path) -> Result<()> {
+    let file = File::create(fname).map_err(error::io("Failed to create derivatives archive"))?;
+    let mut archive = zip::ZipWriter::new(std::io::BufWriter::new(file));
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    for (drone_id, samples) in drones {
+        let millis: Vec<f64> = samples.iter().map(|sample| sample.t).collect();
+        let seconds: Vec<f64> = millis.iter().map(|t| t / 1000.0).collect();
+        let positions: Vec<[f64; 3]> = samples.iter().map(|sample| sample.pos.map(f64::from)).collect();
+        let velocities = gradient(&seconds, &positions);
+        let accelerations = gradient(&seconds, &velocities);
+
+        archive.start_file(format!("Drone {}.csv", drone_id), options)
+            .map_err(error::zip("Failed to write derivatives archive"))?;
+        let mut csv = String::from("t,vx,vy,vz,speed,ax,ay,az,acceleration\n");
+        for ((t, v), a) in millis.iter().zip(&velocities).zip(&accelerations) {
+            csv.push_str(&format!(
+                "{},{:.4},{:.4},{:.4},{:.4},{:.4},{:.4},{:.4},{:.4}\n",
+                t, v[0], v[1], v[2], norm(*v), a[0], a[1], a[2], norm(*a)
+            ));
+        }
+        archive.write_all(csv.as_bytes()).map_err(error::io("Failed to write derivatives archive"))?;
+    }
+
+    archive.finish().map_err(error::zip("Failed to write derivatives archive"))?;
+    println!("Wrote velocity and acceleration tracks for {} drones to {}", drones.len(), fname.display());
+    Ok(())
+}
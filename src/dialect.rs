@@ -0,0 +1,187 @@
+use std::{collections::VecDeque, io::{self, BufRead, BufReader, Read}};
+
+use clap::Args;
+
+/// How the trajectory CSVs are written, for exports that stray from plain RFC 4180.
+#[derive(Args, Debug, Clone, Default)]
+pub struct CsvDialect {
+
+    /// Skip lines starting with this prefix anywhere in the file (`#`, `//`, ...). Leading
+    /// `#`, `//` and `;` lines and other preamble before the header are skipped regardless
+    #[arg(long, value_parser = prefix)]
+    comment: Option<String>,
+
+    /// Read the first line as the header even when it looks like preamble
+    #[arg(long)]
+    keep_preamble: bool,
+
+    /// Field separator (default `,`)
+    #[arg(long, value_parser = ascii_byte)]
+    delimiter: Option<u8>,
+
+    /// Quote character (default `"`)
+    #[arg(long, value_parser = ascii_byte)]
+    quote: Option<u8>,
+
+    /// Treat quote characters as ordinary data
+    #[arg(long, conflicts_with = "quote")]
+    no_quoting: bool,
+
+    /// Accept rows with more fields than the header, such as trailing commas or extra columns
+    #[arg(long)]
+    flexible: bool
+}
+
+fn prefix(s: &str) -> Result<String, String> {
+    match s {
+        "" => Err("the comment prefix can't be empty".to_string()),
+        s => Ok(s.to_string())
+    }
+}
+
+fn ascii_byte(s: &str) -> Result<u8, String> {
+    let s = if s == "\\t" { "\t" } else { s };
+    match s.as_bytes() {
+        [byte] if byte.is_ascii() => Ok(*byte),
+        _ => Err(format!("expected a single ASCII character, got '{}'", s))
+    }
+}
+
+impl CsvDialect {
+
+    /// CSV reader over `reader`, skipping its preamble. A one-byte comment prefix is left to
+    /// the CSV parser; longer ones are filtered line by line.
+    pub fn reader<R: Read>(&self, reader: R) -> csv::Reader<Preamble<R>> {
+        let single = self.comment.as_deref().filter(|prefix| prefix.len() == 1).map(|prefix| prefix.as_bytes()[0]);
+        let filtered = self.comment.clone().filter(|prefix| prefix.len() > 1);
+        let preamble = Preamble {
+            inner: BufReader::new(reader),
+            delimiter: self.delimiter.unwrap_or(b','),
+            prefix: filtered,
+            detect: !self.keep_preamble,
+            comment: self.comment.clone(),
+            started: false,
+            pending: VecDeque::new(),
+            line: vec![],
+            start: 0,
+            skipped: 0
+        };
+        let mut builder = csv::ReaderBuilder::new();
+        builder.comment(single)
+            .flexible(self.flexible)
+            .quoting(!self.no_quoting);
+        if let Some(delimiter) = self.delimiter {
+            builder.delimiter(delimiter);
+        }
+        if let Some(quote) = self.quote {
+            builder.quote(quote);
+        }
+        builder.from_reader(preamble)
+    }
+}
+
+/// Comment markers that always start preamble lines.
+const COMMENT_MARKERS: [&str; 3] = ["#", "//", ";"];
+
+/// Leading lines this far into a file are the most that count as preamble.
+const MAX_PREAMBLE: usize = 100;
+
+/// Reads a CSV without the lines before its header: comment lines and lines with fewer than
+/// four fields (`Exported by X on DATE`), which no trajectory or flight log has. When no
+/// such line is followed by anything else, nothing is skipped and the CSV parser reports
+/// what's wrong with the file.
+pub struct Preamble<R> {
+    inner: BufReader<R>,
+    delimiter: u8,
+    /// Multi-byte comment prefix filtered out of every line.
+    prefix: Option<String>,
+    detect: bool,
+    comment: Option<String>,
+    started: bool,
+    /// Lines read while looking for the end of the preamble, to hand out before the rest.
+    pending: VecDeque<Vec<u8>>,
+    /// The current line, from `start`.
+    line: Vec<u8>,
+    start: usize,
+    skipped: usize
+}
+
+impl<R: Read> Preamble<R> {
+
+    /// Number of preamble lines skipped so far.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    fn is_preamble(&self, line: &[u8]) -> bool {
+        let text = String::from_utf8_lossy(line);
+        let text = text.trim_start();
+        let marked = COMMENT_MARKERS.iter().copied().chain(self.comment.as_deref())
+            .any(|marker| text.starts_with(marker));
+        let fields = line.iter().filter(|&&byte| byte == self.delimiter).count() + 1;
+        marked || fields < 4
+    }
+
+    /// Reads ahead to the first line that isn't preamble.
+    fn skip_preamble(&mut self) -> io::Result<()> {
+        self.started = true;
+        loop {
+            let mut line = vec![];
+            if self.inner.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let preamble = self.is_preamble(&line);
+            self.pending.push_back(line);
+            if !preamble {
+                self.skipped = self.pending.len() - 1;
+                self.pending.drain(..self.skipped);
+                return Ok(());
+            }
+            if self.pending.len() > MAX_PREAMBLE {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the next line worth handing out into `line`; false at the end of the input.
+    fn next_line(&mut self) -> io::Result<bool> {
+        loop {
+            self.start = 0;
+            match self.pending.pop_front() {
+                Some(line) => self.line = line,
+                None => {
+                    self.line.clear();
+                    if self.inner.read_until(b'\n', &mut self.line)? == 0 {
+                        return Ok(false);
+                    }
+                }
+            }
+            if self.prefix.as_ref().is_some_and(|prefix| self.line.starts_with(prefix.as_bytes())) {
+                continue;
+            }
+            return Ok(true);
+        }
+    }
+}
+
+impl<R: Read> Read for Preamble<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.started && self.detect {
+            self.skip_preamble()?;
+        }
+        if self.start == self.line.len() {
+            // Past the preamble, lines only need splitting out to filter a long prefix.
+            if self.pending.is_empty() && self.prefix.is_none() {
+                return self.inner.read(buf);
+            }
+            if !self.next_line()? {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.line.len() - self.start);
+        buf[..n].copy_from_slice(&self.line[self.start..self.start + n]);
+        self.start += n;
+        Ok(n)
+    }
+}
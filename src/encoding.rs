@@ -0,0 +1,188 @@
+//! Text encodings of CSV entries. Spreadsheet round trips save CSVs as UTF-16 with a byte
+//! order mark; those are transcoded to UTF-8 as they are read, and a UTF-8 BOM is dropped.
+
+use std::io::{self, Read};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be
+}
+
+/// Reads `inner` as UTF-8, whatever its encoding. The encoding is told from the BOM or, for
+/// UTF-16 without one, from a zero byte next to the first character.
+pub struct Decoder<R> {
+    inner: R,
+    encoding: Option<Encoding>,
+    /// Decoded bytes not yet handed out, from `start`.
+    out: Vec<u8>,
+    start: usize,
+    /// A trailing odd byte or high surrogate carried over to the next chunk.
+    odd: Option<u8>,
+    high: Option<u16>
+}
+
+const CHUNK: usize = 8192;
+
+impl<R: Read> Decoder<R> {
+
+    pub fn new(inner: R) -> Self {
+        Decoder { inner, encoding: None, out: vec![], start: 0, odd: None, high: None }
+    }
+
+    /// Reads up to three bytes to tell the encoding, keeping whatever isn't a BOM.
+    fn detect(&mut self) -> io::Result<()> {
+        let mut head = [0; 3];
+        let mut len = 0;
+        while len < head.len() {
+            match self.inner.read(&mut head[len..])? {
+                0 => break,
+                n => len += n
+            }
+        }
+        let head = &head[..len];
+        let (encoding, bom) = match head {
+            [0xef, 0xbb, 0xbf, ..] => (Encoding::Utf8, 3),
+            [0xff, 0xfe, ..] => (Encoding::Utf16Le, 2),
+            [0xfe, 0xff, ..] => (Encoding::Utf16Be, 2),
+            [c, 0, ..] if *c != 0 => (Encoding::Utf16Le, 0),
+            [0, c, ..] if *c != 0 => (Encoding::Utf16Be, 0),
+            _ => (Encoding::Utf8, 0)
+        };
+        self.encoding = Some(encoding);
+        match encoding {
+            Encoding::Utf8 => self.out.extend_from_slice(&head[bom..]),
+            _ => self.decode(&head[bom..])
+        }
+        Ok(())
+    }
+
+    /// Appends the UTF-8 of the UTF-16 `bytes`, carrying split code units and surrogate pairs.
+    fn decode(&mut self, bytes: &[u8]) {
+        let mut data: Vec<u8> = self.odd.take().into_iter().collect();
+        data.extend_from_slice(bytes);
+        let pairs = data.chunks_exact(2);
+        self.odd = pairs.remainder().first().copied();
+        let mut units: Vec<u16> = self.high.take().into_iter().collect();
+        units.extend(pairs.map(|pair| match self.encoding {
+            Some(Encoding::Utf16Be) => u16::from_be_bytes([pair[0], pair[1]]),
+            _ => u16::from_le_bytes([pair[0], pair[1]])
+        }));
+        if units.last().is_some_and(|unit| (0xd800..0xdc00).contains(unit)) {
+            self.high = units.pop();
+        }
+        let mut buffer = [0; 4];
+        for c in char::decode_utf16(units) {
+            let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+            self.out.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+        }
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.encoding.is_none() {
+            self.detect()?;
+        }
+        while self.start == self.out.len() {
+            self.out.clear();
+            self.start = 0;
+            if self.encoding == Some(Encoding::Utf8) {
+                return self.inner.read(buf);
+            }
+            let mut chunk = [0; CHUNK];
+            let n = self.inner.read(&mut chunk)?;
+            if n == 0 {
+                if self.odd.take().is_some() || self.high.take().is_some() {
+                    self.out.extend_from_slice(char::REPLACEMENT_CHARACTER.to_string().as_bytes());
+                    continue;
+                }
+                return Ok(0);
+            }
+            self.decode(&chunk[..n]);
+        }
+        let n = buf.len().min(self.out.len() - self.start);
+        buf[..n].copy_from_slice(&self.out[self.start..self.start + n]);
+        self.start += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::Decoder;
+
+    /// Hands out one byte per read, so every code unit and surrogate pair is split.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let Some((first, rest)) = self.0.split_first().filter(|_| !buf.is_empty()) else {
+                return Ok(0);
+            };
+            buf[0] = *first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> String {
+        let mut whole = String::new();
+        Decoder::new(bytes).read_to_string(&mut whole).unwrap();
+        let mut trickled = String::new();
+        Decoder::new(Trickle(bytes)).read_to_string(&mut trickled).unwrap();
+        assert_eq!(whole, trickled);
+        whole
+    }
+
+    fn utf16le(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn utf16be(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(u16::to_be_bytes).collect()
+    }
+
+    #[test]
+    fn utf8() {
+        assert_eq!(decode(b""), "");
+        assert_eq!(decode(b"t"), "t");
+        assert_eq!(decode("t,x\n0,é\n".as_bytes()), "t,x\n0,é\n");
+        assert_eq!(decode(b"\xef\xbb\xbft,x\n"), "t,x\n");
+        assert_eq!(decode(b"\xef\xbb\xbf"), "");
+    }
+
+    #[test]
+    fn utf16_with_bom() {
+        let text = "Time [msec],x [m]\n0,1.5 é\n";
+        assert_eq!(decode(&[&[0xff, 0xfe][..], &utf16le(text)].concat()), text);
+        assert_eq!(decode(&[&[0xfe, 0xff][..], &utf16be(text)].concat()), text);
+    }
+
+    #[test]
+    fn utf16_without_bom() {
+        let text = "t,x,y,z\n0,1,2,3\n";
+        assert_eq!(decode(&utf16le(text)), text);
+        assert_eq!(decode(&utf16be(text)), text);
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        let text = "drone \u{1f681}\n";
+        assert_eq!(utf16le("\u{1f681}"), [0x3d, 0xd8, 0x81, 0xde]);
+        assert_eq!(decode(&[&[0xff, 0xfe][..], &utf16le(text)].concat()), text);
+        assert_eq!(decode(&[&[0xfe, 0xff][..], &utf16be(text)].concat()), text);
+    }
+
+    #[test]
+    fn broken_utf16_is_replaced() {
+        // A lone high surrogate at the end, then a trailing odd byte.
+        assert_eq!(decode(&[0xff, 0xfe, b'a', 0x00, 0x3d, 0xd8]), "a\u{fffd}");
+        assert_eq!(decode(&[0xff, 0xfe, b'a', 0x00, b'b']), "a\u{fffd}");
+        // A low surrogate with no high one before it.
+        assert_eq!(decode(&[0xff, 0xfe, 0x81, 0xde, b'a', 0x00]), "\u{fffd}a");
+    }
+}
@@ -0,0 +1,69 @@
+use std::{io::Write, path::Path};
+
+use clap::Args;
+
+use crate::{error::{self, Result}, track::{distance, Track}, Show};
+
+/// Rough battery model: a constant hover draw plus a drag term growing with the cube of
+/// airspeed, ignoring wind, climb efficiency and battery sag.
+#[derive(Args, Debug, Clone)]
+pub struct EnergyModel {
+
+    /// Power drawn while hovering (W)
+    #[arg(long, default_value_t = 180.0)]
+    pub hover_power: f32,
+
+    /// Extra power per cubed airspeed (W·s³/m³)
+    #[arg(long, default_value_t = 0.5)]
+    pub drag_coefficient: f32
+}
+
+impl Default for EnergyModel {
+    fn default() -> Self {
+        EnergyModel { hover_power: 180.0, drag_coefficient: 0.5 }
+    }
+}
+
+impl EnergyModel {
+
+    /// Estimated energy in Wh to fly the track.
+    pub fn estimate(&self, track: &Track) -> f32 {
+        let joules: f32 = track.samples.windows(2).map(|w| {
+            let ((t0, a), (t1, b)) = (w[0], w[1]);
+            let dt = t1 - t0;
+            if dt <= 0.0 {
+                return 0.0;
+            }
+            let speed = distance(a, b) / dt;
+            (self.hover_power + self.drag_coefficient * speed.powi(3)) * dt
+        }).sum();
+        joules / 3600.0
+    }
+}
+
+/// Writes per-drone flight time and estimated energy as `drone,flight_time,energy_wh`.
+pub fn energy_report(show: &Show, model: &EnergyModel, fname: Option<&Path>) -> Result<()> {
+    let mut report = fname.map(|fname| -> Result<std::fs::File> {
+        let mut report = std::fs::File::create(fname).map_err(error::io("Failed to create energy report"))?;
+        writeln!(report, "drone,flight_time,energy_wh").map_err(error::io("Failed to write energy report"))?;
+        Ok(report)
+    }).transpose()?;
+
+    let mut highest: Option<(usize, f32)> = None;
+    for performance in &show.performances {
+        let track = Track::new(performance, show.default_position_rate);
+        let drone_id = performance.id + 1;
+        let energy = model.estimate(&track);
+        if let Some(report) = report.as_mut() {
+            writeln!(report, "{},{:.3},{:.3}", drone_id, track.duration(), energy).map_err(error::io("Failed to write energy report"))?;
+        }
+        if highest.is_none_or(|(_, most)| energy > most) {
+            highest = Some((drone_id, energy));
+        }
+    }
+
+    if let Some((drone_id, energy)) = highest {
+        println!("Highest estimated energy use: drone {} at {:.2} Wh", drone_id, energy);
+    }
+    Ok(())
+}
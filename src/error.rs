@@ -0,0 +1,131 @@
+//! Failure categories and the process exit codes scripts can branch on:
+//!
+//! | code | meaning                                                        |
+//! |------|----------------------------------------------------------------|
+//! | 0    | success                                                        |
+//! | 1    | invalid command line                                           |
+//! | 2    | unsupported or corrupt input format (not a zip/tar archive)    |
+//! | 3    | parse error in an input file (CSV values, config, show JSON)   |
+//! | 4    | validation failed (flight limits, checksum, alignment inputs)  |
+//! | 5    | IO error (missing files, unwritable outputs, failed downloads) |
+
+use std::fmt::{self, Display};
+
+#[derive(Debug)]
+pub enum Error {
+    Format(String),
+    Parse(String),
+    Validation(String),
+    Io(String)
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Exit status for command-line errors reported by clap.
+pub const USAGE_EXIT_CODE: i32 = 1;
+
+impl Error {
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Format(_) => 2,
+            Error::Parse(_) => 3,
+            Error::Validation(_) => 4,
+            Error::Io(_) => 5
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Format(message) | Error::Parse(message) | Error::Validation(message) | Error::Io(message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// `.map_err(error::io("Failed to open color manifest"))`
+pub fn io<E: Display>(context: &str) -> impl FnOnce(E) -> Error + '_ {
+    move |e| Error::Io(format!("{}: {}", context, e))
+}
+
+pub fn parse<E: Display>(context: &str) -> impl FnOnce(E) -> Error + '_ {
+    move |e| Error::Parse(format!("{}: {}", context, e))
+}
+
+/// CSV reader errors are IO errors when the underlying read failed and parse errors otherwise
+/// (ragged rows, invalid UTF-8, ...).
+pub fn csv(context: &str) -> impl FnOnce(csv::Error) -> Error + '_ {
+    move |e| if e.is_io_error() {
+        Error::Io(format!("{}: {}", context, e))
+    } else {
+        Error::Parse(format!("{}: {}", context, e))
+    }
+}
+
+/// A damaged or unsupported zip is a format error; failing to read it at all is IO.
+pub fn zip(context: &str) -> impl FnOnce(zip::result::ZipError) -> Error + '_ {
+    move |e| match e {
+        zip::result::ZipError::Io(e) => Error::Io(format!("{}: {}", context, e)),
+        e => Error::Format(format!("{}: {}", context, e))
+    }
+}
+
+/// Collects parse errors so every bad row of an input can be reported at once instead of
+/// stopping at the first. At most `limit` messages are kept; the rest are only counted.
+#[derive(Debug)]
+pub struct ErrorReport {
+    messages: Vec<String>,
+    total: usize,
+    limit: usize
+}
+
+impl ErrorReport {
+
+    pub fn new(limit: usize) -> Self {
+        ErrorReport { messages: vec![], total: 0, limit }
+    }
+
+    /// Records a parse error. Any other kind is returned, since reading can't carry on.
+    pub fn push(&mut self, e: Error) -> Result<()> {
+        let Error::Parse(message) = e else {
+            return Err(e);
+        };
+        self.total += 1;
+        if self.messages.len() < self.limit {
+            self.messages.push(message);
+        }
+        Ok(())
+    }
+
+    /// Records everything `other` collected, after what is here already.
+    pub fn append(&mut self, other: ErrorReport) {
+        let room = self.limit.saturating_sub(self.messages.len());
+        self.messages.extend(other.messages.into_iter().take(room));
+        self.total += other.total;
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Fails with all the collected messages if anything was recorded.
+    pub fn finish(self) -> Result<()> {
+        if self.total == 0 {
+            return Ok(());
+        }
+        let mut message = format!("{} parse errors:", self.total);
+        for line in &self.messages {
+            message.push_str("\n  ");
+            message.push_str(line);
+        }
+        if self.total > self.messages.len() {
+            message.push_str(&format!("\n  ... and {} more", self.total - self.messages.len()));
+        }
+        Err(Error::Parse(message))
+    }
+}
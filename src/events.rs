@@ -0,0 +1,55 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{error::{self, Error, Result}, field, EventAction, Payload, PayloadAction};
+
+/// Non-light payload triggers (pyro, stage effects, ...) read from a CSV of
+/// `id, time, channel, action` rows. `id` is the drone number, `time` is in milliseconds on
+/// the same clock as the trajectory files, and `channel` becomes the payload's type string.
+#[derive(Debug, Default)]
+pub struct PayloadEvents(BTreeMap<usize, BTreeMap<String, Vec<(f64, String)>>>);
+
+impl PayloadEvents {
+
+    pub fn load(fname: &Path) -> Result<Self> {
+        let mut reader = csv::Reader::from_path(fname)
+            .map_err(error::csv("Failed to open payload events file"))?;
+        let mut events = PayloadEvents::default();
+        for record in reader.records() {
+            let record = record.map_err(error::csv("Failed to read payload events file"))?;
+            let file = fname.display().to_string();
+            let cell = |index: usize, column: &str| field::cell(&record, index, column, &file);
+            let id = field::parse::<usize>(cell(0, "id")?)
+                .map_err(|_| Error::Parse(format!("Invalid drone id '{}' in payload events file.", &record[0])))?;
+            let time = field::parse::<f64>(cell(1, "time")?)
+                .map_err(|_| Error::Parse(format!("Invalid event time '{}' for drone {}.", &record[1], id)))?;
+            events.0.entry(id).or_default()
+                .entry(cell(2, "channel")?.trim().to_string()).or_default()
+                .push((time, cell(3, "action")?.trim().to_string()));
+        }
+        Ok(events)
+    }
+
+    /// Builds one payload description per channel used by the drone, numbered from
+    /// `first_id`. Event times are made relative to the performance start (`start`, ms).
+    pub fn payloads(&self, drone_id: usize, start: f64, first_id: usize) -> Vec<Payload> {
+        let Some(channels) = self.0.get(&drone_id) else {
+            return vec![];
+        };
+        channels.iter().enumerate().map(|(index, (channel, events))| {
+            let mut events = events.clone();
+            events.sort_by(|a, b| a.0.total_cmp(&b.0));
+            Payload {
+                id: first_id + index,
+                payload_type: channel.clone(),
+                actions: events.into_iter().map(|(time, action)| PayloadAction::Event(EventAction {
+                    time: ((time - start) / 1000.0) as f32,
+                    action
+                })).collect()
+            }
+        }).collect()
+    }
+
+    pub fn drone_ids(&self) -> impl Iterator<Item = &usize> {
+        self.0.keys()
+    }
+}
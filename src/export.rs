@@ -0,0 +1,336 @@
+use std::{io::{Cursor, Write}, path::PathBuf, str::FromStr};
+
+use clap::{Args, ValueEnum};
+
+use crate::{error::{self, Result}, flightlog, keyframes::{self, Keyframe}, output::{OutputFormat, OutputOptions}, read_show, track::{distance, Track}, PayloadAction, Performance, Show};
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+
+    /// Converted show to export
+    show: PathBuf,
+
+    #[arg(long, value_enum)]
+    format: ExportFormat,
+
+    /// Waypoints per drone, including the first and last position; crazyswarm trajectories
+    /// get one polynomial piece between each pair
+    #[arg(long, default_value_t = 20)]
+    waypoints: usize,
+
+    /// WGS84 position of the show origin: `latitude,longitude[,altitude]`, altitude in meters
+    /// above sea level
+    #[arg(long, required_if_eq_any([("format", "qgc-wpl"), ("format", "gpx")]))]
+    origin: Option<GeoOrigin>,
+
+    /// Output file: a zip with one file per drone for missions and trajectories, a single file
+    /// for `flat-csv` and `gpx` (defaults to `<show>.missions.zip`, `<show>.trajectories.zip`,
+    /// `<show>.flat.csv` or `<show>.gpx`)
+    #[arg(short, long)]
+    output: Option<PathBuf>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// QGroundControl `QGC WPL 110` waypoint files (MAVLink missions)
+    QgcWpl,
+    /// Crazyswarm `uav_trajectory` piecewise-polynomial CSVs
+    Crazyswarm,
+    /// One CSV of every drone's absolute position and color over time
+    FlatCsv,
+    /// One GPX file with a track per drone, for mapping tools and GPS devices
+    Gpx
+}
+
+impl ExportFormat {
+
+    fn writer(&self) -> &'static dyn OutputFormat {
+        match self {
+            ExportFormat::QgcWpl => &QgcWpl,
+            ExportFormat::Crazyswarm => &Crazyswarm,
+            ExportFormat::FlatCsv => &FlatCsv,
+            ExportFormat::Gpx => &Gpx
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GeoOrigin {
+    latitude: f64,
+    longitude: f64,
+    altitude: f64
+}
+
+impl FromStr for GeoOrigin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s.split(',')
+            .map(|value| value.trim().parse::<f64>().map_err(|_| format!("invalid coordinate '{}'", value.trim())))
+            .collect::<Result<Vec<f64>, String>>()?;
+        match values[..] {
+            [latitude, longitude] => Ok(GeoOrigin { latitude, longitude, altitude: 0.0 }),
+            [latitude, longitude, altitude] => Ok(GeoOrigin { latitude, longitude, altitude }),
+            _ => Err(format!("expected latitude,longitude[,altitude], found {} values", values.len()))
+        }
+    }
+}
+
+/// Altitude (m) below which a waypoint counts as on the ground, for takeoff and landing.
+const GROUND: f32 = 0.5;
+
+const MAV_CMD_NAV_WAYPOINT: u32 = 16;
+const MAV_CMD_NAV_LAND: u32 = 21;
+const MAV_CMD_NAV_TAKEOFF: u32 = 22;
+const MAV_CMD_DO_CHANGE_SPEED: u32 = 178;
+const MAV_FRAME_GLOBAL: u32 = 0;
+const MAV_FRAME_GLOBAL_RELATIVE_ALT: u32 = 3;
+
+/// Mission items: command, frame, param 1, param 2, and position (east, north, up) if any.
+type Item = (u32, u32, f32, f32, Option<[f32; 3]>);
+
+/// Takes off from the first waypoint when it is on the ground, flies each leg at the speed
+/// that keeps the show's timing, loiters where the drone holds and lands at the last
+/// waypoint when it is on the ground.
+fn mission(waypoints: &[Keyframe]) -> Vec<Item> {
+    let mut items: Vec<Item> = vec![];
+    for (i, (t, position)) in waypoints.iter().enumerate() {
+        if i == 0 {
+            let command = if position[2] < GROUND { MAV_CMD_NAV_TAKEOFF } else { MAV_CMD_NAV_WAYPOINT };
+            let altitude = waypoints.iter().map(|(_, p)| p[2]).find(|z| *z >= GROUND).unwrap_or(position[2]);
+            items.push((command, MAV_FRAME_GLOBAL_RELATIVE_ALT, 0.0, 0.0, Some([position[0], position[1], altitude])));
+            continue;
+        }
+        let (previous_t, previous) = waypoints[i - 1];
+        let dt = ((t - previous_t) / 1000.0) as f32;
+        let length = distance(previous, *position);
+        let vertical = distance([previous[0], previous[1], 0.0], [position[0], position[1], 0.0]) < 0.05;
+        if i == 1 && items[0].0 == MAV_CMD_NAV_TAKEOFF && vertical {
+            // The takeoff already climbs to this waypoint.
+            continue;
+        }
+        if length < 0.05 {
+            // Holding: loiter at the previous waypoint instead of adding a new one.
+            if let Some(item) = items.iter_mut().rev().find(|item| item.0 == MAV_CMD_NAV_WAYPOINT) {
+                item.2 += dt;
+            }
+            continue;
+        }
+        if dt > 0.0 {
+            items.push((MAV_CMD_DO_CHANGE_SPEED, MAV_FRAME_GLOBAL_RELATIVE_ALT, 1.0, length / dt, None));
+        }
+        let last = i == waypoints.len() - 1;
+        let command = if last && position[2] < GROUND { MAV_CMD_NAV_LAND } else { MAV_CMD_NAV_WAYPOINT };
+        items.push((command, MAV_FRAME_GLOBAL_RELATIVE_ALT, 0.0, 0.0, Some([position[0], position[1], position[2].max(0.0)])));
+    }
+    items
+}
+
+fn qgc_wpl(items: &[Item], home: [f32; 3], origin: GeoOrigin) -> String {
+    let global = |p: [f32; 3]| flightlog::global(p[0] as f64, p[1] as f64, (origin.latitude, origin.longitude));
+    let (latitude, longitude) = global(home);
+    let mut text = String::from("QGC WPL 110\n");
+    text.push_str(&format!(
+        "0\t1\t{}\t{}\t0\t0\t0\t0\t{:.8}\t{:.8}\t{:.2}\t1\n",
+        MAV_FRAME_GLOBAL, MAV_CMD_NAV_WAYPOINT, latitude, longitude, origin.altitude + home[2] as f64
+    ));
+    for (index, (command, frame, param1, param2, position)) in items.iter().enumerate() {
+        let (latitude, longitude, altitude) = match position {
+            Some(p) => {
+                let (latitude, longitude) = global(*p);
+                (latitude, longitude, p[2])
+            }
+            None => (0.0, 0.0, 0.0)
+        };
+        text.push_str(&format!(
+            "{}\t0\t{}\t{}\t{:.2}\t{:.2}\t0\t0\t{:.8}\t{:.8}\t{:.2}\t1\n",
+            index + 1, frame, command, param1, param2, latitude, longitude, altitude
+        ));
+    }
+    text
+}
+
+/// Cubic Hermite pieces through the waypoints, as 7th-order `uav_trajectory` rows with the
+/// unused coefficients zero. Velocities at inner waypoints follow the neighbouring
+/// waypoints; the drone starts and ends at rest.
+fn crazyswarm(waypoints: &[Keyframe]) -> String {
+    let mut text = String::from("Duration");
+    for axis in ["x", "y", "z", "yaw"] {
+        for power in 0..8 {
+            text.push_str(&format!(",{}^{}", axis, power));
+        }
+    }
+    text.push('\n');
+
+    let seconds = |i: usize| waypoints[i].0 / 1000.0;
+    let velocity = |i: usize| -> [f64; 3] {
+        if i == 0 || i == waypoints.len() - 1 {
+            return [0.0; 3];
+        }
+        let dt = seconds(i + 1) - seconds(i - 1);
+        let (a, b) = (waypoints[i - 1].1, waypoints[i + 1].1);
+        [0, 1, 2].map(|axis| if dt > 0.0 { (b[axis] - a[axis]) as f64 / dt } else { 0.0 })
+    };
+    for i in 1..waypoints.len() {
+        let duration = seconds(i) - seconds(i - 1);
+        if duration <= 0.0 {
+            continue;
+        }
+        let (p0, p1, v0, v1) = (waypoints[i - 1].1, waypoints[i].1, velocity(i - 1), velocity(i));
+        text.push_str(&format!("{}", duration));
+        for axis in 0..3 {
+            let (p0, p1) = (p0[axis] as f64, p1[axis] as f64);
+            let a2 = (3.0 * (p1 - p0) / duration - 2.0 * v0[axis] - v1[axis]) / duration;
+            let a3 = (2.0 * (p0 - p1) / duration + v0[axis] + v1[axis]) / duration.powi(2);
+            text.push_str(&format!(",{},{},{},{},0,0,0,0", p0, v0[axis], a2, a3));
+        }
+        text.push_str(",0,0,0,0,0,0,0,0\n");
+    }
+    text
+}
+
+pub type Rgb = (u8, u8, u8);
+
+/// Light payload color of the performance at `t` seconds, holding the last one.
+pub fn color_at(performance: &Performance, color_rate: f32, t: f32) -> Option<Rgb> {
+    let light = performance.payload.iter().find(|payload| payload.payload_type == "light")?;
+    let mut end = 0.0;
+    let mut color = None;
+    for action in &light.actions {
+        if let PayloadAction::Color(action) = action {
+            color = Some((action.r, action.g, action.b));
+            end += action.frames.unwrap_or(1) as f32 / color_rate;
+            if t < end {
+                break;
+            }
+        }
+    }
+    color
+}
+
+/// Every drone's absolute positions in one `time,drone_id,x,y,z,r,g,b` table ordered by time,
+/// in the trajectory CSVs' axes. Colors are left empty for drones without a light payload.
+fn flat_csv(show: &Show) -> String {
+    let mut rows: Vec<(f32, usize, [f32; 3], Option<Rgb>)> = vec![];
+    for performance in &show.performances {
+        let track = Track::new(performance, show.default_position_rate);
+        for (t, p) in &track.samples {
+            rows.push((*t, performance.id + 1, [p[0], p[2], p[1]], color_at(performance, show.default_color_rate, *t)));
+        }
+    }
+    rows.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+    let mut text = String::from("time,drone_id,x,y,z,r,g,b\n");
+    for (t, drone_id, [x, y, z], color) in rows {
+        let color = color.map_or(",,".to_string(), |(r, g, b)| format!("{},{},{}", r, g, b));
+        text.push_str(&format!("{},{},{},{},{},{}\n", t, drone_id, x, y, z, color));
+    }
+    text
+}
+
+/// Every drone's full track, placed around `origin`, with elevations above sea level.
+fn gpx(show: &Show, origin: GeoOrigin) -> String {
+    let mut text = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<gpx version=\"1.1\" creator=\"csv2vviz\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n"
+    ));
+    for performance in &show.performances {
+        text.push_str(&format!("  <trk>\n    <name>Drone {}</name>\n    <trkseg>\n", performance.id + 1));
+        for (_, p) in Track::new(performance, show.default_position_rate).samples {
+            let (latitude, longitude) = flightlog::global(p[0] as f64, p[2] as f64, (origin.latitude, origin.longitude));
+            text.push_str(&format!(
+                "      <trkpt lat=\"{:.8}\" lon=\"{:.8}\"><ele>{:.2}</ele></trkpt>\n",
+                latitude, longitude, origin.altitude + p[1] as f64
+            ));
+        }
+        text.push_str("    </trkseg>\n  </trk>\n");
+    }
+    text.push_str("</gpx>\n");
+    text
+}
+
+/// One file per drone in a zip, named and written by `file` from the drone number, its
+/// decimated waypoints and its first position (east, north, up).
+fn per_drone(show: &Show, waypoints: usize, file: impl Fn(usize, &[Keyframe], [f32; 3]) -> (String, String)) -> Result<Vec<u8>> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for performance in &show.performances {
+        // Track samples are (x, up, north) in seconds; missions work in east/north/up.
+        let track = Track::new(performance, show.default_position_rate);
+        let samples: Vec<Keyframe> = track.samples.iter()
+            .map(|(t, p)| (*t as f64 * 1000.0, [p[0], p[2], p[1]]))
+            .collect();
+        let (name, text) = file(performance.id + 1, &keyframes::decimate(&samples, waypoints), samples[0].1);
+        writer.start_file(name, options).map_err(error::zip("Failed to write export"))?;
+        writer.write_all(text.as_bytes()).map_err(error::io("Failed to write export"))?;
+    }
+    Ok(writer.finish().map_err(error::zip("Failed to write export"))?.into_inner())
+}
+
+/// A zip of QGroundControl missions, one per drone.
+struct QgcWpl;
+
+impl OutputFormat for QgcWpl {
+
+    fn extension(&self) -> &'static str {
+        "missions.zip"
+    }
+
+    fn write(&self, show: &Show, options: &OutputOptions) -> Result<Vec<u8>> {
+        let origin = options.origin.expect("clap requires --origin for missions");
+        per_drone(show, options.waypoints, |drone_id, waypoints, home| {
+            (format!("Drone {}.waypoints", drone_id), qgc_wpl(&mission(waypoints), home, origin))
+        })
+    }
+}
+
+/// A zip of crazyswarm trajectories, one per drone.
+struct Crazyswarm;
+
+impl OutputFormat for Crazyswarm {
+
+    fn extension(&self) -> &'static str {
+        "trajectories.zip"
+    }
+
+    fn write(&self, show: &Show, options: &OutputOptions) -> Result<Vec<u8>> {
+        per_drone(show, options.waypoints, |drone_id, waypoints, _| (format!("Drone {}.csv", drone_id), crazyswarm(waypoints)))
+    }
+}
+
+struct FlatCsv;
+
+impl OutputFormat for FlatCsv {
+
+    fn extension(&self) -> &'static str {
+        "flat.csv"
+    }
+
+    fn write(&self, show: &Show, _options: &OutputOptions) -> Result<Vec<u8>> {
+        Ok(flat_csv(show).into_bytes())
+    }
+}
+
+struct Gpx;
+
+impl OutputFormat for Gpx {
+
+    fn extension(&self) -> &'static str {
+        "gpx"
+    }
+
+    fn write(&self, show: &Show, options: &OutputOptions) -> Result<Vec<u8>> {
+        Ok(gpx(show, options.origin.expect("clap requires --origin for GPX")).into_bytes())
+    }
+}
+
+/// Writes the show's trajectories in the chosen format.
+pub fn run(args: ExportArgs) -> Result<()> {
+    let show = read_show(&args.show)?;
+    let format = args.format.writer();
+    let output = args.output.clone().unwrap_or_else(|| args.show.with_extension(format.extension()));
+    let bytes = format.write(&show, &OutputOptions { waypoints: args.waypoints, origin: args.origin })?;
+    std::fs::write(&output, bytes).map_err(error::io("Failed to write export"))?;
+    println!("Wrote {} drones to {}", show.performances.len(), output.display());
+    Ok(())
+}
@@ -0,0 +1,47 @@
+//! `csv2vviz extract`: one drone of a converted show back out as a trajectory CSV.
+
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::{error::{self, Error, Result}, export::color_at, read_show, track::Track};
+
+#[derive(Args, Debug)]
+pub struct ExtractArgs {
+
+    /// Converted show to read
+    show: PathBuf,
+
+    /// Drone number, as in the `Drone N` CSV names
+    #[arg(short, long)]
+    drone: usize,
+
+    /// Output CSV (defaults to `Drone <N>.csv` next to the show)
+    #[arg(short, long)]
+    output: Option<PathBuf>
+}
+
+/// Writes the drone's absolute positions, in the trajectory CSVs' axes, with its light color
+/// at each of them, in the column layout `convert` reads. Drones without lights are written
+/// as dark.
+pub fn run(args: ExtractArgs) -> Result<()> {
+    let show = read_show(&args.show)?;
+    let performance = show.performances.iter().find(|performance| performance.id + 1 == args.drone)
+        .ok_or_else(|| Error::Parse(format!("{} has no drone {}", args.show.display(), args.drone)))?;
+
+    if !performance.payload.iter().any(|payload| payload.payload_type == "light") {
+        println!("Warning: drone {} has no light payload; writing it as dark", args.drone);
+    }
+    let track = Track::new(performance, show.default_position_rate);
+    let mut csv = String::from("Time [msec],x [m],y [m],z [m],Red,Green,Blue\n");
+    for (t, [x, y, z]) in &track.samples {
+        let (r, g, b) = color_at(performance, show.default_color_rate, *t).unwrap_or((0, 0, 0));
+        let millis = (*t as f64 * 1e6).round() / 1e3;
+        csv.push_str(&format!("{},{},{},{},{},{},{}\n", millis, x, z, y, r, g, b));
+    }
+
+    let output = args.output.unwrap_or_else(|| args.show.with_file_name(format!("Drone {}.csv", args.drone)));
+    std::fs::write(&output, csv).map_err(error::io("Failed to write drone CSV"))?;
+    println!("Wrote {} samples of drone {} to {}", track.samples.len(), args.drone, output.display());
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+pub fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// File name of the URL's last path segment, ignoring any query string or fragment.
+pub fn file_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let name = path.trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+    if name.is_empty() || name.contains(':') {
+        "download.zip".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Downloads `url` to a file in the system temp directory using `curl`, returning its path.
+/// The caller removes it with [`cleanup`] once done.
+#[cfg(feature = "fetch")]
+pub fn download(url: &str) -> Result<PathBuf> {
+    let target = create_target(url)?;
+    println!("Downloading {}", url);
+    let status = std::process::Command::new("curl")
+        .args(["--fail", "--location", "--silent", "--show-error", "--output"])
+        .arg(&target)
+        .arg(url)
+        .status()
+        .map_err(crate::error::io("Failed to run curl; it is needed to download URL inputs"))?;
+    if !status.success() {
+        cleanup(&target);
+        return Err(Error::Io(format!("Failed to download {}.", url)));
+    }
+    Ok(target)
+}
+
+/// Creates the file a download goes to under a random name in the temp directory, so that
+/// concurrent downloads of files with the same name don't meet and no other user can put
+/// something (a symlink for curl to follow) in its place first. Ends in the URL's file name,
+/// whose extension tells the archive format.
+#[cfg(feature = "fetch")]
+fn create_target(url: &str) -> Result<PathBuf> {
+    let mut attempt = 0;
+    loop {
+        let path = std::env::temp_dir().join(format!("csv2vviz-{:016x}-{}", crate::spill::random(), file_name(url)));
+        let mut options = std::fs::File::options();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempt + 1 < crate::spill::ATTEMPTS => attempt += 1,
+            Err(e) => return Err(crate::error::io("Failed to create download file")(e))
+        }
+    }
+}
+
+#[cfg(not(feature = "fetch"))]
+pub fn download(url: &str) -> Result<PathBuf> {
+    Err(Error::Io(format!("Cannot download {}: csv2vviz was built without the `fetch` feature.", url)))
+}
+
+pub fn cleanup(path: &Path) {
+    let _ = std::fs::remove_file(path);
+}
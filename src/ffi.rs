@@ -0,0 +1,165 @@
+//! C interface for embedding the converter, exported from the cdylib (see
+//! `include/csv2vviz.h`):
+//!
+//! ```c
+//! char *result = csv2vviz_convert("show.zip", "{\"rotate\": \"0 0 90\", \"reproducible\": true}");
+//! /* {"ok":true,"outputs":["show.vviz"]} */
+//! csv2vviz_free_string(result);
+//! ```
+//!
+//! Option keys are the `convert` flag names (`out_dir` or `out-dir`). `true` passes a switch,
+//! `false` and `null` leave it out, and arrays repeat the flag. Failures come back as
+//! `{"ok":false,"error":"...","exitCode":N}` with the command-line exit codes.
+
+use std::{ffi::{c_char, CStr, CString}, path::PathBuf, panic};
+
+use clap::Parser;
+use serde_json::{json, Value};
+
+use crate::{error::{Error, Result}, pipeline, progress, Cli, Command, ConvertArgs};
+
+/// Command line equivalent to converting `path` with `options`.
+fn arguments(path: &str, options: &str) -> Result<Vec<String>> {
+    let options: Value = if options.trim().is_empty() {
+        Value::Object(Default::default())
+    } else {
+        serde_json::from_str(options).map_err(|e| Error::Parse(format!("Invalid options JSON: {}", e)))?
+    };
+    let Value::Object(options) = options else {
+        return Err(Error::Parse("Options must be a JSON object.".into()));
+    };
+
+    let mut arguments = vec!["csv2vviz".to_string(), "convert".to_string()];
+    for (key, value) in options {
+        let flag = format!("--{}", key.replace('_', "-"));
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value]
+        };
+        for value in values {
+            match value {
+                Value::Bool(true) => arguments.push(flag.clone()),
+                Value::Bool(false) | Value::Null => {}
+                Value::String(value) => arguments.push(format!("{}={}", flag, value)),
+                Value::Number(value) => arguments.push(format!("{}={}", flag, value)),
+                _ => return Err(Error::Parse(format!("Unsupported value for option '{}'.", key)))
+            }
+        }
+    }
+    // Everything after `--` is positional, so paths starting with `-` are safe.
+    arguments.push("--".into());
+    arguments.push(path.into());
+    Ok(arguments)
+}
+
+/// Parses an options object into `convert` arguments for the input at `path`.
+pub(crate) fn parse_options(path: &str, options: &str) -> Result<ConvertArgs> {
+    let cli = Cli::try_parse_from(arguments(path, options)?)
+        .map_err(|e| {
+            let message = e.to_string();
+            let first = message.lines().next().unwrap_or_default();
+            Error::Parse(format!("Invalid options: {}", first.trim_start_matches("error: ")))
+        })?;
+    match cli.command {
+        Some(Command::Convert(args)) => Ok(*args),
+        _ => unreachable!("arguments always start with the convert subcommand")
+    }
+}
+
+/// The process-wide settings a conversion changes (`--progress-json`, `--jobs`), put back
+/// when it is dropped, so one call's options don't carry over into the host or the next call.
+struct Settings {
+    progress: bool,
+    workers: usize
+}
+
+impl Settings {
+
+    fn save() -> Self {
+        Settings { progress: progress::enabled(), workers: pipeline::workers_set() }
+    }
+}
+
+impl Drop for Settings {
+    fn drop(&mut self) {
+        if !self.progress {
+            progress::disable();
+        }
+        pipeline::restore_workers(self.workers);
+    }
+}
+
+fn convert(path: &str, options: &str) -> Result<Vec<PathBuf>> {
+    let _settings = Settings::save();
+    crate::convert(parse_options(path, options)?)
+}
+
+unsafe fn string_arg<'a>(s: *const c_char, name: &str) -> Result<&'a str> {
+    if s.is_null() {
+        return Err(Error::Parse(format!("{} is null.", name)));
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| Error::Parse(format!("{} is not valid UTF-8.", name)))
+}
+
+/// Converts the archive at `path` with the options in the `options_json` object (may be
+/// null or empty) and returns a JSON result string.
+///
+/// # Safety
+///
+/// `path` and `options_json` must be null or point to NUL-terminated strings. The returned
+/// string is owned by the caller and must be released with [`csv2vviz_free_string`].
+#[no_mangle]
+pub unsafe extern "C" fn csv2vviz_convert(path: *const c_char, options_json: *const c_char) -> *mut c_char {
+    let result = panic::catch_unwind(|| {
+        let path = string_arg(path, "path")?;
+        let options = if options_json.is_null() { "" } else { string_arg(options_json, "options_json")? };
+        convert(path, options)
+    });
+
+    let response = match result {
+        Ok(Ok(outputs)) => json!({ "ok": true, "outputs": outputs }),
+        Ok(Err(e)) => json!({ "ok": false, "error": e.to_string(), "exitCode": e.exit_code() }),
+        Err(panic) => {
+            let message = panic.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown error".into());
+            json!({ "ok": false, "error": format!("Internal error: {}", message), "exitCode": 101 })
+        }
+    };
+    // serde_json escapes control characters, so the text never contains a NUL.
+    CString::new(response.to_string()).unwrap().into_raw()
+}
+
+/// Frees a string returned by [`csv2vviz_convert`].
+///
+/// # Safety
+///
+/// `s` must be null or a pointer returned by this library that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn csv2vviz_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{pipeline, progress};
+
+    #[test]
+    fn settings_are_put_back_after_a_call() {
+        let dir = std::env::temp_dir().join(format!("csv2vviz-ffi-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("Drone 1.csv");
+        fs::write(&input, "Time [msec],x [m],y [m],z [m],Red,Green,Blue\n0,0,0,0,255,0,0\n1000,1,0,0,255,0,0\n").unwrap();
+        let options = format!(r#"{{"jobs": 3, "progress_json": true, "out_dir": "{}"}}"#, dir.display());
+        let result = super::convert(input.to_str().unwrap(), &options);
+        let (progress, workers) = (progress::enabled(), pipeline::workers_set());
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_ok());
+        assert!(!progress);
+        assert_eq!(workers, 0);
+    }
+}
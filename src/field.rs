@@ -0,0 +1,32 @@
+//! Numeric cells as spreadsheets leave them: padded with spaces, or still wrapped in quotes
+//! the CSV reader kept, because they follow a space or quoting is off (`--no-quoting`).
+
+use std::str::FromStr;
+
+use csv::StringRecord;
+
+use crate::error::{Error, Result};
+
+/// The cell without surrounding whitespace and pairs of quotes: `" 12.5"`, `'255'` and
+/// `"\"3\""` become `12.5`, `255` and `3`.
+pub fn clean(cell: &str) -> &str {
+    let mut cell = cell.trim();
+    while let Some(inner) = ['"', '\''].iter().find_map(|quote| cell.strip_prefix(*quote)?.strip_suffix(*quote)) {
+        cell = inner.trim();
+    }
+    cell
+}
+
+/// Parses a numeric cell once [`clean`]ed.
+pub fn parse<T: FromStr>(cell: &str) -> Result<T, T::Err> {
+    clean(cell).parse()
+}
+
+/// Cell `index` of `record`, which the rest of `file`'s rows call `column`; a row too short
+/// to have it is a parse error naming the file, line and column.
+pub fn cell<'r>(record: &'r StringRecord, index: usize, column: &str, file: &str) -> Result<&'r str> {
+    record.get(index).ok_or_else(|| {
+        let line = record.position().map_or(0, |position| position.line());
+        Error::Parse(format!("{} line {}: no '{}' column", file, line, column))
+    })
+}
@@ -0,0 +1,112 @@
+//! `--fit-box`: the show stretched or squeezed along each axis to fill a venue's volume.
+
+use std::{fmt, str::FromStr};
+
+use crate::{progress, Drone};
+
+/// Size of the target volume in meters: east, north and up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FitBox([f32; 3]);
+
+impl FromStr for FitBox {
+    type Err = String;
+
+    /// `"120 80 100"` or `120,80,100`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<f32>().ok().filter(|value| value.is_finite() && *value > 0.0)
+                .ok_or_else(|| format!("invalid size '{}'", token)))
+            .collect::<Result<Vec<f32>, _>>()?;
+        let [east, north, up] = values[..] else {
+            return Err(format!("expected three sizes (east, north, up), found {} numbers", values.len()));
+        };
+        Ok(FitBox([east, north, up]))
+    }
+}
+
+impl fmt::Display for FitBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [east, north, up] = self.0;
+        write!(f, "{} x {} x {} m", east, north, up)
+    }
+}
+
+/// Smallest and largest scale any one axis may be given, `MIN:MAX`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaleLimits {
+    pub min: f32,
+    pub max: f32
+}
+
+impl FromStr for ScaleLimits {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected MIN:MAX scales such as 0.5:2, got '{}'", s);
+        let (min, max) = s.split_once(':').ok_or_else(invalid)?;
+        let parse = |value: &str| value.trim().parse::<f32>().ok().filter(|value| value.is_finite() && *value > 0.0);
+        match (parse(min), parse(max)) {
+            (Some(min), Some(max)) if min <= max => Ok(ScaleLimits { min, max }),
+            _ => Err(invalid())
+        }
+    }
+}
+
+impl Default for ScaleLimits {
+    fn default() -> Self {
+        ScaleLimits { min: 0.5, max: 2.0 }
+    }
+}
+
+const AXES: [&str; 3] = ["east", "north", "up"];
+
+/// Scales each axis so that the show fills `target`, within `limits`: horizontally about the
+/// middle of the show, vertically about the ground. Warns when the axes are scaled more than
+/// `distortion` times differently, and when the limits leave the show larger than the box.
+pub fn apply(drones: &mut [Drone], target: FitBox, limits: ScaleLimits, distortion: f32) {
+    let (mut min, mut max) = ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]);
+    for sample in drones.iter().flat_map(|(_, samples)| samples) {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(sample.pos[axis]);
+            max[axis] = max[axis].max(sample.pos[axis]);
+        }
+    }
+    let origin = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0, 0.0];
+    let extent = [max[0] - min[0], max[1] - min[1], max[2].max(0.0)];
+
+    // Axes along which the show doesn't extend keep their scale.
+    let scale: [f32; 3] = std::array::from_fn(|axis| match extent[axis] {
+        extent if extent > 0.0 => (target.0[axis] / extent).clamp(limits.min, limits.max),
+        _ => 1.0
+    });
+    for (_, samples) in drones.iter_mut() {
+        for sample in samples.iter_mut() {
+            for axis in 0..3 {
+                sample.pos[axis] = origin[axis] + (sample.pos[axis] - origin[axis]) * scale[axis];
+            }
+        }
+    }
+
+    println!(
+        "Fitted the {:.1} x {:.1} x {:.1} m show to {}: scaled east x{:.3}, north x{:.3}, up x{:.3}",
+        extent[0], extent[1], extent[2], target, scale[0], scale[1], scale[2]
+    );
+    for axis in 0..3 {
+        let fitted = extent[axis] * scale[axis];
+        if fitted > target.0[axis] * (1.0 + 1e-4) {
+            progress::warning(format!(
+                "the fitted show is {:.2} m over the box along {}, where --fit-limits keeps the scale at {} or more",
+                fitted - target.0[axis], AXES[axis], limits.min
+            ));
+        }
+    }
+    let (least, most) = (scale.iter().copied().fold(f32::INFINITY, f32::min), scale.iter().copied().fold(0.0, f32::max));
+    if most / least > distortion {
+        let (stretched, squeezed) = (scale.iter().position(|s| *s == most).unwrap(), scale.iter().position(|s| *s == least).unwrap());
+        progress::warning(format!(
+            "fitting distorts the show: {} is scaled {:.2} times as much as {} (more than {})",
+            AXES[stretched], most / least, AXES[squeezed], distortion
+        ));
+    }
+}
@@ -0,0 +1,182 @@
+use std::{io::{Cursor, Write}, path::PathBuf};
+
+use clap::Args;
+use csv::StringRecord;
+use regex::Regex;
+
+use crate::{archive, columns::{self, Columns, Units}, dialect, encoding, error::{self, Error, Result}, timing, Sample, DRONE_NAME_PATTERN};
+
+#[derive(Args, Debug)]
+pub struct FixArgs {
+
+    /// Archive, directory of drone CSVs or single drone CSV to repair
+    input: PathBuf,
+
+    /// Repaired archive (default `<input>.fixed.zip`)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Fill gaps longer than this (`1.5`, `2s`) with interpolated samples; by default, gaps of
+    /// more than twice a drone's typical sample spacing
+    #[arg(long, value_parser = crate::timing::parse_duration)]
+    max_gap: Option<f32>,
+
+    #[command(flatten)]
+    dialect: dialect::CsvDialect,
+
+    #[command(flatten)]
+    columns: columns::ColumnChoice
+}
+
+/// Rows of a drone CSV laid out as `columns`, in `units`, with bad rows dropped, repeated or
+/// backwards timestamps removed, gaps filled and below-ground samples raised to z = 0. Rows
+/// that aren't changed keep their text, and filled ones copy the row before the gap.
+fn repair(
+    rows: columns::Rows, name: &str, columns: &Columns, units: Option<&Units>, max_gap: Option<f32>, changes: &mut Vec<String>
+) -> Vec<StringRecord> {
+    let to_input = |sample| units.map_or(sample, |units| units.revert(sample));
+    let mut kept: Vec<(StringRecord, Sample)> = vec![];
+    for row in rows {
+        let record = match row {
+            Ok(record) => record,
+            Err(e) => {
+                changes.push(format!("dropped an unreadable row: {}", e));
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |position| position.line());
+        let sample = match columns.colored_sample(&record, name) {
+            Ok(sample) => units.map_or(sample, |units| units.apply(sample)),
+            Err(e) => {
+                // The entry is named in the heading already.
+                changes.push(format!("dropped {}", e.to_string().trim_start_matches(name).trim_start()));
+                continue;
+            }
+        };
+        if let Some((_, last)) = kept.last() {
+            if sample.t == last.t {
+                changes.push(format!("dropped line {}: repeats time {} ms", line, sample.t));
+                continue;
+            }
+            if sample.t < last.t {
+                changes.push(format!("dropped line {}: time {} ms goes back from {} ms", line, sample.t, last.t));
+                continue;
+            }
+        }
+        kept.push((record.iter().map(str::trim).collect(), sample));
+    }
+
+    // Gaps are filled at the drone's median spacing.
+    let mut spacings: Vec<f64> = kept.windows(2).map(|w| w[1].1.t - w[0].1.t).collect();
+    spacings.sort_by(f64::total_cmp);
+    if let Some(&spacing) = spacings.get(spacings.len() / 2) {
+        let limit = max_gap.map_or(2.0 * spacing, |seconds| seconds as f64 * 1000.0);
+        let mut filled = vec![];
+        for (i, row) in kept.iter().enumerate() {
+            filled.push(row.clone());
+            let Some((_, next)) = kept.get(i + 1) else {
+                continue;
+            };
+            let (start, end) = (row.1.t, next.t);
+            if end - start > limit {
+                let steps = ((end - start) / spacing).round().max(2.0) as usize;
+                // Written rows are interpolated in the file's own units, so they round as its rows do.
+                let (from, to) = (to_input(row.1), to_input(*next));
+                for step in 1..steps {
+                    let fraction = step as f64 / steps as f64;
+                    let sample = timing::interpolate(&row.1, next, start + (end - start) * fraction);
+                    let written = timing::interpolate(&from, &to, from.t + (to.t - from.t) * fraction);
+                    filled.push((columns.fill(&row.0, &written, &[0, 1, 2, 3, 4, 5, 6]), sample));
+                }
+                changes.push(format!(
+                    "filled a {:.3} s gap after {:.3} s with {} samples", (end - start) / 1000.0, start / 1000.0, steps - 1
+                ));
+            }
+        }
+        kept = filled;
+    }
+
+    let below: Vec<f32> = kept.iter().map(|(_, sample)| sample.pos[2]).filter(|z| *z < 0.0).collect();
+    if !below.is_empty() {
+        for (record, sample) in kept.iter_mut().filter(|(_, sample)| sample.pos[2] < 0.0) {
+            sample.pos[2] = 0.0;
+            *record = columns.fill(record, &to_input(*sample), &[3]);
+        }
+        changes.push(format!(
+            "raised {} samples below the ground (lowest {:.3} m) to z = 0", below.len(), below.iter().copied().fold(0.0, f32::min)
+        ));
+    }
+    kept.into_iter().map(|(record, _)| record).collect()
+}
+
+/// Writes a repaired copy of the input and lists every change made. Entries other than drone
+/// CSVs are copied unchanged.
+pub fn run(args: FixArgs) -> Result<()> {
+    let output = args.output.clone().unwrap_or_else(|| archive::output_path(&args.input).with_extension("fixed.zip"));
+    if output == args.input {
+        return Err(Error::Io(format!("Refusing to overwrite the input {}", args.input.display())));
+    }
+    let name_re = Regex::new(DRONE_NAME_PATTERN).unwrap();
+    let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let (mut drones, mut fixed, mut repairs) = (0, 0, 0);
+
+    archive::for_each_entry(&args.input, |name, file| {
+        let basename = archive::basename(name);
+        writer.start_file(name, options).map_err(error::zip("Failed to write repaired archive"))?;
+        if !name_re.is_match(basename) || !basename.to_ascii_lowercase().ends_with(".csv") {
+            let mut bytes = vec![];
+            file.read_to_end(&mut bytes).map_err(error::io(&format!("Failed to read {}", name)))?;
+            return writer.write_all(&bytes).map_err(error::io("Failed to write repaired archive"));
+        }
+
+        drones += 1;
+        let mut reader = args.dialect.reader(encoding::Decoder::new(file));
+        let headers = reader.headers().ok().cloned();
+        let mut changes = vec![];
+        let skipped = reader.get_ref().skipped();
+        if skipped > 0 {
+            changes.push(format!("dropped {} preamble lines", skipped));
+        }
+        // Columns and units are found as `convert` finds them, and filled rows keep the layout.
+        let mut rows: columns::Rows = Box::new(reader.records());
+        let columns = match args.columns.choose(headers.as_ref(), &mut rows) {
+            columns::Chosen::Known(columns) | columns::Chosen::Inferred(columns) => columns,
+            columns::Chosen::Ambiguous(guess) => return Err(Error::Parse(format!(
+                "{}: the column layout is ambiguous (best guess {}); pass --columns, or --yes to use the guess",
+                name, guess
+            ))),
+            columns::Chosen::Unknown => Columns::default()
+        };
+        let units = headers.as_ref().map(|headers| Units::from_headers(headers, &columns).0);
+        let records = repair(rows, name, &columns, units.as_ref(), args.max_gap, &mut changes);
+
+        let mut csv = csv::Writer::from_writer(vec![]);
+        if let Some(headers) = &headers {
+            csv.write_record(headers).map_err(error::csv("Failed to write repaired CSV"))?;
+        }
+        for record in &records {
+            csv.write_record(record).map_err(error::csv("Failed to write repaired CSV"))?;
+        }
+        let bytes = csv.into_inner().map_err(|e| Error::Io(format!("Failed to write repaired CSV: {}", e)))?;
+        writer.write_all(&bytes).map_err(error::io("Failed to write repaired archive"))?;
+
+        if !changes.is_empty() {
+            println!("{}:", name);
+            for change in &changes {
+                println!("  {}", change);
+            }
+            fixed += 1;
+            repairs += changes.len();
+        }
+        Ok(())
+    })?;
+
+    let bytes = writer.finish().map_err(error::zip("Failed to write repaired archive"))?.into_inner();
+    std::fs::write(&output, bytes).map_err(error::io("Failed to write repaired archive"))?;
+    match fixed {
+        0 => println!("No repairs needed; wrote {} drones to {}", drones, output.display()),
+        _ => println!("Made {} repairs to {} of {} drones; wrote {}", repairs, fixed, drones, output.display())
+    }
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! Consumer flight log exports (Airdata, Litchi, DJI CsvView): GPS positions in columns found
+//! by header name, converted to meters in a local east/north/up frame.
+
+use csv::StringRecord;
+
+use crate::{error::{Error, Result}, field, Sample};
+
+const FEET: f64 = 0.3048;
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Time columns and their scale to milliseconds.
+const TIME_COLUMNS: &[(&str, f64)] = &[
+    ("time(millisecond)", 1.0),
+    ("clock:offsettime", 1000.0),
+    ("osd.flytime [s]", 1000.0),
+    ("osd.flytime", 1000.0),
+    ("offsettime", 1000.0)
+];
+const LATITUDE_COLUMNS: &[&str] = &["latitude", "osd.latitude", "lat"];
+const LONGITUDE_COLUMNS: &[&str] = &["longitude", "osd.longitude", "lon", "lng"];
+/// Height above the takeoff point, its scale to meters, and the tool that writes it.
+const ALTITUDE_COLUMNS: &[(&str, f64, &str)] = &[
+    ("height_above_takeoff(meters)", 1.0, "Airdata"),
+    ("height_above_takeoff(feet)", FEET, "Airdata"),
+    ("altitude(m)", 1.0, "Litchi"),
+    ("altitude(feet)", FEET, "Litchi"),
+    ("osd.height [m]", 1.0, "DJI"),
+    ("osd.height [ft]", FEET, "DJI")
+];
+
+/// East and north offsets (m) of a WGS84 position from `origin`, which is set to the
+/// position itself if it is the first one seen. Flat-earth, so only good for a few km.
+pub fn local(latitude: f64, longitude: f64, origin: &mut Option<(f64, f64)>) -> (f64, f64) {
+    let (latitude0, longitude0) = *origin.get_or_insert((latitude, longitude));
+    let north = (latitude - latitude0).to_radians() * EARTH_RADIUS;
+    let east = (longitude - longitude0).to_radians() * EARTH_RADIUS * latitude0.to_radians().cos();
+    (east, north)
+}
+
+/// Inverse of [`local`]: the WGS84 position `east`/`north` meters from `origin`.
+pub fn global(east: f64, north: f64, (latitude0, longitude0): (f64, f64)) -> (f64, f64) {
+    let latitude = latitude0 + (north / EARTH_RADIUS).to_degrees();
+    let longitude = longitude0 + (east / (EARTH_RADIUS * latitude0.to_radians().cos())).to_degrees();
+    (latitude, longitude)
+}
+
+/// Where a flight log keeps its time, position and height columns.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub kind: &'static str,
+    time: (usize, f64),
+    latitude: usize,
+    longitude: usize,
+    altitude: (usize, f64)
+}
+
+impl Layout {
+
+    /// Recognizes a flight log by its header row. Skybrush-style headers give `None`.
+    pub fn detect(headers: &StringRecord) -> Option<Layout> {
+        let names: Vec<String> = headers.iter().map(|name| name.trim().to_ascii_lowercase()).collect();
+        let find = |candidate: &str| names.iter().position(|name| name == candidate);
+        let time = TIME_COLUMNS.iter().find_map(|(name, scale)| Some((find(name)?, *scale)))?;
+        let latitude = LATITUDE_COLUMNS.iter().find_map(|name| find(name))?;
+        let longitude = LONGITUDE_COLUMNS.iter().find_map(|name| find(name))?;
+        let (altitude, kind) = ALTITUDE_COLUMNS.iter().find_map(|(name, scale, kind)| Some(((find(name)?, *scale), *kind)))?;
+        Some(Layout { kind, time, latitude, longitude, altitude })
+    }
+
+    /// The row as a white sample in east/north/up meters relative to `origin`, which is set
+    /// from the first fix seen. Rows without a GPS fix give `None`.
+    pub fn sample(&self, record: &StringRecord, origin: &mut Option<(f64, f64)>, name: &str) -> Result<Option<Sample>> {
+        let line = record.position().map_or(0, |position| position.line());
+        let field = |i: usize| field::clean(record.get(i).unwrap_or_default());
+        let number = |i: usize| field(i).parse::<f64>()
+            .map_err(|_| Error::Parse(format!("{} line {}: invalid number '{}'", name, line, field(i))));
+
+        if field(self.latitude).is_empty() || field(self.longitude).is_empty() {
+            return Ok(None);
+        }
+        let (latitude, longitude) = (number(self.latitude)?, number(self.longitude)?);
+        if latitude == 0.0 && longitude == 0.0 {
+            return Ok(None);
+        }
+        let (east, north) = local(latitude, longitude, origin);
+        let up = number(self.altitude.0)? * self.altitude.1;
+        let time = number(self.time.0)? * self.time.1;
+
+        Ok(Some(Sample { t: time, pos: [east as f32, north as f32, up as f32], rgb: [255; 3] }))
+    }
+}
@@ -0,0 +1,72 @@
+use clap::ValueEnum;
+
+use crate::{timing::interpolate, AgentTraversal, AgentTraversals, Sample};
+
+/// How traversal durations are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum TraversalMode {
+    /// Explicit `dt` in seconds on every traversal, at the input's own sample times
+    #[default]
+    Dt,
+    /// Integer `frames` at the show's position rate, resampled onto that rate
+    Frames
+}
+
+impl TraversalMode {
+
+    /// The `version` the show declares, so readers can tell the two encodings apart.
+    pub fn version(&self) -> &'static str {
+        match self {
+            TraversalMode::Dt => "1.0",
+            TraversalMode::Frames => "1.1"
+        }
+    }
+}
+
+/// Samples every `1 / rate` seconds from the first sample, interpolating positions linearly
+/// and holding colors. The last frame is rounded up, holding the final position.
+pub fn resample(samples: &[Sample], rate: f32) -> Vec<Sample> {
+    let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+        return vec![];
+    };
+    let (start, step) = (first.t, 1000.0 / rate as f64);
+    let frames = ((last.t - start) / step - 1e-6).ceil().max(0.0) as usize;
+    let mut resampled = Vec::with_capacity(frames + 1);
+    let mut index = 0;
+    for frame in 0..=frames {
+        let at = start + frame as f64 * step;
+        while index + 1 < samples.len() && samples[index + 1].t <= at {
+            index += 1;
+        }
+        resampled.push(match samples.get(index + 1) {
+            Some(next) => interpolate(&samples[index], next, at),
+            None => last.with_time(at)
+        });
+    }
+    resampled
+}
+
+/// Replaces each traversal's `dt` with a frame count at `rate` and merges runs of identical
+/// straight frames (holds and constant-velocity legs) into single traversals.
+pub fn to_frames(traversals: AgentTraversals, rate: f32) -> AgentTraversals {
+    let mut merged: Vec<AgentTraversal> = vec![];
+    for mut traversal in traversals.0 {
+        let frames = (traversal.duration(rate) * rate).round().max(1.0) as u32;
+        traversal.dt = None;
+        traversal.frames = Some(frames);
+        if let Some(previous) = merged.last_mut() {
+            let previous_frames = previous.frames.unwrap_or(1) as f32;
+            let same = |a: f32, b: f32| (a / previous_frames - b / frames as f32).abs() < 1e-5;
+            if previous.control_points.is_none() && traversal.control_points.is_none()
+                && same(previous.dx, traversal.dx) && same(previous.dy, traversal.dy) && same(previous.dz, traversal.dz) {
+                previous.dx += traversal.dx;
+                previous.dy += traversal.dy;
+                previous.dz += traversal.dz;
+                previous.frames = Some(previous.frames.unwrap_or(1) + frames);
+                continue;
+            }
+        }
+        merged.push(traversal);
+    }
+    AgentTraversals(merged)
+}
@@ -0,0 +1,261 @@
+use std::{f32::consts::PI, fmt, io::{Cursor, Write}, path::PathBuf, str::FromStr};
+
+use clap::Args;
+
+use crate::error::{self, Error, Result};
+
+pub mod font;
+
+#[derive(Args, Debug)]
+pub struct GenerateArgs {
+
+    /// Number of drones [default: 100, or as many as a `text:` pattern needs]
+    #[arg(long)]
+    drones: Option<usize>,
+
+    /// Formation: `grid`, `circle`, `sphere` or `text:"HELLO"`
+    #[arg(long, default_value = "grid")]
+    pattern: Pattern,
+
+    /// Letter height of `text:` patterns (m)
+    #[arg(long, default_value_t = 20.0)]
+    height: f32,
+
+    /// Show length (`60`, `2m`, `1m30s`)
+    #[arg(long, default_value = "60", value_parser = crate::timing::parse_duration)]
+    duration: f32,
+
+    /// Output file: a Skybrush-style CSV zip, or a converted show when it ends in `.vviz`
+    #[arg(short, long, default_value = "generated.zip")]
+    output: PathBuf
+}
+
+/// Formations flown by generated shows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// A vertical wall facing the audience
+    Grid,
+    /// A vertical ring
+    Circle,
+    /// Drones spread evenly over a sphere
+    Sphere,
+    /// Vertical text in a 5×7 pixel font, revealed left to right
+    Text(String)
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(text) = s.strip_prefix("text:") {
+            let text = text.trim_matches('"').to_ascii_uppercase();
+            if text.trim().is_empty() {
+                return Err("text pattern has no text".into());
+            }
+            if let Some(c) = text.chars().find(|&c| font::glyph(c).is_none()) {
+                return Err(format!("text pattern cannot draw '{}'", c));
+            }
+            return Ok(Pattern::Text(text));
+        }
+        match s.to_ascii_lowercase().as_str() {
+            "grid" => Ok(Pattern::Grid),
+            "circle" => Ok(Pattern::Circle),
+            "sphere" => Ok(Pattern::Sphere),
+            _ => Err(format!("unknown pattern '{}' (expected grid, circle, sphere or text:\"...\")", s))
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Grid => f.write_str("grid"),
+            Pattern::Circle => f.write_str("circle"),
+            Pattern::Sphere => f.write_str("sphere"),
+            Pattern::Text(text) => write!(f, "\"{}\"", text)
+        }
+    }
+}
+
+/// Distance between neighbouring pads and formation positions (m).
+const SPACING: f32 = 2.0;
+/// Lowest formation altitude (m).
+const ALTITUDE: f32 = 10.0;
+/// Sample interval (ms).
+const STEP: u32 = 250;
+
+type Vec3 = [f32; 3];
+
+/// Pads on a square grid on the ground, in CSV axes (z up).
+fn pads(count: usize) -> Vec<Vec3> {
+    let columns = (count as f32).sqrt().ceil().max(1.0) as usize;
+    (0..count).map(|i| [(i % columns) as f32 * SPACING, (i / columns) as f32 * SPACING, 0.0]).collect()
+}
+
+impl Pattern {
+
+    /// Drones needed to draw the pattern, if it has a fixed size.
+    fn required(&self) -> Option<usize> {
+        match self {
+            Pattern::Text(text) => Some(text.chars()
+                .filter_map(font::glyph)
+                .map(|rows| rows.iter().map(|row| row.count_ones() as usize).sum::<usize>())
+                .sum()),
+            _ => None
+        }
+    }
+
+    /// Formation positions centred on the origin, before being lifted over the pads.
+    /// `height` sets the letter size of text patterns.
+    fn positions(&self, count: usize, height: f32) -> Vec<Vec3> {
+        match self {
+            Pattern::Grid => {
+                let columns = (count as f32).sqrt().ceil().max(1.0) as usize;
+                let width = (columns - 1) as f32 * SPACING;
+                (0..count).map(|i| [(i % columns) as f32 * SPACING - width / 2.0, 0.0, (i / columns) as f32 * SPACING]).collect()
+            }
+            Pattern::Circle => {
+                let radius = (count as f32 * SPACING / (2.0 * PI)).max(3.0);
+                (0..count).map(|i| {
+                    let angle = 2.0 * PI * i as f32 / count as f32;
+                    [radius * angle.cos(), 0.0, radius * (1.0 + angle.sin())]
+                }).collect()
+            }
+            Pattern::Sphere => {
+                let radius = (SPACING * (count as f32 / (4.0 * PI)).sqrt()).max(3.0);
+                // Fibonacci lattice.
+                let golden = PI * (3.0 - 5f32.sqrt());
+                (0..count).map(|i| {
+                    let z = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+                    let ring = (1.0 - z * z).sqrt();
+                    let angle = golden * i as f32;
+                    [radius * ring * angle.cos(), radius * ring * angle.sin(), radius * (1.0 + z)]
+                }).collect()
+            }
+            Pattern::Text(text) => {
+                let pitch = height / (font::HEIGHT - 1) as f32;
+                let columns = text.chars().count() * (font::WIDTH + 1) - 1;
+                let width = (columns - 1) as f32 * pitch;
+                // Column by column, so drone numbers, hues and the reveal run left to right.
+                let mut positions = vec![];
+                for (index, rows) in text.chars().filter_map(font::glyph).enumerate() {
+                    for column in 0..font::WIDTH {
+                        for (row, bits) in rows.iter().enumerate() {
+                            if bits & (0x10 >> column) != 0 {
+                                let x = (index * (font::WIDTH + 1) + column) as f32 * pitch - width / 2.0;
+                                positions.push([x, 0.0, (font::HEIGHT - 1 - row) as f32 * pitch]);
+                            }
+                        }
+                    }
+                }
+                positions
+            }
+        }
+    }
+}
+
+fn lerp(a: Vec3, b: Vec3, f: f32) -> Vec3 {
+    [a[0] + (b[0] - a[0]) * f, a[1] + (b[1] - a[1]) * f, a[2] + (b[2] - a[2]) * f]
+}
+
+/// Eases in and out so every move starts and ends at rest.
+fn smoothstep(f: f32) -> f32 {
+    let f = f.clamp(0.0, 1.0);
+    f * f * (3.0 - 2.0 * f)
+}
+
+/// Fully saturated color for a hue in 0..1.
+fn hue(h: f32) -> [u8; 3] {
+    let channel = |offset: f32| {
+        let k = (h * 6.0 + offset) % 6.0;
+        (255.0 * (1.0 - (k.min(4.0 - k)).clamp(0.0, 1.0))).round() as u8
+    };
+    [channel(5.0), channel(3.0), channel(1.0)]
+}
+
+/// Take off, fly to the formation, hold, return over the pad and land, in five equal
+/// phases. Paths are straight lines and are not checked for collisions.
+///
+/// With a `reveal` fraction the drone flies in dark and lights up that far through the
+/// first half of the hold. Drones without a `target` are spares and stay dark on their pad.
+fn trajectory(pad: Vec3, target: Option<Vec3>, color: [u8; 3], reveal: Option<f32>, duration: f32) -> String {
+    const WHITE: [u8; 3] = [255, 255, 255];
+    const DARK: [u8; 3] = [0, 0, 0];
+    let above_pad = [pad[0], pad[1], ALTITUDE / 2.0];
+    let mut csv = String::from("Time [msec],x [m],y [m],z [m],Red,Green,Blue\n");
+    let steps = (duration * 1000.0 / STEP as f32).round().max(1.0) as u32;
+    for step in 0..=steps {
+        let f = step as f32 / steps as f32 * 5.0;
+        let phase = (f.floor() as u32).min(4);
+        let local = smoothstep(f - phase as f32);
+        let (position, rgb) = match (target, phase) {
+            (None, _) => (pad, DARK),
+            (Some(_), 0) => (lerp(pad, above_pad, local), WHITE),
+            (Some(target), 1) => (lerp(above_pad, target, local), if reveal.is_some() { DARK } else { color }),
+            (Some(target), 2) => match reveal {
+                Some(reveal) if f - 2.0 < reveal * 0.5 => (target, DARK),
+                _ => (target, color)
+            },
+            (Some(target), 3) => (lerp(target, above_pad, local), color),
+            (Some(_), _) => (lerp(above_pad, pad, local), WHITE)
+        };
+        csv.push_str(&format!(
+            "{},{:.3},{:.3},{:.3},{},{},{}\n",
+            step * STEP, position[0], position[1], position[2], rgb[0], rgb[1], rgb[2]
+        ));
+    }
+    csv
+}
+
+/// Zip of `Drone N.csv` entries for the show.
+fn archive(args: &GenerateArgs, count: usize) -> Result<Vec<u8>> {
+    let pads = pads(count);
+    let center = [
+        pads.iter().map(|p| p[0]).fold(0.0, f32::max) / 2.0,
+        pads.iter().map(|p| p[1]).fold(0.0, f32::max) / 2.0
+    ];
+    let formation = args.pattern.positions(count, args.height);
+    let span = formation.iter().map(|p| p[0]).fold(f32::NEG_INFINITY, f32::max)
+        - formation.iter().map(|p| p[0]).fold(f32::INFINITY, f32::min);
+
+    let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (i, pad) in pads.iter().enumerate() {
+        let position = formation.get(i);
+        let target = position.map(|p| [center[0] + p[0], center[1] + p[1], ALTITUDE + p[2]]);
+        let reveal = match (&args.pattern, position) {
+            (Pattern::Text(_), Some(p)) if span > 0.0 => Some((p[0] - formation[0][0]) / span),
+            (Pattern::Text(_), Some(_)) => Some(0.0),
+            _ => None
+        };
+        let csv = trajectory(*pad, target, hue(i as f32 / formation.len() as f32), reveal, args.duration);
+        writer.start_file(format!("Drone {}.csv", i + 1), options)
+            .map_err(error::zip("Failed to build show archive"))?;
+        writer.write_all(csv.as_bytes()).map_err(error::io("Failed to build show archive"))?;
+    }
+    let bytes = writer.finish().map_err(error::zip("Failed to build show archive"))?.into_inner();
+    Ok(bytes)
+}
+
+/// Writes a synthetic show of simple formations for exercising viewers and validation.
+pub fn generate(args: GenerateArgs) -> Result<()> {
+    let required = args.pattern.required();
+    let count = args.drones.or(required).unwrap_or(100);
+    if let Some(required) = required {
+        if count < required {
+            return Err(Error::Validation(format!("Pattern {} needs {} drones, but only {} were requested", args.pattern, required, count)));
+        }
+        println!("Pattern {} needs {} drones", args.pattern, required);
+        let pitch = args.height / (font::HEIGHT - 1) as f32;
+        if pitch < 1.0 {
+            println!("Warning: --height {} puts text pixels only {:.2} m apart", args.height, pitch);
+        }
+    }
+    let bytes = archive(&args, count)?;
+    let is_vviz = args.output.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("vviz"));
+    let contents = if is_vviz { crate::convert_bytes(&bytes, "")?.into_bytes() } else { bytes };
+    std::fs::write(&args.output, contents).map_err(error::io("Failed to write generated show"))?;
+    println!("Generated {} drones flying a {} for {} s in {}", count, args.pattern, args.duration, args.output.display());
+    Ok(())
+}
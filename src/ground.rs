@@ -0,0 +1,58 @@
+use clap::ValueEnum;
+
+use crate::{progress, Drone, Sample};
+
+/// Repair for samples below the ground (z < 0).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroundRepair {
+    /// Raise only the offending samples to the ground
+    Clamp,
+    /// Raise the whole show so its lowest sample is on the ground
+    Lift
+}
+
+/// Depth below the ground (m) that still counts as on it, so rotation noise isn't flagged.
+const TOLERANCE: f32 = 1e-3;
+
+/// Reports every drone with samples below the ground and applies `repair`, if any, printing
+/// a summary of what changed.
+pub fn check(drones: &mut [Drone], repair: Option<GroundRepair>) {
+    let mut affected = vec![];
+    let mut lowest = 0.0f32;
+    for (drone_id, samples) in drones.iter() {
+        let below: Vec<&Sample> = samples.iter().filter(|sample| sample.pos[2] < -TOLERANCE).collect();
+        let Some(worst) = below.iter().min_by(|a, b| a.pos[2].total_cmp(&b.pos[2])) else {
+            continue;
+        };
+        progress::warning(format!(
+            "drone {} has {} samples below the ground (lowest {:.2} m at {:.3} s)",
+            drone_id, below.len(), worst.pos[2], worst.t / 1000.0
+        ));
+        affected.push((*drone_id, below.len()));
+        lowest = lowest.min(worst.pos[2]);
+    }
+    if affected.is_empty() {
+        return;
+    }
+
+    match repair {
+        Some(GroundRepair::Clamp) => {
+            for (_, samples) in drones.iter_mut() {
+                for sample in samples.iter_mut().filter(|sample| sample.pos[2] < 0.0) {
+                    sample.pos[2] = 0.0;
+                }
+            }
+            let samples: usize = affected.iter().map(|(_, count)| count).sum();
+            println!("Clamped {} samples on {} drones to the ground", samples, affected.len());
+        }
+        Some(GroundRepair::Lift) => {
+            for (_, samples) in drones.iter_mut() {
+                for sample in samples.iter_mut() {
+                    sample.pos[2] -= lowest;
+                }
+            }
+            println!("Lifted the show by {:.2} m", -lowest);
+        }
+        None => progress::warning(format!("{} drones go below the ground; use --clamp-ground to repair", affected.len()))
+    }
+}
@@ -0,0 +1,22 @@
+//! `--align-heading`: the show turned about the vertical so that its front faces a compass
+//! bearing.
+
+use euclid::{Angle, Rotation3D};
+
+use crate::Rotation;
+
+/// A compass bearing in degrees (`135`, `135deg`, `135°`), normalized to 0–360.
+pub fn parse_bearing(s: &str) -> Result<f32, String> {
+    let s = s.trim();
+    let number = s.strip_suffix("deg").or_else(|| s.strip_suffix('°')).unwrap_or(s).trim();
+    number.parse::<f32>().ok()
+        .filter(|bearing| bearing.is_finite())
+        .map(|bearing| bearing.rem_euclid(360.0))
+        .ok_or_else(|| format!("invalid bearing '{}'", s))
+}
+
+/// Turns the show clockwise, seen from above, so that the direction that was at bearing
+/// `front` ends up at `bearing`. Works on points in vviz axes (east, up, north).
+pub fn rotation(front: f32, bearing: f32) -> Rotation {
+    Rotation3D::around_y(Angle::degrees(bearing - front))
+}
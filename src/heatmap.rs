@@ -0,0 +1,122 @@
+//! `--heatmap`: a top-down map of where the drones spend their time, each cell of the ground
+//! shaded by the flight time summed over every drone above it, for safety briefings.
+
+use std::path::Path;
+
+use crate::{columns, error::{Error, Result}, export::Rgb, plot::Figure, track::{distance, Track}, Show};
+
+/// Largest map, in cells along either side.
+const MAX_CELLS: usize = 2000;
+
+/// Pixels along the longer side of the map, when cells can be drawn at least a pixel wide.
+const MAP_SIZE: f32 = 800.0;
+
+const MARGIN: f32 = 20.0;
+const TOP: f32 = 44.0;
+
+/// Parses the cell size (`1m`, `50cm`, `2`), in meters.
+pub fn parse_resolution(s: &str) -> Result<f32, String> {
+    let resolution = columns::parse_length(s)?;
+    if resolution <= 0.0 || !resolution.is_finite() {
+        return Err(format!("the heatmap resolution must be positive, found '{}'", s));
+    }
+    Ok(resolution)
+}
+
+/// White through yellow and red to dark red as `level` goes from 0 to 1.
+fn shade(level: f32) -> Rgb {
+    const STOPS: [(f32, f32, f32); 4] = [(255.0, 255.0, 255.0), (255.0, 220.0, 60.0), (220.0, 40.0, 20.0), (90.0, 0.0, 10.0)];
+    let position = level.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let index = (position.floor() as usize).min(STOPS.len() - 2);
+    let (a, b, f) = (STOPS[index], STOPS[index + 1], position - index as f32);
+    let mix = |a: f32, b: f32| (a + (b - a) * f).round() as u8;
+    (mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+}
+
+/// Draws the time the drones spend above each `resolution`-meter cell of the ground, north
+/// up, as PNG or SVG after the extension of `fname`.
+pub fn write(show: &Show, resolution: f32, fname: &Path) -> Result<()> {
+    let tracks: Vec<Track> = show.performances.iter().map(|p| Track::new(p, show.default_position_rate)).collect();
+    // East and north of every sample; the track's second axis is up.
+    let ground = |position: [f32; 3]| [position[0], position[2]];
+    let (mut min, mut max) = ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]);
+    for (_, position) in tracks.iter().flat_map(|track| &track.samples) {
+        let [east, north] = ground(*position);
+        min = [min[0].min(east), min[1].min(north)];
+        max = [max[0].max(east), max[1].max(north)];
+    }
+    if min[0] > max[0] {
+        return Err(Error::Validation(format!("Cannot draw {}: the show has no drones", fname.display())));
+    }
+    // One empty cell around the show.
+    let origin = [(min[0] / resolution).floor() - 1.0, (min[1] / resolution).floor() - 1.0];
+    let cells = [
+        ((max[0] / resolution).floor() - origin[0] + 2.0) as usize,
+        ((max[1] / resolution).floor() - origin[1] + 2.0) as usize
+    ];
+    if cells[0].max(cells[1]) > MAX_CELLS {
+        return Err(Error::Validation(format!(
+            "The heatmap would be {} x {} cells; pick a coarser --resolution than {} m",
+            cells[0], cells[1], resolution
+        )));
+    }
+    let cell = |[east, north]: [f32; 2]| {
+        let column = ((east / resolution).floor() - origin[0]) as usize;
+        let row = ((north / resolution).floor() - origin[1]) as usize;
+        row.min(cells[1] - 1) * cells[0] + column.min(cells[0] - 1)
+    };
+
+    // Each move is cut into steps of at most half a cell, sharing its time between them.
+    let mut seconds = vec![0.0f32; cells[0] * cells[1]];
+    for track in &tracks {
+        for w in track.samples.windows(2) {
+            let ((t0, a), (t1, b)) = (w[0], w[1]);
+            let (a, b) = (ground(a), ground(b));
+            let steps = (distance([a[0], a[1], 0.0], [b[0], b[1], 0.0]) / (resolution / 2.0)).ceil().max(1.0);
+            for step in 0..steps as usize {
+                let u = (step as f32 + 0.5) / steps;
+                seconds[cell([a[0] + (b[0] - a[0]) * u, a[1] + (b[1] - a[1]) * u])] += (t1 - t0) / steps;
+            }
+        }
+    }
+    let (busiest, most) = seconds.iter().copied().enumerate().fold((0, 0.0), |best, (index, s)| if s > best.1 { (index, s) } else { best });
+
+    let scale = (MAP_SIZE / cells[0].max(cells[1]) as f32).floor().clamp(1.0, 40.0);
+    let size = [cells[0] as f32 * scale, cells[1] as f32 * scale];
+    let mut figure = Figure::new((size[0] + 2.0 * MARGIN).max(760.0) as u32, (TOP + size[1] + 50.0) as u32);
+    let pixel = |[east, north]: [f32; 2]| [
+        MARGIN + (east / resolution - origin[0]) * scale,
+        TOP + size[1] - (north / resolution - origin[1]) * scale
+    ];
+    figure.text([MARGIN, 12.0], &format!("Flight time per {} m cell, north up", resolution), (0, 0, 0));
+    for (index, s) in seconds.iter().enumerate().filter(|(_, s)| **s > 0.0) {
+        let (column, row) = (index % cells[0], index / cells[0]);
+        // Square root, so that cells flown through briefly still show next to the holds.
+        let color = shade((s / most).sqrt());
+        figure.rect([MARGIN + column as f32 * scale, TOP + size[1] - (row + 1) as f32 * scale], [scale, scale], color);
+    }
+    let corners = [[MARGIN, TOP], [MARGIN + size[0], TOP], [MARGIN + size[0], TOP + size[1]], [MARGIN, TOP + size[1]]];
+    for side in 0..4 {
+        figure.line(corners[side], corners[(side + 1) % 4], 1.0, (190, 190, 190));
+    }
+    // The show origin, to measure the audience line from.
+    let [x, y] = pixel([0.0, 0.0]);
+    if (MARGIN..=MARGIN + size[0]).contains(&x) && (TOP..=TOP + size[1]).contains(&y) {
+        figure.line([x, TOP], [x, TOP + size[1]], 1.0, (80, 120, 200));
+        figure.line([MARGIN, y], [MARGIN + size[0], y], 1.0, (80, 120, 200));
+    }
+    figure.text(
+        [MARGIN, TOP + size[1] + 16.0],
+        &format!("{:.0} x {:.0} m, darkest {:.1} s, blue lines cross at the origin", cells[0] as f32 * resolution, cells[1] as f32 * resolution, most),
+        (80, 80, 80)
+    );
+    figure.save(fname)?;
+
+    let (column, row) = (busiest % cells[0], busiest / cells[0]);
+    println!(
+        "Wrote a {} m heatmap of {} drones to {}; busiest cell {:.1} s at {:.1} m east, {:.1} m north",
+        resolution, tracks.len(), fname.display(), most,
+        (origin[0] + column as f32 + 0.5) * resolution, (origin[1] + row as f32 + 0.5) * resolution
+    );
+    Ok(())
+}
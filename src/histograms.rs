@@ -0,0 +1,147 @@
+//! `--histograms`: how the fleet's flight time is spread over speed, acceleration and altitude,
+//! with each drone's peak, to pick a hardware profile and spot the drones that stand out.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::{error::{self, Result}, track::{distance, Track}, Show};
+
+/// Bins per histogram, give or take the rounding of the bin width.
+const BINS: f32 = 20.0;
+
+#[derive(Serialize)]
+struct Bin {
+    from: f32,
+    to: f32,
+    /// Flight time of all drones in the bin.
+    seconds: f32,
+    /// Drones whose peak falls in the bin.
+    drones: usize
+}
+
+#[derive(Serialize)]
+struct Peak {
+    drone: usize,
+    value: f32
+}
+
+#[derive(Serialize)]
+struct Histogram {
+    unit: &'static str,
+    bin_width: f32,
+    bins: Vec<Bin>,
+    max: Option<Peak>,
+    /// Drones peaking well above the rest of the fleet.
+    outliers: Vec<usize>
+}
+
+#[derive(Serialize)]
+struct Report {
+    drones: usize,
+    speed: Histogram,
+    acceleration: Histogram,
+    altitude: Histogram
+}
+
+/// Time-weighted values of one quantity, by drone number.
+#[derive(Default)]
+struct Samples(Vec<(usize, Vec<(f32, f32)>)>);
+
+impl Samples {
+
+    fn histogram(&self, unit: &'static str) -> Histogram {
+        let peaks: Vec<(usize, f32)> = self.0.iter()
+            .filter_map(|(drone, values)| values.iter().map(|(_, value)| *value).reduce(f32::max).map(|peak| (*drone, peak)))
+            .collect();
+        let highest = peaks.iter().map(|(_, peak)| *peak).fold(0.0, f32::max);
+        let lowest = self.0.iter().flat_map(|(_, values)| values).map(|(_, value)| *value).fold(0.0, f32::min);
+        let bin_width = nice_step((highest - lowest) / BINS);
+        let first = (lowest / bin_width).floor() as i64;
+        let count = ((highest / bin_width).floor() as i64 - first + 1).max(1) as usize;
+
+        let index = |value: f32| (((value / bin_width).floor() as i64 - first).max(0) as usize).min(count - 1);
+        let mut bins: Vec<Bin> = (0..count).map(|i| {
+            let from = (first + i as i64) as f32 * bin_width;
+            Bin { from, to: from + bin_width, seconds: 0.0, drones: 0 }
+        }).collect();
+        for (dt, value) in self.0.iter().flat_map(|(_, values)| values) {
+            bins[index(*value)].seconds += dt;
+        }
+        for (_, peak) in &peaks {
+            bins[index(*peak)].drones += 1;
+        }
+
+        let max = peaks.iter().copied().reduce(|a, b| if b.1 > a.1 { b } else { a }).map(|(drone, value)| Peak { drone, value });
+        Histogram { unit, bin_width, bins, max, outliers: outliers(&peaks) }
+    }
+}
+
+/// 1, 2 or 5 times a power of ten, at least `step`.
+fn nice_step(step: f32) -> f32 {
+    if !step.is_finite() || step <= 0.0 {
+        return 1.0;
+    }
+    let magnitude = 10f32.powf(step.log10().floor());
+    [1.0, 2.0, 5.0, 10.0].into_iter().map(|factor| factor * magnitude).find(|nice| *nice >= step * 0.999).unwrap()
+}
+
+/// Drones peaking more than one and a half interquartile ranges above the upper quartile.
+fn outliers(peaks: &[(usize, f32)]) -> Vec<usize> {
+    if peaks.len() < 4 {
+        return vec![];
+    }
+    let mut sorted: Vec<f32> = peaks.iter().map(|(_, peak)| *peak).collect();
+    sorted.sort_by(f32::total_cmp);
+    let quartile = |q: f32| sorted[((sorted.len() - 1) as f32 * q).round() as usize];
+    let (q1, q3) = (quartile(0.25), quartile(0.75));
+    let fence = q3 + 1.5 * (q3 - q1);
+    let mut outliers: Vec<usize> = peaks.iter().filter(|(_, peak)| *peak > fence).map(|(drone, _)| *drone).collect();
+    outliers.sort();
+    outliers
+}
+
+/// Writes the speed, acceleration and altitude histograms of the show as JSON.
+pub fn write_report(show: &Show, fname: &Path) -> Result<()> {
+    let (mut speed, mut acceleration, mut altitude) = (Samples::default(), Samples::default(), Samples::default());
+    for performance in &show.performances {
+        let drone = performance.id + 1;
+        let track = Track::new(performance, show.default_position_rate);
+        let segments: Vec<(f32, [f32; 3], f32)> = track.samples.windows(2)
+            .filter(|w| w[1].0 > w[0].0)
+            .map(|w| {
+                let ((t0, a), (t1, b)) = (w[0], w[1]);
+                let dt = t1 - t0;
+                (dt, [(b[0] - a[0]) / dt, (b[1] - a[1]) / dt, (b[2] - a[2]) / dt], (a[1] + b[1]) / 2.0)
+            })
+            .collect();
+        speed.0.push((drone, segments.iter().map(|(dt, v, _)| (*dt, distance([0.0; 3], *v))).collect()));
+        altitude.0.push((drone, segments.iter().map(|(dt, _, up)| (*dt, *up)).collect()));
+        acceleration.0.push((drone, segments.windows(2).map(|w| {
+            let ((dt0, a, _), (dt1, b, _)) = (w[0], w[1]);
+            let dt = (dt0 + dt1) / 2.0;
+            (dt, distance(a, b) / dt)
+        }).collect()));
+    }
+
+    let report = Report {
+        drones: show.performances.len(),
+        speed: speed.histogram("m/s"),
+        acceleration: acceleration.histogram("m/s²"),
+        altitude: altitude.histogram("m")
+    };
+    let json = serde_json::to_string(&report).expect("Failed to serialize histograms.");
+    std::fs::write(fname, json).map_err(error::io("Failed to write histograms"))?;
+
+    println!("Wrote speed, acceleration and altitude histograms of {} drones to {}", report.drones, fname.display());
+    for (name, histogram) in [("speed", &report.speed), ("acceleration", &report.acceleration), ("altitude", &report.altitude)] {
+        if let Some(max) = &histogram.max {
+            let outliers = match histogram.outliers.len() {
+                0 => String::new(),
+                _ => format!("; outliers: drones {}", histogram.outliers.iter().map(usize::to_string).collect::<Vec<_>>().join(", "))
+            };
+            println!("  Highest {} {:.2} {} (drone {}){}", name, max.value, histogram.unit, max.drone, outliers);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! Input formats. A format recognizes its inputs and reads the agents (drones) out of them;
+//! everything after that (transforms, checks, timing) is shared. The drone CSV archives and
+//! flight logs read by default are one such format, and others can be registered by crates
+//! embedding the converter.
+
+use std::sync::{Arc, Mutex};
+
+use crate::{error::{ErrorReport, Result}, ConvertOptions, Sample};
+
+pub use crate::archive::Input;
+
+mod csv_archive;
+
+/// One agent's trajectory as a format read it.
+pub struct AgentData {
+    /// Input entry the agent was read from, for messages.
+    pub name: String,
+    /// Drone number, as in the `Drone N` entry names.
+    pub id: usize,
+    /// Samples in input order.
+    pub samples: Vec<Sample>,
+    /// Positions are east/north/up meters already, as in flight logs, rather than in the
+    /// `--preset`'s CSV axes.
+    pub east_north_up: bool,
+    /// Parse errors in the agent's data. They are reported together once the whole input
+    /// is read.
+    pub errors: ErrorReport,
+    /// Unparsable rows left out with `--lenient`.
+    pub skipped_rows: usize
+}
+
+/// A kind of input the converter reads.
+pub trait InputFormat: Send + Sync {
+
+    /// Whether `input` is in this format, by its path or its contents.
+    fn detect(&self, input: Input) -> bool;
+
+    /// Folders of `input` holding separate shows. Formats without acts have none.
+    fn acts(&self, _input: Input) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    /// Every agent in `input` (only those of folder `act`, when given). An error item ends
+    /// reading; errors in an agent's data belong in its `errors`.
+    fn read_agents<'a>(&'a self, input: Input<'a>, act: Option<&'a str>) -> Result<Box<dyn Iterator<Item = Result<AgentData>> + 'a>>;
+}
+
+static REGISTERED: Mutex<Vec<Arc<dyn InputFormat>>> = Mutex::new(vec![]);
+
+/// Makes `format` available to every conversion that follows. Registered formats are tried
+/// in turn before the built-in one.
+pub fn register(format: impl InputFormat + 'static) {
+    REGISTERED.lock().unwrap().push(Arc::new(format));
+}
+
+/// The format to read `input` with: the first registered one recognizing it, or else the
+/// drone CSV archive reader.
+pub(crate) fn detect<'a>(input: Input, options: &'a ConvertOptions) -> Arc<dyn InputFormat + 'a> {
+    let registered = REGISTERED.lock().unwrap().iter().find(|format| format.detect(input)).cloned();
+    registered.unwrap_or_else(|| Arc::new(csv_archive::CsvArchive { options }))
+}
+
+/// Whether any format reads `input`.
+pub(crate) fn recognized(input: Input, options: &ConvertOptions) -> bool {
+    detect(input, options).detect(input)
+}
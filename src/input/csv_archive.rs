@@ -0,0 +1,499 @@
+use std::{cell::RefCell, collections::{HashMap, HashSet}, io::Read, path::PathBuf, sync::{mpsc, Mutex}, thread};
+
+use regex::Regex;
+
+use super::{AgentData, Input, InputFormat};
+use crate::{acts, archive, columns, dialect::{self, Preamble}, encoding, error::{self, Error, ErrorReport, Result}, flightlog, pipeline, progress, ulog, ConvertOptions, Sample, DRONE_NAME_PATTERN};
+
+/// Skybrush-style drone CSVs and flight logs, in a zip or tar archive, a directory, or on
+/// their own.
+pub struct CsvArchive<'a> {
+    pub options: &'a ConvertOptions
+}
+
+/// Parsed entries held ahead of the agents handed out.
+const ENTRIES_AHEAD: usize = 4;
+
+/// What reading the entries takes from the options, held by the thread reading them.
+#[derive(Clone)]
+struct ReadOptions {
+    dialect: dialect::CsvDialect,
+    columns: columns::ColumnChoice,
+    /// Colors have to be readable, as a script or the Blender export uses them.
+    colored: bool,
+    lenient: bool,
+    max_errors: usize,
+    ulog_rate: f32
+}
+
+impl ReadOptions {
+
+    fn new(options: &ConvertOptions) -> Self {
+        ReadOptions {
+            dialect: options.dialect.clone(),
+            columns: options.columns.clone(),
+            colored: options.script.is_some() || options.export_blender.is_some(),
+            lenient: options.lenient,
+            max_errors: options.max_errors,
+            ulog_rate: options.ulog_rate
+        }
+    }
+}
+
+/// An input held by the thread reading it. Archives in memory are copied, as the agents are
+/// handed out after [`CsvArchive::read_agents`] returns.
+enum Source {
+    Path(PathBuf),
+    Bytes(Vec<u8>)
+}
+
+impl Source {
+
+    fn new(input: Input) -> Self {
+        match input {
+            Input::Path(fname) => Source::Path(fname.to_path_buf()),
+            Input::Bytes(bytes) => Source::Bytes(bytes.to_vec())
+        }
+    }
+
+    fn input(&self) -> Input<'_> {
+        match self {
+            Source::Path(fname) => Input::Path(fname),
+            Source::Bytes(bytes) => Input::Bytes(bytes)
+        }
+    }
+}
+
+/// What a pipeline worker makes of one entry, before anything that depends on the entries
+/// ahead of it.
+enum Entry {
+    /// A CSV not named `Drone N`.
+    Unnamed,
+    /// A flight log met while entries are parsed in parallel, held to be read in input order.
+    Log(Vec<u8>),
+    Trajectory(Box<Trajectory>),
+    /// A flight log read in turn that could not be read, or an entry with a drone number of 0.
+    Failed(Error)
+}
+
+/// What flight logs share, in input order: they are placed relative to the first GPS fix,
+/// and those not named `Drone N` are numbered in turn.
+#[derive(Default)]
+struct Logs {
+    origin: Option<(f64, f64)>,
+    unnamed: usize
+}
+
+impl Logs {
+
+    fn drone_id(&mut self, name: &str, name_re: &Regex) -> Result<usize> {
+        match name_re.captures(archive::basename(name)).and_then(|captures| captures[1].parse::<usize>().ok()) {
+            Some(drone_id) => numbered(name, drone_id),
+            None => {
+                self.unnamed += 1;
+                Ok(self.unnamed)
+            }
+        }
+    }
+}
+
+/// Drone numbers count from 1, as the vviz ids below them count from 0.
+fn numbered(name: &str, drone_id: usize) -> Result<usize> {
+    match drone_id {
+        0 => Err(Error::Validation(format!("{}: drone numbers start at 1", name))),
+        drone_id => Ok(drone_id)
+    }
+}
+
+/// Copies the bytes read through it while `copy` holds a buffer, so a flight log recognized
+/// by its header can still be held whole.
+struct Recorder<'a> {
+    inner: &'a mut dyn Read,
+    copy: &'a RefCell<Option<Vec<u8>>>
+}
+
+impl Read for Recorder<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        if let Some(copy) = self.copy.borrow_mut().as_mut() {
+            copy.extend_from_slice(&buf[..len]);
+        }
+        Ok(len)
+    }
+}
+
+/// One drone's samples, with what was found out on the way for the messages printed in
+/// input order.
+struct Trajectory {
+    drone_id: usize,
+    /// The flight log format, for logs.
+    kind: Option<&'static str>,
+    /// Preamble lines skipped before the header.
+    skipped: usize,
+    /// The column layout when it was inferred from the data.
+    inferred: Option<columns::Columns>,
+    /// Header units converted, and header units not recognized.
+    units: Option<columns::Units>,
+    unknown_units: Vec<String>,
+    samples: Vec<Sample>,
+    errors: ErrorReport,
+    /// With `--lenient`, unparsable rows are left out and counted instead of reported.
+    lenient: bool,
+    skipped_rows: usize,
+    /// An error that ends reading once the entry's messages are out.
+    fatal: Option<Error>
+}
+
+impl Trajectory {
+
+    fn new(drone_id: usize, options: &ReadOptions) -> Self {
+        Trajectory {
+            drone_id, kind: None, skipped: 0, inferred: None, units: None, unknown_units: vec![], samples: vec![],
+            errors: ErrorReport::new(options.max_errors), lenient: options.lenient, skipped_rows: 0, fatal: None
+        }
+    }
+
+    /// Keeps a sample, or records why a row has none. Returns false once reading can't
+    /// carry on.
+    fn push(&mut self, sample: Result<Option<Sample>>) -> bool {
+        match sample {
+            Ok(Some(sample)) => self.samples.push(sample),
+            Ok(None) => {}
+            Err(Error::Parse(_)) if self.lenient => self.skipped_rows += 1,
+            Err(e) => if let Err(e) = self.errors.push(e) {
+                self.fatal = Some(e);
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Parses a trajectory CSV as it streams in; runs on the pipeline workers. Flight logs are
+/// read too when `logs` is given, as entries then come one after the other, and held for the
+/// consumer otherwise.
+fn read_entry(
+    name: &str, file: &mut dyn Read, name_re: &Regex, logs: Option<&Mutex<Logs>>, options: &ReadOptions
+) -> Entry {
+    let basename = archive::basename(name);
+    if ulog::is_ulog(basename) {
+        if let Some(logs) = logs {
+            let mut logs = logs.lock().unwrap();
+            let drone_id = match logs.drone_id(name, name_re) {
+                Ok(drone_id) => drone_id,
+                Err(e) => return Entry::Failed(e)
+            };
+            return match read_ulog(name, file, drone_id, &mut logs.origin, options) {
+                Ok(trajectory) => Entry::Trajectory(Box::new(trajectory)),
+                Err(e) => Entry::Failed(e)
+            };
+        }
+        let mut data = vec![];
+        return match file.read_to_end(&mut data) {
+            Ok(_) => Entry::Log(data),
+            Err(e) => Entry::Failed(error::io(&format!("Failed to read {}", name))(e))
+        };
+    }
+    let held = RefCell::new(logs.is_none().then(Vec::new));
+    let mut reader = options.dialect.reader(encoding::Decoder::new(Recorder { inner: &mut *file, copy: &held }));
+    let headers = reader.headers().ok().cloned();
+    if let Some(layout) = headers.as_ref().and_then(flightlog::Layout::detect) {
+        if let Some(logs) = logs {
+            let mut logs = logs.lock().unwrap();
+            let drone_id = match logs.drone_id(name, name_re) {
+                Ok(drone_id) => drone_id,
+                Err(e) => return Entry::Failed(e)
+            };
+            return Entry::Trajectory(Box::new(read_log_rows(name, &mut reader, &layout, drone_id, &mut logs.origin, options)));
+        }
+        drop(reader);
+        let mut data = held.take().unwrap_or_default();
+        return match file.read_to_end(&mut data) {
+            Ok(_) => Entry::Log(data),
+            Err(e) => Entry::Failed(error::io(&format!("Failed to read {}", name))(e))
+        };
+    }
+    held.take();
+    let Some(drone_id) = name_re.captures(basename).and_then(|captures| captures[1].parse::<usize>().ok()) else {
+        return Entry::Unnamed;
+    };
+    let drone_id = match numbered(name, drone_id) {
+        Ok(drone_id) => drone_id,
+        Err(e) => return Entry::Failed(e)
+    };
+    let mut trajectory = Box::new(Trajectory::new(drone_id, options));
+    trajectory.skipped = reader.get_ref().skipped();
+    let mut rows: columns::Rows = Box::new(reader.records());
+
+    // Trajectory CSVs take their column order from --columns, the header row or, failing
+    // both, the first rows of data.
+    let columns = match options.columns.choose(headers.as_ref(), &mut rows) {
+        columns::Chosen::Known(columns) => Some(columns),
+        columns::Chosen::Inferred(columns) => {
+            trajectory.inferred = Some(columns.clone());
+            Some(columns)
+        }
+        columns::Chosen::Ambiguous(guess) => {
+            trajectory.fatal = Some(Error::Parse(format!(
+                "{}: the column layout is ambiguous (best guess {}); pass --columns, or --yes to use the guess",
+                name, guess
+            )));
+            return Entry::Trajectory(trajectory);
+        }
+        columns::Chosen::Unknown => None
+    };
+    // Header units (`x [ft]`, `t (s)`) are converted to the milliseconds and meters the
+    // rest of the pipeline works in.
+    if let Some(headers) = &headers {
+        let (units, unknown) = columns::Units::from_headers(headers, columns.as_ref().unwrap_or(&columns::Columns::default()));
+        trajectory.units = Some(units).filter(|units| !units.is_identity());
+        trajectory.unknown_units = unknown;
+    }
+
+    // Rows are parsed once, straight into samples.
+    let columns = columns.unwrap_or_default();
+    for record in rows {
+        let sample = record.map_err(error::csv(&format!("Failed to read {}", name)))
+            .and_then(|record| if options.colored {
+                columns.colored_sample(&record, name)
+            } else {
+                columns.sample(&record, name)
+            })
+            .map(|sample| Some(trajectory.units.as_ref().map_or(sample, |units| units.apply(sample))));
+        if !trajectory.push(sample) {
+            break;
+        }
+    }
+    Entry::Trajectory(trajectory)
+}
+
+/// Reads a PX4 ULog, in input order; `origin` is the first GPS fix of the input.
+fn read_ulog(
+    name: &str, file: &mut dyn Read, drone_id: usize, origin: &mut Option<(f64, f64)>, options: &ReadOptions
+) -> Result<Trajectory> {
+    let mut trajectory = Trajectory::new(drone_id, options);
+    trajectory.kind = Some("PX4 ULog");
+    trajectory.samples = ulog::read(file, options.ulog_rate, name, origin)?;
+    Ok(trajectory)
+}
+
+/// Reads the rows of a CSV flight log past its header, in input order.
+fn read_log_rows<R: Read>(
+    name: &str, reader: &mut csv::Reader<Preamble<R>>, layout: &flightlog::Layout, drone_id: usize,
+    origin: &mut Option<(f64, f64)>, options: &ReadOptions
+) -> Trajectory {
+    let mut trajectory = Trajectory::new(drone_id, options);
+    trajectory.kind = Some(layout.kind);
+    trajectory.skipped = reader.get_ref().skipped();
+    for record in reader.records() {
+        let sample = record.map_err(error::csv(&format!("Failed to read {}", name)))
+            .and_then(|record| layout.sample(&record, origin, name));
+        if !trajectory.push(sample) {
+            break;
+        }
+    }
+    trajectory
+}
+
+/// Reads a flight log held by [`read_entry`].
+fn read_log(name: &str, data: &[u8], drone_id: usize, origin: &mut Option<(f64, f64)>, options: &ReadOptions) -> Result<Trajectory> {
+    if ulog::is_ulog(archive::basename(name)) {
+        return read_ulog(name, &mut &data[..], drone_id, origin, options);
+    }
+    let mut reader = options.dialect.reader(encoding::Decoder::new(data));
+    let headers = reader.headers().ok().cloned();
+    let Some(layout) = headers.as_ref().and_then(flightlog::Layout::detect) else {
+        return Err(Error::Format(format!("{}: not a flight log", name)));
+    };
+    Ok(read_log_rows(name, &mut reader, &layout, drone_id, origin, options))
+}
+
+/// Parses the entries of `source` (only those of folder `act`, when given) and sends them
+/// on in input order, followed by the error that ended reading, if any.
+fn read_entries(source: &Source, act: Option<&str>, options: &ReadOptions, send: impl Fn(Result<(String, Entry)>) -> Result<()>) {
+    let input = source.input();
+    let name_re = Regex::new(DRONE_NAME_PATTERN).unwrap();
+    let logs = Mutex::new(Logs::default());
+    let in_turn = pipeline::is_sequential(input).then_some(&logs);
+
+    // Entries of other acts are passed over unread; the rest are parsed in parallel, and
+    // everything below sees them in input order.
+    let in_act = |name: &str| act.is_none_or(|folder| archive::parent(name) == folder);
+    let read = pipeline::map_entries(
+        input, in_act,
+        |name, file| read_entry(name, file, &name_re, in_turn, options),
+        |name, entry| send(Ok((name.to_string(), entry)))
+    );
+    if let Err(e) = read {
+        let _ = send(Err(e));
+    }
+}
+
+/// The agents of an input, handed out as their entries come through from the thread
+/// reading them. Messages about each entry are printed as its agent is handed out.
+struct Agents {
+    /// Gone once reading has ended, which stops the thread at its next entry.
+    entries: Option<mpsc::Receiver<Result<(String, Entry)>>>,
+    options: ReadOptions,
+    name_re: Regex,
+    /// Flight logs met while entries are parsed in parallel.
+    logs: Logs,
+    entry_names: HashMap<String, String>,
+    inferred: Option<columns::Columns>,
+    scaled: Option<columns::Units>,
+    preamble: Option<usize>,
+    unknown_units: HashSet<String>
+}
+
+impl Agents {
+
+    /// The agent of entry `name`, if it is one.
+    fn agent(&mut self, name: &str, entry: Entry) -> Result<Option<AgentData>> {
+        let basename = archive::basename(name);
+        let trajectory = match entry {
+            Entry::Unnamed => {
+                println!("Skipping {}: not named 'Drone <number>'", name);
+                return Ok(None);
+            }
+            Entry::Log(data) => {
+                let drone_id = self.logs.drone_id(name, &self.name_re)?;
+                read_log(name, &data, drone_id, &mut self.logs.origin, &self.options)?
+            }
+            Entry::Trajectory(trajectory) => *trajectory,
+            Entry::Failed(e) => return Err(e)
+        };
+        if let Some(kind) = trajectory.kind {
+            println!("Reading {} as drone {} ({} flight log)", name, trajectory.drone_id, kind);
+        }
+        if trajectory.skipped > 0 && self.preamble != Some(trajectory.skipped) {
+            println!("Skipping {} preamble lines of {}", trajectory.skipped, name);
+            self.preamble = Some(trajectory.skipped);
+        }
+
+        if let Some(other) = self.entry_names.insert(basename.to_string(), name.to_string()) {
+            return Err(Error::Format(format!("Duplicate entry name '{}': found both '{}' and '{}'.", basename, other, name)));
+        }
+
+        if let Some(columns) = trajectory.inferred.as_ref().filter(|columns| self.inferred.as_ref() != Some(columns)) {
+            println!("Inferred columns {} from {}", columns::CsvLayout::describe(columns), name);
+            self.inferred = Some(columns.clone());
+        }
+        for header in trajectory.unknown_units.into_iter().filter(|header| self.unknown_units.insert(header.clone())) {
+            progress::warning(format!("unknown unit in column '{}' of {}; reading it unscaled", header, name));
+        }
+        if let Some(units) = trajectory.units.as_ref().filter(|units| self.scaled.as_ref() != Some(units)) {
+            println!("Converting {} of {} to milliseconds and meters", units, name);
+            self.scaled = Some(units.clone());
+        }
+        if let Some(e) = trajectory.fatal {
+            return Err(e);
+        }
+
+        Ok(Some(AgentData {
+            name: name.to_string(),
+            id: trajectory.drone_id,
+            samples: trajectory.samples,
+            east_north_up: trajectory.kind.is_some(),
+            errors: trajectory.errors,
+            skipped_rows: trajectory.skipped_rows
+        }))
+    }
+}
+
+impl Iterator for Agents {
+    type Item = Result<AgentData>;
+
+    fn next(&mut self) -> Option<Result<AgentData>> {
+        loop {
+            let item = self.entries.as_ref()?.recv().ok();
+            let agent = match item {
+                Some(Ok((name, entry))) => self.agent(&name, entry),
+                Some(Err(e)) => Err(e),
+                None => {
+                    self.entries = None;
+                    return None;
+                }
+            };
+            match agent {
+                Ok(Some(agent)) => return Some(Ok(agent)),
+                Ok(None) => {}
+                Err(e) => {
+                    self.entries = None;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl InputFormat for CsvArchive<'_> {
+
+    fn detect(&self, input: Input) -> bool {
+        match input {
+            Input::Path(fname) => archive::Format::detect(fname).is_some(),
+            Input::Bytes(bytes) => archive::Format::sniff(&bytes[..bytes.len().min(262)]).is_some()
+        }
+    }
+
+    fn acts(&self, input: Input) -> Result<Vec<String>> {
+        acts::detect(input)
+    }
+
+    /// Entries are read on a thread of their own, a few ahead of the agents handed out. There
+    /// are no threads on wasm32, so there the whole input is read up front.
+    fn read_agents<'a>(&'a self, input: Input<'a>, act: Option<&'a str>) -> Result<Box<dyn Iterator<Item = Result<AgentData>> + 'a>> {
+        let options = ReadOptions::new(self.options);
+        let entries = if cfg!(target_arch = "wasm32") {
+            let (sender, entries) = mpsc::channel();
+            read_entries(&Source::new(input), act, &options, |item| sender.send(item).map_err(|_| Error::Io(String::new())));
+            entries
+        } else {
+            let (sender, entries) = mpsc::sync_channel(ENTRIES_AHEAD);
+            let (source, act, options) = (Source::new(input), act.map(str::to_string), options.clone());
+            // Once the agents are dropped, the failed send ends reading.
+            thread::spawn(move || {
+                read_entries(&source, act.as_deref(), &options, |item| sender.send(item).map_err(|_| Error::Io(String::new())));
+            });
+            entries
+        };
+        Ok(Box::new(Agents {
+            entries: Some(entries),
+            options,
+            name_re: Regex::new(DRONE_NAME_PATTERN).unwrap(),
+            logs: Logs::default(),
+            entry_names: HashMap::new(),
+            inferred: None,
+            scaled: None,
+            preamble: None,
+            unknown_units: HashSet::new()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use crate::{convert_bytes, error::Error};
+
+    #[test]
+    fn drone_zero_is_rejected() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        writer.start_file("Drone 0.csv", zip::write::FileOptions::default()).unwrap();
+        writer.write_all(b"Time [msec],x [m],y [m],z [m],Red,Green,Blue\n0,0,0,0,255,0,0\n1000,1,0,0,255,0,0\n").unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+        match convert_bytes(&archive, "") {
+            Err(Error::Validation(message)) => assert_eq!(message, "Drone 0.csv: drone numbers start at 1"),
+            other => panic!("expected a validation error, got {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn a_log_without_its_header_is_a_format_error() {
+        let options = super::ReadOptions::new(&crate::ConvertOptions::default());
+        match super::read_log("log.csv", b"t,x,y,z\n0,0,0,0\n", 1, &mut None, &options) {
+            Err(Error::Format(message)) => assert_eq!(message, "log.csv: not a flight log"),
+            other => panic!("expected a format error, got {:?}", other.map(|_| ()))
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! `csv2vviz inspect`: a summary of an existing show.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use clap::Args;
+
+use crate::{error::Result, read_show, PayloadAction, Show};
+
+#[derive(Args, Debug)]
+pub struct InspectArgs {
+
+    /// Show to summarize
+    show: PathBuf,
+
+    /// Leave out the per-drone lines
+    #[arg(long)]
+    summary: bool
+}
+
+/// Per payload type: the payloads of that type, their color actions and their events.
+type PayloadCounts = BTreeMap<String, (usize, usize, usize)>;
+
+pub fn run(args: InspectArgs) -> Result<()> {
+    let show = read_show(&args.show)?;
+    print(&show, !args.summary);
+    Ok(())
+}
+
+fn print(show: &Show, per_drone: bool) {
+    println!("vviz {}, {} drones", show.version, show.performances.len());
+    println!("Position rate {} /s, color rate {} /s", show.default_position_rate, show.default_color_rate);
+
+    let mut payloads = PayloadCounts::new();
+    let (mut samples, mut duration, mut drones) = (vec![], 0.0f32, vec![]);
+    for performance in &show.performances {
+        let traversals = &performance.description.traversals.0;
+        let length: f32 = traversals.iter().map(|traversal| traversal.duration(show.default_position_rate)).sum();
+        let curves = traversals.iter().filter(|traversal| traversal.control_points.is_some()).count();
+        let mut colors = 0;
+        for payload in &performance.payload {
+            let color_actions = payload.actions.iter().filter(|action| matches!(action, PayloadAction::Color(_))).count();
+            let counts = payloads.entry(payload.payload_type.clone()).or_default();
+            counts.0 += 1;
+            counts.1 += color_actions;
+            counts.2 += payload.actions.len() - color_actions;
+            colors += color_actions;
+        }
+        let curves = if curves > 0 { format!(" ({} curved)", curves) } else { String::new() };
+        drones.push(format!("  Drone {}: {} positions{}, {} colors, {:.1} s", performance.id + 1, traversals.len() + 1, curves, colors, length));
+        samples.push(traversals.len() + 1);
+        duration = duration.max(length);
+    }
+
+    if let (Some(min), Some(max)) = (samples.iter().min(), samples.iter().max()) {
+        println!("Positions per drone: {} to {}, {} in total", min, max, samples.iter().sum::<usize>());
+    }
+    println!("Duration {:.1} s", duration);
+    for (payload_type, (count, colors, events)) in &payloads {
+        let mut actions = vec![];
+        if *colors > 0 {
+            actions.push(format!("{} color actions", colors));
+        }
+        if *events > 0 {
+            actions.push(format!("{} events", events));
+        }
+        let actions = if actions.is_empty() { String::new() } else { format!(", {}", actions.join(", ")) };
+        println!("Payload '{}': {} drones{}", payload_type, count, actions);
+    }
+
+    if let Some(metadata) = &show.metadata {
+        if let Some(preview) = &metadata.preview {
+            println!("Preview: converted from only 1 in {} samples", preview.decimate);
+        }
+        if let Some(provenance) = &metadata.provenance {
+            println!("Converted by {} from {} ({})", provenance.tool, provenance.input, provenance.converted_at.as_deref().unwrap_or("no timestamp"));
+            for transform in &provenance.transforms {
+                println!("  {}", transform);
+            }
+        }
+        if let Some(timecode) = &metadata.timecode {
+            println!("Timecode {} at {} fps", timecode.start, timecode.fps);
+        }
+        for act in metadata.acts.iter().flatten() {
+            println!("Act {}: {:.2} to {:.2} s, from {}", act.name, act.start, act.end, act.source);
+        }
+        for cue in metadata.cues.iter().flatten() {
+            println!("Cue at {:.2} s: {}", cue.time, cue.label);
+        }
+    }
+
+    if per_drone {
+        for line in drones {
+            println!("{}", line);
+        }
+    }
+}
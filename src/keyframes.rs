@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::{error::{self, Result}, track::distance, Drone, Sample};
+
+/// A kept sample: time in ms on the input clock and position along the input CSV axes.
+pub type Keyframe = (f64, [f32; 3]);
+
+#[derive(Serialize)]
+struct DroneKeyframes {
+    id: usize,
+    /// `[t, x, y, z]` rows.
+    keyframes: Vec<(f64, f32, f32, f32)>
+}
+
+#[derive(Serialize)]
+struct Keyframes {
+    tolerance: f32,
+    drones: Vec<DroneKeyframes>
+}
+
+/// Distance between the sample at index `i` and where linear interpolation between the
+/// samples at `first` and `last` puts the drone at the same time.
+fn deviation(samples: &[Keyframe], first: usize, last: usize, i: usize) -> f32 {
+    let ((ta, a), (tb, b), (t, p)) = (samples[first], samples[last], samples[i]);
+    let f = if tb > ta { ((t - ta) / (tb - ta)) as f32 } else { 0.0 };
+    let expected = [0, 1, 2].map(|axis| a[axis] + (b[axis] - a[axis]) * f);
+    distance(expected, p)
+}
+
+/// Douglas-Peucker over time: a sample is kept when dropping it would move the
+/// interpolated drone by more than `tolerance` at that sample's time, so timing is
+/// preserved along with the path.
+fn simplify(samples: &[Keyframe], tolerance: f32) -> Vec<Keyframe> {
+    if samples.len() < 3 {
+        return samples.to_vec();
+    }
+    let mut keep = vec![false; samples.len()];
+    keep[0] = true;
+    keep[samples.len() - 1] = true;
+    let mut stack = vec![(0, samples.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let worst = (first + 1..last)
+            .map(|i| (i, deviation(samples, first, last, i)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, d)) = worst {
+            if d > tolerance {
+                keep[i] = true;
+                stack.push((first, i));
+                stack.push((i, last));
+            }
+        }
+    }
+    samples.iter().zip(keep).filter(|(_, keep)| *keep).map(|(sample, _)| *sample).collect()
+}
+
+/// The `count` samples (at least the first and last) that best preserve the trajectory:
+/// Douglas-Peucker that splits the worst segment until `count` samples are kept.
+pub fn decimate(samples: &[Keyframe], count: usize) -> Vec<Keyframe> {
+    if samples.len() <= count.max(2) {
+        return samples.to_vec();
+    }
+    let mut kept = vec![0, samples.len() - 1];
+    while kept.len() < count {
+        let worst = kept.windows(2)
+            .flat_map(|w| (w[0] + 1..w[1]).map(move |i| (i, w[0], w[1])))
+            .map(|(i, first, last)| (i, deviation(samples, first, last, i)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        let Some((i, _)) = worst else { break };
+        let at = kept.partition_point(|&k| k < i);
+        kept.insert(at, i);
+    }
+    kept.into_iter().map(|i| samples[i]).collect()
+}
+
+fn to_keyframes(samples: &[Sample]) -> Vec<Keyframe> {
+    samples.iter().map(|sample| (sample.t, sample.pos)).collect()
+}
+
+/// Writes every drone's keyframes to `fname`, as `drone,t,x,y,z` rows when it ends in
+/// `.csv` and as JSON otherwise.
+pub fn write(drones: &[Drone], tolerance: f32, fname: &Path) -> Result<()> {
+    let mut keyframes = Keyframes { tolerance, drones: vec![] };
+    let mut total = 0;
+    for (drone_id, samples) in drones {
+        let kept = simplify(&to_keyframes(samples), tolerance);
+        total += samples.len();
+        keyframes.drones.push(DroneKeyframes {
+            id: *drone_id,
+            keyframes: kept.iter().map(|(t, p)| (*t, p[0], p[1], p[2])).collect()
+        });
+    }
+    keyframes.drones.sort_by_key(|drone| drone.id);
+
+    let is_csv = fname.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    let text = if is_csv {
+        let mut text = String::from("drone,t,x,y,z\n");
+        for drone in &keyframes.drones {
+            for (t, x, y, z) in &drone.keyframes {
+                text.push_str(&format!("{},{},{},{},{}\n", drone.id, t, x, y, z));
+            }
+        }
+        text
+    } else {
+        serde_json::to_string(&keyframes).expect("Failed to serialize keyframes.")
+    };
+    std::fs::write(fname, text).map_err(error::io("Failed to write keyframes"))?;
+
+    let kept: usize = keyframes.drones.iter().map(|drone| drone.keyframes.len()).sum();
+    println!("Kept {} of {} samples as keyframes in {}", kept, total, fname.display());
+    Ok(())
+}
@@ -0,0 +1,122 @@
+//! `--land-stagger`: the end of the operation appended to a show that stops in the air, with
+//! every drone hovering and groups of them then descending one after another.
+
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crate::{timing, Drone, Sample};
+
+/// Drones at or below this altitude (m) at the end of the show have already landed.
+const LANDED: f32 = 0.05;
+
+/// Largest gap (m) between neighbouring drones of one row, column or layer.
+const GROUP_TOLERANCE: f32 = 0.5;
+
+/// How drones are grouped for landing, and the order the groups land in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grouping {
+    /// Drones in line east to west, from the southernmost row
+    Rows,
+    /// Drones in line north to south, from the westernmost column
+    Columns,
+    /// Drones at the same altitude, from the lowest layer
+    Layers,
+    /// One drone at a time, in drone order
+    Drones
+}
+
+/// `GROUPING:DELAY`, e.g. `rows:3s`: the groups start descending `delay` seconds apart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LandStagger {
+    pub grouping: Grouping,
+    pub delay: f32
+}
+
+impl FromStr for LandStagger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (grouping, delay) = s.split_once(':')
+            .ok_or_else(|| format!("expected GROUPING:DELAY (e.g. rows:3s), got '{}'", s))?;
+        let grouping = match grouping.trim() {
+            "rows" => Grouping::Rows,
+            "columns" => Grouping::Columns,
+            "layers" => Grouping::Layers,
+            "drones" => Grouping::Drones,
+            other => return Err(format!("unknown grouping '{}' (rows, columns, layers or drones)", other))
+        };
+        let delay = timing::parse_duration(delay)?;
+        Ok(LandStagger { grouping, delay })
+    }
+}
+
+impl fmt::Display for LandStagger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let grouping = match self.grouping {
+            Grouping::Rows => "rows",
+            Grouping::Columns => "columns",
+            Grouping::Layers => "layers",
+            Grouping::Drones => "drones"
+        };
+        write!(f, "{}, {} s apart", grouping, self.delay)
+    }
+}
+
+/// When each airborne drone starts its descent (ms), and how fast it descends.
+pub struct Plan {
+    descents: HashMap<usize, f64>,
+    speed: f32
+}
+
+impl LandStagger {
+
+    /// Groups the drones still in the air at the end of the show. They all hover where they
+    /// finished until `hover` seconds after the last drone does, then the groups descend in
+    /// turn at `speed` m/s.
+    pub fn plan(&self, drones: &[Drone], hover: f32, speed: f32) -> Plan {
+        let end = drones.iter().map(|(_, samples)| samples[samples.len() - 1].t).fold(f64::NEG_INFINITY, f64::max);
+        let mut airborne: Vec<(usize, [f32; 3])> = drones.iter()
+            .map(|(drone_id, samples)| (*drone_id, samples[samples.len() - 1].pos))
+            .filter(|(_, pos)| pos[2] > LANDED)
+            .collect();
+
+        let key = |pos: [f32; 3]| match self.grouping {
+            Grouping::Rows => pos[1],
+            Grouping::Columns => pos[0],
+            Grouping::Layers | Grouping::Drones => pos[2]
+        };
+        match self.grouping {
+            Grouping::Drones => airborne.sort_by_key(|(drone_id, _)| *drone_id),
+            _ => airborne.sort_by(|a, b| key(a.1).total_cmp(&key(b.1)))
+        }
+
+        let mut descents = HashMap::new();
+        let (mut group, mut previous) = (0, None);
+        for (drone_id, pos) in airborne {
+            let next_group = match self.grouping {
+                Grouping::Drones => previous.is_some(),
+                _ => previous.is_some_and(|previous| key(pos) - previous > GROUP_TOLERANCE)
+            };
+            group += next_group as usize;
+            previous = Some(key(pos));
+            descents.insert(drone_id, end + (hover + group as f32 * self.delay) as f64 * 1000.0);
+        }
+        let groups = if descents.is_empty() { 0 } else { group + 1 };
+        println!("Landing {} drones in {} groups by {}", descents.len(), groups, self);
+        Plan { descents, speed }
+    }
+}
+
+impl Plan {
+
+    /// Appends the drone's hover and descent to the ground below it.
+    pub fn append(&self, drone_id: usize, samples: &mut Vec<Sample>) {
+        let Some(descent) = self.descents.get(&drone_id) else {
+            return;
+        };
+        let last = samples[samples.len() - 1];
+        // Frame-encoded drones may already hold a little past the end of the show.
+        let descent = descent.max(last.t);
+        samples.push(last.with_time(descent));
+        samples.push(Sample { t: descent + (last.pos[2] / self.speed) as f64 * 1000.0, pos: [last.pos[0], last.pos[1], 0.0], ..last });
+    }
+}
@@ -0,0 +1,1691 @@
+use std::{collections::HashSet, path::PathBuf, io::Write, str::FromStr, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Mutex}};
+use serde::{Deserialize, Serialize};
+
+use euclid::{Rotation3D, Point3D, Angle, UnknownUnit, Translation3D};
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+mod acts;
+mod align;
+mod archive;
+mod blender;
+mod columns;
+mod compare;
+mod config;
+mod continuity;
+mod countdown;
+mod crop;
+mod cues;
+mod curve;
+mod datum;
+mod derivatives;
+mod dialect;
+mod encoding;
+mod energy;
+pub mod error;
+mod events;
+mod export;
+mod extract;
+mod ffi;
+mod field;
+mod fit;
+mod fetch;
+mod fix;
+mod flightlog;
+mod frames;
+mod generate;
+mod ground;
+mod heading;
+mod heatmap;
+mod histograms;
+pub mod input;
+mod inspect;
+mod keyframes;
+mod landing;
+mod lights;
+mod lint;
+mod merge_lights;
+mod migrate;
+mod msgpack;
+mod output;
+mod pipeline;
+mod play;
+mod plot;
+mod preset;
+mod preview;
+mod progress;
+mod protobuf;
+mod proximity;
+mod provenance;
+mod quantize;
+mod reassign;
+mod resample;
+mod retime;
+mod script;
+mod segment;
+mod sha256;
+mod spill;
+mod swap;
+mod timecode;
+mod timing;
+mod track;
+mod transform;
+mod ulog;
+mod validate;
+mod verify;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+mod wind;
+
+use config::Config;
+
+use error::{Error, Result};
+
+use events::PayloadEvents;
+
+use lights::{ColorManifest, LightTracks};
+
+use output::OutputFormat;
+
+use preset::Preset;
+
+use timing::{TimeAlign, TimeShifts};
+
+/// Entry names are expected to start with `Drone <number>`, numbered from 1.
+const DRONE_NAME_PATTERN: &str = r"^Drone (\d+)";
+
+type Rotation = Rotation3D<f32, UnknownUnit, UnknownUnit>;
+type Translation = Translation3D<f32, UnknownUnit, UnknownUnit>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AgentTraversal {
+    dx: f32,
+    dy: f32,
+    dz: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dt: Option<f32>,
+    /// Duration in frames at the show's position rate, written instead of `dt` by
+    /// `--traversal-mode frames`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    frames: Option<u32>,
+    /// Inner control points of a cubic Bézier traversal, relative to its start; the
+    /// traversal is straight without them.
+    #[serde(rename = "controlPoints", default, skip_serializing_if = "Option::is_none")]
+    control_points: Option<[[f32; 3]; 2]>
+}
+
+impl AgentTraversal {
+
+    /// Length in seconds: `dt`, else `frames` at `position_rate`, else a single frame.
+    fn duration(&self, position_rate: f32) -> f32 {
+        self.dt.unwrap_or_else(|| self.frames.unwrap_or(1) as f32 / position_rate)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AgentTraversals(Vec<AgentTraversal>);
+
+impl From<Vec<Sample>> for AgentTraversals {
+    fn from(samples: Vec<Sample>) -> Self {
+        AgentTraversals(samples.windows(2).map(|w| {
+            let (prev, cur) = (w[0], w[1]);
+            AgentTraversal {
+                dt: Some(((cur.t - prev.t) / 1000.0) as f32),
+                dx: cur.pos[0] - prev.pos[0],
+                dy: cur.pos[2] - prev.pos[2],
+                dz: cur.pos[1] - prev.pos[1],
+                frames: None,
+                control_points: None
+            }
+        }).collect())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ColorAction {
+    r: u8,
+    g: u8,
+    b: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frames: Option<u32>
+}
+
+/// A timed trigger on a non-light payload; `time` is in seconds from the performance start.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EventAction {
+    time: f32,
+    action: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum PayloadAction {
+    Color(ColorAction),
+    Event(EventAction)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AgentDescription {
+    #[serde(rename = "homeX")]
+    home_x: f32,
+    #[serde(rename = "homeY")]
+    home_y: f32,
+    #[serde(rename = "homeZ")]
+    home_z: f32,
+    #[serde(rename = "agentTraversal")]
+    traversals: AgentTraversals
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Payload {
+    id: usize,
+    #[serde(rename = "type")]
+    payload_type: String,
+    #[serde(rename = "payloadActions")]
+    actions: Vec<PayloadAction>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Performance {
+    id: usize,
+    #[serde(rename = "agentDescription")]
+    description: AgentDescription,
+    #[serde(rename = "payloadDescription")]
+    payload: Vec<Payload>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Show {
+    version: String,
+    #[serde(rename = "defaultPositionRate")]
+    default_position_rate: f32,
+    #[serde(rename = "defaultColorRate")]
+    default_color_rate: f32,
+    performances: Vec<Performance>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>
+}
+
+/// Extra, non-vviz information carried alongside the show.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Metadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    provenance: Option<provenance::Provenance>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timecode: Option<timecode::Mapping>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cues: Option<Vec<cues::Cue>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    acts: Option<Vec<acts::Act>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    preview: Option<preview::Preview>
+}
+
+/// Rounds to a fixed number of decimals so values that differ only in float noise print
+/// identically.
+fn round_fixed(value: f32) -> f32 {
+    const SCALE: f64 = 1e6;
+    ((value as f64 * SCALE).round() / SCALE) as f32
+}
+
+/// Puts the show into a canonical form for `--reproducible`: performances and payloads in
+/// id order and every float rounded to micrometres / microseconds.
+fn make_reproducible(show: &mut Show) {
+    show.performances.sort_by_key(|p| p.id);
+    for performance in show.performances.iter_mut() {
+        performance.payload.sort_by_key(|p| p.id);
+        for action in performance.payload.iter_mut().flat_map(|p| p.actions.iter_mut()) {
+            if let PayloadAction::Event(event) = action {
+                event.time = round_fixed(event.time);
+            }
+        }
+        let description = &mut performance.description;
+        description.home_x = round_fixed(description.home_x);
+        description.home_y = round_fixed(description.home_y);
+        description.home_z = round_fixed(description.home_z);
+        for traversal in description.traversals.0.iter_mut() {
+            traversal.dx = round_fixed(traversal.dx);
+            traversal.dy = round_fixed(traversal.dy);
+            traversal.dz = round_fixed(traversal.dz);
+            traversal.dt = traversal.dt.map(round_fixed);
+            if let Some(points) = traversal.control_points.as_mut() {
+                *points = points.map(|point| point.map(round_fixed));
+            }
+        }
+    }
+}
+
+fn read_show(fname: &std::path::Path) -> Result<Show> {
+    let vviz_file = std::fs::File::open(fname).map_err(error::io("Failed to open show file"))?;
+    serde_json::from_reader(std::io::BufReader::new(vviz_file)).map_err(|e| if e.is_io() {
+        Error::Io(format!("Failed to read show file: {}", e))
+    } else {
+        Error::Parse(format!("Failed to parse show file: {}", e))
+    })
+}
+
+fn write_show(show: &Show, fname: &std::path::Path) -> Result<()> {
+    write_show_as(show, &output::Vviz, fname)
+}
+
+/// Writes next to `fname` and moves the result over it once complete, so a failed encoding
+/// leaves any previous output in place.
+fn write_show_as(show: &Show, format: &dyn output::OutputFormat, fname: &std::path::Path) -> Result<()> {
+    let mut partial = fname.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = std::path::PathBuf::from(partial);
+    let written = std::fs::File::create(&partial).map_err(error::io("Failed to create new file")).and_then(|vviz_file| {
+        let mut writer = std::io::BufWriter::new(vviz_file);
+        format.write_to(show, &output::OutputOptions::default(), &mut writer)?;
+        writer.flush().map_err(error::io("Failed to write new file"))
+    });
+    match written {
+        Ok(()) => std::fs::rename(&partial, fname).map_err(error::io("Failed to write new file")),
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ChecksumAlgorithm {
+    Sha256
+}
+
+#[derive(Debug, Default)]
+struct ConvertOptions {
+    preset: Preset,
+    rotation: Option<Rotation>,
+    heading: Option<Rotation>,
+    translation: Option<Translation>,
+    /// Added to every input altitude before any other transform.
+    altitude_shift: f32,
+    colors: Option<ColorManifest>,
+    lights: Option<LightTracks>,
+    events: Option<PayloadEvents>,
+    countdown: Option<countdown::Countdown>,
+    time_align: Option<TimeAlign>,
+    time_shifts: Option<TimeShifts>,
+    script: Option<script::Script>,
+    clamp_speed: Option<f32>,
+    limit_accel: Option<f32>,
+    clamp_ground: Option<ground::GroundRepair>,
+    fit_box: Option<fit::FitBox>,
+    fit_limits: fit::ScaleLimits,
+    fit_distortion: f32,
+    crop: Option<crop::CropBox>,
+    crop_mode: crop::CropMode,
+    land_stagger: Option<landing::LandStagger>,
+    land_hover: f32,
+    land_speed: f32,
+    proximity_report: Option<PathBuf>,
+    histograms: Option<PathBuf>,
+    /// The heatmap file, and its cell size in meters.
+    heatmap: Option<(PathBuf, f32)>,
+    export_derivatives: Option<PathBuf>,
+    export_blender: Option<PathBuf>,
+    keyframes: Option<f32>,
+    keyframes_output: Option<PathBuf>,
+    curve: curve::Curve,
+    traversal_mode: frames::TraversalMode,
+    curve_tolerance: f32,
+    split_output: Option<PathBuf>,
+    split_only: bool,
+    segment: Option<f32>,
+    merge_acts: bool,
+    columns: columns::ColumnChoice,
+    quantize: Option<f32>,
+    format: output::ShowFormat,
+    reproducible: bool,
+    embed_provenance: bool,
+    timecode: Option<timecode::Timecode>,
+    cues: Option<Vec<cues::Cue>>,
+    transforms: Vec<String>,
+    checksum: Option<ChecksumAlgorithm>,
+    max_errors: usize,
+    ulog_rate: f32,
+    lenient: bool,
+    on_duplicate: continuity::OnDuplicate,
+    expect_drones: Option<usize>,
+    renumber: bool,
+    dry_run: bool,
+    backup: bool,
+    dialect: dialect::CsvDialect,
+    energy_report: Option<PathBuf>,
+    energy_model: energy::EnergyModel,
+    /// Keep only every Nth input sample, for a marked preview.
+    preview_decimate: Option<usize>,
+    /// Bytes the built show may take before its performances spill to disk.
+    max_memory: Option<u64>,
+    limits: validate::Limits,
+    profile: Option<validate::Profile>,
+    config: Config
+}
+
+impl ConvertOptions {
+
+    fn from_args(args: &ConvertArgs) -> Result<Self> {
+        if !(args.land_speed > 0.0 && args.land_speed.is_finite()) {
+            return Err(Error::Parse("--land-speed must be positive".to_string()));
+        }
+        if args.clamp_speed.is_some_and(|speed| !(speed > 0.0 && speed.is_finite())) {
+            return Err(Error::Parse("--clamp-speed must be positive".to_string()));
+        }
+        if args.limit_accel.is_some_and(|accel| !(accel > 0.0 && accel.is_finite())) {
+            return Err(Error::Parse("--limit-accel must be positive".to_string()));
+        }
+        if !args.alt_offset.is_finite() {
+            return Err(Error::Parse("--alt-offset must be a finite number".to_string()));
+        }
+        if args.max_memory.is_some() {
+            // These read every trajectory of the finished show, which may be on disk by then.
+            let whole_show = [
+                ("--proximity-report", args.proximity_report.is_some()),
+                ("--histograms", args.histograms.is_some()),
+                ("--heatmap", args.heatmap.is_some()),
+                ("--export-blender", args.export_blender.is_some()),
+                ("--energy-report", args.energy_report.is_some()),
+                ("--events", args.events.is_some()),
+                ("--timecode-start", args.timecode_start.is_some()),
+                ("--split-output", args.split_output.is_some()),
+                ("--quantize", args.quantize.is_some()),
+                ("--reproducible", args.reproducible),
+                ("--profile or flight limits", args.profile.is_some() || !args.limits.is_empty()),
+                ("--format other than vviz", args.format != output::ShowFormat::Vviz)
+            ];
+            if let Some((flag, _)) = whole_show.iter().find(|(_, set)| *set) {
+                return Err(Error::Parse(format!("--max-memory can't be combined with {}, which needs the whole show in memory", flag)));
+            }
+        }
+        Ok(ConvertOptions {
+            preset: args.preset,
+            rotation: args.rotate.as_ref().map(F3D::rotation),
+            heading: args.align_heading.map(|bearing| heading::rotation(args.front, bearing)),
+            translation: args.translate.as_ref().map(F3D::translation),
+            altitude_shift: args.alt_offset - args.alt_datum.ground(),
+            colors: args.color_manifest.as_deref().map(ColorManifest::load).transpose()?,
+            lights: args.lights.as_deref().map(LightTracks::load).transpose()?,
+            events: args.payload_events.as_deref().map(PayloadEvents::load).transpose()?,
+            countdown: args.countdown.map(|length| countdown::Countdown { length, pattern: args.countdown_pattern, color: args.countdown_color }),
+            time_align: args.time_align,
+            time_shifts: args.time_shift.as_deref().map(TimeShifts::load).transpose()?,
+            script: args.script.as_deref().map(script::Script::load).transpose()?,
+            clamp_speed: args.clamp_speed,
+            limit_accel: args.limit_accel,
+            clamp_ground: args.clamp_ground,
+            fit_box: args.fit_box,
+            fit_limits: args.fit_limits,
+            fit_distortion: args.fit_distortion,
+            crop: args.crop,
+            crop_mode: args.crop_mode,
+            land_stagger: args.land_stagger,
+            land_hover: args.land_hover,
+            land_speed: args.land_speed,
+            // A dry run computes everything but writes none of it.
+            proximity_report: args.proximity_report.clone().filter(|_| !args.dry_run),
+            histograms: args.histograms.clone().filter(|_| !args.dry_run),
+            heatmap: args.heatmap.clone().filter(|_| !args.dry_run).map(|fname| (fname, args.resolution)),
+            export_derivatives: args.export_derivatives.clone().filter(|_| !args.dry_run),
+            export_blender: args.export_blender.clone().filter(|_| !args.dry_run),
+            keyframes: args.keyframes,
+            keyframes_output: args.keyframes_output.clone(),
+            curve: args.curve,
+            traversal_mode: args.traversal_mode,
+            curve_tolerance: args.curve_tolerance,
+            split_output: args.split_output.clone(),
+            split_only: args.split_only,
+            segment: args.segment,
+            merge_acts: args.merge_acts,
+            columns: args.columns.clone(),
+            quantize: args.quantize,
+            format: args.format,
+            reproducible: args.reproducible,
+            embed_provenance: args.embed_provenance,
+            timecode: args.timecode_start.map(|start| timecode::Timecode::new(start, args.fps)).transpose()?,
+            cues: args.events.as_deref().map(cues::load).transpose()?,
+            transforms: describe_transforms(args),
+            checksum: args.checksum,
+            max_errors: args.max_errors,
+            ulog_rate: args.ulog_rate,
+            lenient: args.lenient,
+            on_duplicate: args.on_duplicate,
+            expect_drones: args.expect_drones,
+            renumber: args.renumber,
+            dry_run: args.dry_run,
+            backup: args.backup,
+            dialect: args.dialect.clone(),
+            energy_report: args.energy_report.clone(),
+            max_memory: args.max_memory,
+            preview_decimate: args.preview_decimate,
+            energy_model: args.energy_model.clone(),
+            limits: match args.profile {
+                Some(profile) => args.limits.clone().or(profile.limits()),
+                None => args.limits.clone()
+            },
+            profile: args.profile,
+            config: args.config.as_deref().map(Config::load).transpose()?.unwrap_or_default()
+        })
+    }
+}
+
+/// Converts one archive, returning the combined shows written and the number of validation
+/// problems found in them. A multi-show archive gives one `show.<folder>.vviz` per folder
+/// unless its acts are merged, and a segmented show one `show.part<N>.vviz` per segment.
+fn csv2vviz(source: &str, fname: PathBuf, new_file: PathBuf, options: &ConvertOptions) -> Result<(Vec<PathBuf>, usize)> {
+    let (mut written, mut problems) = (vec![], 0);
+    for (act, drones, acts) in acts::read(archive::Input::Path(&fname), options)? {
+        let origin = drones.iter().map(|(_, samples)| samples[0].t).fold(f64::INFINITY, f64::min);
+        let segments = match options.segment {
+            Some(length) => segment::split(drones, length)?,
+            None => vec![drones]
+        };
+        let count = segments.len();
+        for (index, drones) in segments.into_iter().enumerate() {
+            // Drones start and finish at their own times, and may sit out an act.
+            let from = drones.iter().map(|(_, samples)| samples[0].t).fold(f64::INFINITY, f64::min);
+            let to = drones.iter().map(|(_, samples)| samples[samples.len() - 1].t).fold(f64::NEG_INFINITY, f64::max);
+            let acts = acts::clip(&acts, ((from - origin) / 1000.0) as f32, ((to - origin) / 1000.0) as f32);
+            let mut names = vec![];
+            if !act.is_empty() {
+                names.push(act.clone());
+            }
+            if count > 1 {
+                names.push(format!("part{}", index + 1));
+            }
+            let (new_file, split_output) = if names.is_empty() {
+                (new_file.clone(), options.split_output.clone())
+            } else {
+                let name = names.join(".");
+                (new_file.with_extension(format!("{}.{}", name, options.format.writer().extension())), options.split_output.as_ref().map(|dir| dir.join(&name)))
+            };
+            problems += write_converted(source, &fname, drones, acts, &new_file, split_output.as_deref(), options)?;
+            if !options.split_only && !options.dry_run {
+                written.push(new_file);
+            }
+        }
+    }
+    Ok((written, problems))
+}
+
+/// Builds and writes one show from `drones`, and the `acts` it was merged from, returning its
+/// number of validation problems.
+fn write_converted(
+    source: &str, fname: &std::path::Path, drones: Vec<Drone>, acts: Vec<acts::Act>, new_file: &std::path::Path,
+    split_output: Option<&std::path::Path>, options: &ConvertOptions
+) -> Result<usize> {
+
+    if options.dry_run {
+        println!("Checking {} (dry run)", new_file.to_str().unwrap());
+    } else if !options.split_only {
+        println!("Generating {}", new_file.to_str().unwrap());
+    }
+
+    if let Some(tolerance) = options.keyframes.filter(|_| !options.dry_run) {
+        let output = options.keyframes_output.clone()
+            .unwrap_or_else(|| new_file.with_extension("keyframes.json"));
+        keyframes::write(&drones, tolerance, &output)?;
+    }
+    progress::phase("build");
+    let mut spill = options.max_memory.map(spill::Spill::new).transpose()?;
+    let (mut show, problems) = build_show(drones, options, spill.as_mut())?;
+    if !acts.is_empty() {
+        show.metadata.get_or_insert_with(Metadata::default).acts = Some(countdown::delay_acts(acts, options.countdown, show.default_color_rate));
+    }
+
+    if options.renumber {
+        let mapping = continuity::renumber(&mut show);
+        if !options.dry_run {
+            continuity::write_mapping(&mapping, &new_file.with_extension("renumber.csv"))?;
+        }
+    }
+
+    if options.embed_provenance {
+        let digest = sha256::file_digest(fname).map_err(error::io("Failed to hash input file"))?;
+        show.metadata.get_or_insert_with(Metadata::default).provenance = Some(
+            provenance::Provenance::new(source, digest, options.transforms.clone(), !options.reproducible)
+        );
+    }
+
+    if let Some(step) = options.quantize {
+        quantize::apply(&mut show, step);
+    }
+
+    if options.reproducible {
+        make_reproducible(&mut show);
+    }
+
+    if options.dry_run {
+        return Ok(problems);
+    }
+
+    progress::phase("write");
+    if let Some(split_dir) = split_output {
+        write_split(&show, split_dir)?;
+    }
+
+    if !options.split_only {
+        if options.backup {
+            backup(new_file)?;
+        }
+        match spill.as_ref().filter(|spill| spill.used()) {
+            Some(spill) => spill.write(&show, new_file)?,
+            None => write_show_as(&show, options.format.writer(), new_file)?
+        }
+        if options.checksum.is_some() {
+            sha256::write_sidecar(new_file)?;
+        }
+        if let Some(timecode) = &options.timecode {
+            timecode::write_sidecar(&show, timecode, &new_file.with_extension("timecode.csv"))?;
+        }
+    }
+
+    Ok(problems)
+}
+
+/// One trajectory sample: time in milliseconds on the input clock, position along the
+/// trajectory CSV axes (east, north, up meters) and the LED color of the CSV's color columns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub t: f64,
+    pub pos: [f32; 3],
+    pub rgb: [u8; 3]
+}
+
+impl Sample {
+
+    /// The same sample at another time.
+    fn with_time(&self, t: f64) -> Sample {
+        Sample { t, ..*self }
+    }
+}
+
+/// A drone number (as in the `Drone N` entry names) with its transformed samples.
+type Drone = (usize, Vec<Sample>);
+
+/// The coordinate transforms for one drone's samples. Flight log positions are already
+/// east/north/up meters.
+fn transformer(options: &ConvertOptions, drone_id: usize, is_log: bool) -> impl Fn(Sample) -> Sample + '_ {
+    let preset_rotation = options.preset.rotation().filter(|_| !is_log);
+    let drone_override = options.config.drones.get(&drone_id);
+    move |sample| {
+        let [east, north, up] = if is_log { sample.pos } else { options.preset.map_axes(sample.pos) };
+        let mut point = Point3D::<f32, UnknownUnit>::new(east, up + options.altitude_shift, north);
+
+        if let Some(rotation) = preset_rotation {
+            point = rotation.transform_point3d(point);
+        }
+
+        if let Some(rotation) = options.rotation {
+            point = rotation.transform_point3d(point);
+        }
+
+        if let Some(heading) = options.heading {
+            point = heading.transform_point3d(point);
+        }
+
+        if let Some(translation) = options.translation {
+            point = translation.transform_point3d(&point);
+        }
+
+        if let Some(drone_override) = drone_override {
+            if let Some(rotate) = &drone_override.rotate {
+                point = rotate.rotation().transform_point3d(point);
+            }
+            if let Some(translate) = &drone_override.translate {
+                point = translate.translation().transform_point3d(&point);
+            }
+        }
+
+        Sample { pos: [point.x, point.z, point.y], ..sample }
+    }
+}
+
+/// Reads the drones out of the input with `format` (only those in folder `only`, when
+/// given), applying the coordinate transforms and timing adjustments.
+fn read_drones(format: &dyn input::InputFormat, input: archive::Input, only: Option<&str>, options: &ConvertOptions) -> Result<Vec<Drone>> {
+
+    let mut drones: Vec<Drone> = vec![];
+    let mut names: Vec<String> = vec![];
+    let mut errors = error::ErrorReport::new(options.max_errors);
+    let mut skipped_rows: Vec<(String, usize)> = vec![];
+
+    for agent in format.read_agents(input, only)? {
+        let agent = agent?;
+        let name = agent.name;
+        progress::entry(&name);
+        let transform = transformer(options, agent.id, agent.east_north_up);
+        let mut samples: Vec<Sample> = agent.samples.into_iter().map(transform).collect();
+        if let Some(every) = options.preview_decimate {
+            samples = preview::decimate(samples, every);
+        }
+        let clean = agent.errors.total() == 0;
+        errors.append(agent.errors);
+        if agent.skipped_rows > 0 {
+            skipped_rows.push((name.clone(), agent.skipped_rows));
+        }
+        if clean {
+            match samples.len() {
+                0 if options.lenient => {
+                    progress::warning(format!("skipping {}: it has no samples", name));
+                    continue;
+                }
+                0 => errors.push(Error::Parse(format!("{} has no samples", name)))?,
+                1 if options.lenient => progress::warning(format!("{} has a single sample; holding it in place", name)),
+                1 => errors.push(Error::Parse(format!("{} has a single sample; a trajectory needs at least two", name)))?,
+                _ => {}
+            }
+        }
+        drones.push((agent.id, samples));
+        names.push(name);
+    }
+    errors.finish()?;
+    // The rows are simply left out, so each trajectory runs straight from the sample before
+    // them to the one after.
+    if !skipped_rows.is_empty() {
+        let total: usize = skipped_rows.iter().map(|(_, rows)| rows).sum();
+        let files: Vec<String> = skipped_rows.iter().map(|(name, rows)| format!("{}: {}", name, rows)).collect();
+        progress::warning(format!(
+            "skipped {} unparsable rows in {} files, interpolating across them ({})",
+            total, skipped_rows.len(), files.join(", ")
+        ));
+    }
+    continuity::resolve_duplicates(&mut drones, &names, options.on_duplicate)?;
+    continuity::check(&drones, options.expect_drones)?;
+
+    // Single-sample drones hold their position until the last sample of the show.
+    let end = drones.iter().filter_map(|(_, samples)| samples.last()).map(|sample| sample.t).fold(f64::NEG_INFINITY, f64::max);
+    for (_, samples) in drones.iter_mut().filter(|(_, samples)| samples.len() == 1) {
+        if end > samples[0].t {
+            samples.push(samples[0].with_time(end));
+        }
+    }
+
+    // Shifted drones are realigned to the original start (unless another alignment was asked
+    // for) so that delays become holds and advances trim the start of the performance.
+    let mut time_align = options.time_align;
+    if let Some(shifts) = &options.time_shifts {
+        let start = shifts.apply(&mut drones);
+        time_align = time_align.or(Some(TimeAlign::Epoch(start)));
+    }
+
+    if let Some(mode) = time_align {
+        timing::align_start(&mut drones, mode);
+    }
+
+    if let Some(script) = &options.script {
+        let (samples, changed) = script.apply(&mut drones)?;
+        println!("Script changed {} samples of {} drones", samples, changed);
+    }
+
+    // Fitted first so the speed limits apply to the fitted show.
+    if let Some(target) = options.fit_box {
+        fit::apply(&mut drones, target, options.fit_limits, options.fit_distortion);
+    }
+
+    if let Some(max_speed) = options.clamp_speed {
+        let modified = retime::clamp_speed(&mut drones, max_speed);
+        println!("Slowed {} segments to {} m/s", modified, max_speed);
+    }
+
+    if let Some(max_accel) = options.limit_accel {
+        let modified = retime::limit_accel(&mut drones, max_accel);
+        println!("Re-timed {} segments for {} m/s²", modified, max_accel);
+    }
+
+    ground::check(&mut drones, options.clamp_ground);
+
+    if let Some(crop) = options.crop {
+        crop::apply(&mut drones, crop, options.crop_mode)?;
+    }
+
+    if let Some(fname) = &options.export_derivatives {
+        derivatives::export(&drones, fname)?;
+    }
+
+    Ok(drones)
+}
+
+/// Builds the show from the drones' samples, running the per-show reports and validation.
+/// Returns the show and the number of validation problems.
+fn build_show(drones: Vec<Drone>, options: &ConvertOptions, mut spill: Option<&mut spill::Spill>) -> Result<(Show, usize)> {
+
+    let mut show = Show {
+        version: options.traversal_mode.version().into(),
+        default_position_rate: 4.0,
+        default_color_rate: 4.0,
+        performances: vec![],
+        metadata: None
+    };
+    let mut blender_keys = vec![];
+    let landing = options.land_stagger.map(|stagger| stagger.plan(&drones, options.land_hover, options.land_speed));
+    let mut pending: usize = drones.iter().map(|(_, samples)| spill::samples_size(samples)).sum();
+
+    for (drone_id, mut samples) in drones {
+        pending -= spill::samples_size(&samples);
+        if options.traversal_mode == frames::TraversalMode::Frames {
+            samples = frames::resample(&samples, show.default_position_rate);
+        }
+
+        // The color manifest overrides any light track for the same drone.
+        // Light and payload programs move with the drone's time shift.
+        let shift = options.time_shifts.as_ref().map_or(0.0, |shifts| shifts.millis(drone_id));
+        let start = samples[0].t - shift;
+        let duration = ((samples[samples.len() - 1].t - samples[0].t) / 1000.0) as f32;
+        let frames = (duration * show.default_color_rate).ceil() as u32;
+        let actions = options.colors.as_ref()
+            .and_then(|colors| colors.get(drone_id))
+            .map(|spec| spec.actions(frames))
+            .or_else(|| options.lights.as_ref().and_then(|lights| {
+                lights.actions(drone_id, start, frames, show.default_color_rate)
+            }));
+
+        // The countdown runs before the light program, with the drone holding on its pad, and
+        // moves event times back by the same amount.
+        let mut start = start;
+        let actions = match &options.countdown {
+            Some(countdown) => {
+                countdown.hold(&mut samples, show.default_color_rate);
+                start -= countdown.lead(show.default_color_rate) as f64 * 1000.0;
+                Some(countdown.prepend(actions, show.default_color_rate))
+            }
+            None => actions
+        };
+        // The landing comes after the light program, which holds its last color through it.
+        if let Some(landing) = &landing {
+            landing.append(drone_id, &mut samples);
+            if options.traversal_mode == frames::TraversalMode::Frames {
+                samples = frames::resample(&samples, show.default_position_rate);
+            }
+        }
+
+        if options.export_blender.is_some() {
+            blender_keys.push(blender::DroneKeys::new(drone_id, &samples, actions.as_deref(), show.default_color_rate));
+        }
+
+        let mut payload = vec![];
+        if let Some(actions) = actions {
+            payload.push(Payload {
+                id: 0,
+                payload_type: "light".into(),
+                actions: actions.into_iter().map(PayloadAction::Color).collect()
+            });
+        }
+        if let Some(events) = &options.events {
+            payload.extend(events.payloads(drone_id, start, payload.len()));
+        }
+
+        let mut performance = Performance {
+            id: drone_id - 1, // vviz uses 0-indexing
+            description: AgentDescription {
+                home_x: samples[0].pos[0],
+                home_y: samples[0].pos[2],
+                home_z: samples[0].pos[1],
+                traversals: {
+                    let traversals = match options.curve {
+                        curve::Curve::Linear => samples.into(),
+                        curve::Curve::Bezier => curve::fit(&samples, options.curve_tolerance)
+                    };
+                    match options.traversal_mode {
+                        frames::TraversalMode::Dt => traversals,
+                        frames::TraversalMode::Frames => frames::to_frames(traversals, show.default_position_rate)
+                    }
+                }
+            },
+            payload
+        };
+        if let Some(spill) = spill.as_deref_mut() {
+            spill.hold(&mut performance, pending)?;
+        }
+        show.performances.push(performance);
+    }
+
+    show.performances.sort_by_cached_key(|p| p.id);
+    if let Some(spill) = spill {
+        spill.seal(&show.performances);
+    }
+
+    if let Some(events) = &options.events {
+        for drone_id in events.drone_ids() {
+            if !show.performances.iter().any(|p| p.id + 1 == *drone_id) {
+                progress::warning(format!("payload events for drone {} which is not in the show", drone_id));
+            }
+        }
+    }
+
+    if let Some(report) = &options.proximity_report {
+        proximity::write_report(&show, report)?;
+    }
+
+    if let Some(report) = &options.histograms {
+        histograms::write_report(&show, report)?;
+    }
+
+    if let Some((fname, resolution)) = &options.heatmap {
+        heatmap::write(&show, *resolution, fname)?;
+    }
+
+    if let Some(fname) = &options.export_blender {
+        blender::write(&blender_keys, fname)?;
+    }
+
+    if options.energy_report.is_some() {
+        energy::energy_report(&show, &options.energy_model, options.energy_report.as_deref().filter(|_| !options.dry_run))?;
+    }
+
+    if let Some(timecode) = &options.timecode {
+        show.metadata.get_or_insert_with(Metadata::default).timecode = Some(timecode.mapping());
+    }
+
+    if let Some(decimate) = options.preview_decimate {
+        show.metadata.get_or_insert_with(Metadata::default).preview = Some(preview::Preview { decimate });
+    }
+
+    if let Some(cues) = &options.cues {
+        // Cue times count from the end of the countdown, when the performance starts.
+        let lead = options.countdown.map_or(0.0, |countdown| countdown.lead(show.default_color_rate));
+        let cues: Vec<cues::Cue> = cues.iter().map(|cue| cues::Cue { time: cue.time + lead, ..cue.clone() }).collect();
+        let end = show.performances.iter()
+            .map(|performance| validate::timeline(performance, &show, &track::Track::new(performance, show.default_position_rate)))
+            .fold(0.0, f32::max);
+        for cue in cues.iter().filter(|cue| cue.time > end) {
+            progress::warning(format!("event '{}' at {} s is after the show ends at {:.2} s", cue.label, cue.time, end));
+        }
+        show.metadata.get_or_insert_with(Metadata::default).cues = Some(cues);
+    }
+
+    let mut problems = 0;
+    if !options.limits.is_empty() || options.profile.is_some() {
+        problems = validate::validate(&show, &options.limits, options.profile);
+    }
+
+    Ok((show, problems))
+}
+
+/// Converts an in-memory archive and returns the show as vviz JSON, without touching the
+/// filesystem unless the options name auxiliary files. `options_json` is an object of
+/// `convert` flags, as for the C interface. Validation problems are reported as an error.
+pub fn convert_bytes(archive: &[u8], options_json: &str) -> Result<String> {
+    let args = ffi::parse_options("archive", options_json)?;
+    let options = ConvertOptions::from_args(&args)?;
+
+    let mut shows = acts::read(archive::Input::Bytes(archive), &options)?;
+    if shows.len() > 1 {
+        let names: Vec<&str> = shows.iter().map(|(name, _, _)| name.as_str()).collect();
+        return Err(Error::Format(format!("The archive holds {} shows ({}); merge them with merge_acts", shows.len(), names.join(", "))));
+    }
+    let (_, drones, acts) = shows.remove(0);
+    let (mut show, problems) = build_show(drones, &options, None)?;
+    if problems > 0 {
+        return Err(Error::Validation(format!("Validation found {} problems", problems)));
+    }
+    if !acts.is_empty() {
+        show.metadata.get_or_insert_with(Metadata::default).acts = Some(countdown::delay_acts(acts, options.countdown, show.default_color_rate));
+    }
+    if options.renumber {
+        continuity::renumber(&mut show);
+    }
+
+    if options.embed_provenance {
+        let mut digest = sha256::Sha256::default();
+        digest.update(archive);
+        show.metadata.get_or_insert_with(Metadata::default).provenance = Some(
+            // There is no clock to read on wasm32-unknown-unknown.
+            provenance::Provenance::new(
+                "archive", sha256::hex(&digest.finalize()), options.transforms.clone(),
+                !options.reproducible && cfg!(not(target_arch = "wasm32"))
+            )
+        );
+    }
+
+    if let Some(step) = options.quantize {
+        quantize::apply(&mut show, step);
+    }
+
+    if options.reproducible {
+        make_reproducible(&mut show);
+    }
+
+    let bytes = output::Vviz.write(&show, &output::OutputOptions::default())?;
+    Ok(String::from_utf8(bytes).expect("JSON is UTF-8"))
+}
+
+/// Writes one single-performance show per drone as `drone_NNN.json` (drone numbers as in
+/// the `Drone N` entry names).
+fn write_split(show: &Show, dir: &std::path::Path) -> Result<()> {
+    std::fs::create_dir_all(dir).map_err(error::io("Failed to create split output directory"))?;
+    for performance in &show.performances {
+        let single = Show {
+            version: show.version.clone(),
+            default_position_rate: show.default_position_rate,
+            default_color_rate: show.default_color_rate,
+            performances: vec![performance.clone()],
+            metadata: show.metadata.clone()
+        };
+        write_show(&single, &dir.join(format!("drone_{:03}.json", performance.id + 1)))?;
+    }
+    println!("Wrote {} per-drone files to {}", show.performances.len(), dir.display());
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+struct F3D {
+    x: f32,
+    y: f32,
+    z: f32
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ParseF3DError {
+    error: String
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ParseFloatError {
+    error: String
+}
+
+impl std::fmt::Display for ParseF3DError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.error)
+    }
+}
+
+impl std::error::Error for ParseF3DError {}
+
+impl From<ParseFloatError> for ParseF3DError {
+    fn from(_: ParseFloatError) -> Self {
+        ParseF3DError {
+            error: "Could not parse float".to_string(),
+        }
+    }
+}
+
+impl std::str::FromStr for F3D {
+    type Err = ParseF3DError;
+
+    /// Three numbers separated by spaces and/or commas; each may use a sign or scientific
+    /// notation (`-1.5 +2 1e-3`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let coordinates = s.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<f32>().ok().filter(|value| value.is_finite())
+                .ok_or_else(|| ParseF3DError { error: format!("invalid coordinate '{}'", token) }))
+            .collect::<Result<Vec<f32>, _>>()?;
+        let [x, y, z] = coordinates[..] else {
+            return Err(ParseF3DError { error: format!("expected three coordinates, found {}", coordinates.len()) });
+        };
+        Ok(F3D { x, y, z })
+    }
+}
+
+impl TryFrom<String> for F3D {
+    type Error = ParseF3DError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        F3D::from_str(&value)
+    }
+}
+
+impl F3D {
+
+    /// Euler rotation in degrees.
+    fn rotation(&self) -> Rotation {
+        Rotation3D::euler(
+            Angle::degrees(self.x),
+            Angle::degrees(self.y),
+            Angle::degrees(self.z)
+        ).normalize()
+    }
+
+    fn translation(&self) -> Translation {
+        Translation3D::new(
+            self.x,
+            self.y,
+            self.z
+        )
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "csv2vviz",
+    about = "Converts Skybrush CSV zip archives to Finale3D-compatible VVIZ format",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub struct Cli {
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    convert: ConvertArgs
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Convert a Skybrush CSV zip archive (the default when no subcommand is given)
+    Convert(Box<ConvertArgs>),
+    /// Fit a show's home positions onto surveyed pad positions and transform the whole show
+    Align(align::AlignArgs),
+    /// Check a converted show's checksum sidecar and flight limits
+    Validate(validate::ValidateArgs),
+    /// Write a synthetic show of simple formations for testing viewers and validation
+    Generate(generate::GenerateArgs),
+    /// Export a converted show's trajectories for other flight stacks
+    Export(export::ExportArgs),
+    /// Play a converted show back in real time to lighting consoles and previz rigs
+    Play(play::PlayArgs),
+    /// Write a repaired copy of a drone CSV archive, listing every change made
+    Fix(fix::FixArgs),
+    /// Check drone CSVs against a configurable set of pre-flight rules
+    Lint(lint::LintArgs),
+    /// Summarize a converted show: rates, drones, samples and payloads
+    Inspect(inspect::InspectArgs),
+    /// Rewrite a converted show for another vviz version
+    Migrate(migrate::MigrateArgs),
+    /// Replace a converted show's light program from a lights archive, keeping its trajectories
+    MergeLights(merge_lights::MergeLightsArgs),
+    /// Re-time a converted show for other position and color rates
+    Resample(resample::ResampleArgs),
+    /// Rotate, scale and move a converted show
+    Transform(transform::TransformArgs),
+    /// Write one drone of a converted show as a trajectory CSV
+    Extract(extract::ExtractArgs),
+    /// Convert without writing, and check the show's positions against the input samples
+    Verify(Box<verify::VerifyArgs>),
+    /// Check two converted shows against each other within position and color tolerances
+    Compare(compare::CompareArgs),
+    /// Hand failed drones' performances to spares, optionally taking off from the spares' pads
+    Swap(swap::SwapArgs),
+    /// Trade performances between drones that keep taking off from their own homes
+    Reassign(reassign::ReassignArgs)
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ConvertArgs {
+
+    /// Archives, directories of drone CSVs or single drone CSVs to convert, or http(s) URLs
+    /// to download archives from (needs the `fetch` feature)
+    // clap still requires at least one whenever a conversion runs; the subcommands just
+    // negate the requirement when `Cli` flattens these arguments next to them.
+    #[arg(required = true)]
+    filenames: Vec<String>,
+
+    /// Write outputs into this directory (created if needed) instead of next to the inputs
+    #[arg(long)]
+    out_dir: Option<PathBuf>,
+
+    /// Coordinate conventions of the tool that produced the CSVs
+    #[arg(short, long, value_enum, default_value_t)]
+    preset: Preset,
+
+    /// Euler rotation in degrees about the x (east), y (up) and z (north) axes (`"0 90 0"`)
+    #[arg(short, long, allow_hyphen_values = true)]
+    rotate: Option<F3D>,
+
+    /// Turn the show about the vertical, after --rotate, so that its front faces this compass
+    /// bearing (`135deg`)
+    #[arg(long, value_parser = heading::parse_bearing)]
+    align_heading: Option<f32>,
+
+    /// Bearing the design's front faces in its own coordinates, for --align-heading
+    #[arg(long, value_parser = heading::parse_bearing, default_value = "0", requires = "align_heading")]
+    front: f32,
+
+    /// Offset in meters along x (east), y (up) and z (north), after the rotations
+    #[arg(short, long, allow_hyphen_values = true)]
+    translate: Option<F3D>,
+
+    /// Datum of the input altitudes: `agl`, or `msl:<ground elevation>` to subtract the
+    /// launch site's elevation above sea level
+    #[arg(long, default_value = "agl")]
+    alt_datum: datum::AltDatum,
+
+    /// Raise (or, when negative, lower) every input altitude by this many meters
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    alt_offset: f32,
+
+    /// CSV of drone id to color (`#RRGGBB`, `R G B`, or a `>`-separated gradient)
+    #[arg(long)]
+    color_manifest: Option<PathBuf>,
+
+    /// Zip of per-drone `t, r, g, b` CSVs merged into the light program
+    #[arg(long)]
+    lights: Option<PathBuf>,
+
+    /// Hold every drone on its first position for this long (`30s`) before the performance,
+    /// with its lights running --countdown-pattern
+    #[arg(long, value_parser = parse_countdown, conflicts_with = "segment")]
+    countdown: Option<f32>,
+
+    /// Light pattern shown during --countdown
+    #[arg(long, value_enum, default_value_t, requires = "countdown")]
+    countdown_pattern: countdown::Pattern,
+
+    /// Color of the --countdown pattern (`#RRGGBB` or `R G B`)
+    #[arg(long, value_parser = parse_color, default_value = "#ffffff", requires = "countdown")]
+    countdown_color: lights::Rgb,
+
+    /// CSV of `id, time, channel, action` payload triggers (pyro, stage effects, ...)
+    #[arg(long)]
+    payload_events: Option<PathBuf>,
+
+    /// CSV of `time, label` cues (`Act 2`, `Landing`, ...) recorded in the show's metadata;
+    /// times are seconds of show time or durations like `2m15s`
+    #[arg(long)]
+    events: Option<PathBuf>,
+
+    /// TOML file with per-drone transform overrides
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Align drones to a common t=0: `earliest`, `latest`, or an explicit start time in ms
+    #[arg(long)]
+    time_align: Option<TimeAlign>,
+
+    /// CSV of `id, seconds` per-drone delays (positive) or advances (negative)
+    #[arg(long)]
+    time_shift: Option<PathBuf>,
+
+    /// Script run on every sample to adjust its position (a subset of Rhai)
+    #[arg(long)]
+    script: Option<PathBuf>,
+
+    /// Slow the whole show wherever a drone flies faster than this speed (m/s), so it flies at
+    /// the limit and the others keep in step
+    #[arg(long)]
+    clamp_speed: Option<f32>,
+
+    /// Slow the whole show wherever a drone would accelerate harder than this (m/s²), keeping
+    /// the drones in step
+    #[arg(long)]
+    limit_accel: Option<f32>,
+
+    /// Repair samples below the ground after transforms: `clamp` them to z = 0 (the default)
+    /// or `lift` the whole show
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "clamp")]
+    clamp_ground: Option<ground::GroundRepair>,
+
+    /// Scale each axis on its own so the show fills a box of this size, `"120 80 100"` (east,
+    /// north, up meters): horizontally about its middle, vertically about the ground
+    #[arg(long)]
+    fit_box: Option<fit::FitBox>,
+
+    /// Smallest and largest scale --fit-box may give an axis
+    #[arg(long, default_value = "0.5:2", requires = "fit_box")]
+    fit_limits: fit::ScaleLimits,
+
+    /// Warn when --fit-box scales one axis more than this many times as much as another
+    #[arg(long, default_value_t = 1.25, requires = "fit_box")]
+    fit_distortion: f32,
+
+    /// Box of two opposite corners, `"x1 y1 z1, x2 y2 z2"` (east, north, up meters after
+    /// transforms), that every drone's trajectory must stay inside
+    #[arg(long, allow_hyphen_values = true)]
+    crop: Option<crop::CropBox>,
+
+    /// What to do with drones leaving the --crop box
+    #[arg(long, value_enum, default_value_t, requires = "crop")]
+    crop_mode: crop::CropMode,
+
+    /// End the show on the ground: every drone still in the air hovers where it finished,
+    /// then groups of them (`rows`, `columns`, `layers` or `drones`) descend this far apart
+    /// (`rows:3s`)
+    #[arg(long, conflicts_with = "segment")]
+    land_stagger: Option<landing::LandStagger>,
+
+    /// How long drones hover after the show before the first --land-stagger group descends
+    #[arg(long, value_parser = timing::parse_duration, default_value = "5s", requires = "land_stagger")]
+    land_hover: f32,
+
+    /// Descent speed (m/s) for --land-stagger
+    #[arg(long, default_value_t = 1.0, requires = "land_stagger")]
+    land_speed: f32,
+
+    /// Write the closest pair of drones at every time step to this CSV
+    #[arg(long)]
+    proximity_report: Option<PathBuf>,
+
+    /// Write histograms of the fleet's speed, acceleration and altitude to this JSON file
+    #[arg(long)]
+    histograms: Option<PathBuf>,
+
+    /// Draw a top-down map of the drones' flight time over the ground to this PNG or SVG
+    #[arg(long)]
+    heatmap: Option<PathBuf>,
+
+    /// Cell size of the `--heatmap` (`1m`, `50cm`; meters without a unit)
+    #[arg(long, value_parser = heatmap::parse_resolution, default_value = "1m", requires = "heatmap")]
+    resolution: f32,
+
+    /// Traversal encoding; `bezier` needs a viewer that understands control points
+    #[arg(long, value_enum, default_value_t)]
+    curve: curve::Curve,
+
+    /// Largest distance (m) between a sample and the fitted curve with `--curve bezier`
+    #[arg(long, default_value_t = 0.05)]
+    curve_tolerance: f32,
+
+    /// Write traversal durations as seconds (`dt`) or as frame counts at the position rate,
+    /// resampling the input onto that rate (`frames`, declared as vviz version 1.1)
+    #[arg(long, value_enum, default_value_t)]
+    traversal_mode: frames::TraversalMode,
+
+    /// Write per-drone velocity, speed and acceleration CSVs into this zip
+    #[arg(long)]
+    export_derivatives: Option<PathBuf>,
+
+    /// Write a Blender Python script that recreates the show as keyframed emissive spheres
+    #[arg(long)]
+    export_blender: Option<PathBuf>,
+
+    /// Also write each trajectory's turning points, keeping every sample needed to stay
+    /// within this many metres of the full path
+    #[arg(long)]
+    keyframes: Option<f32>,
+
+    /// Keyframe file (`.json` or `.csv`; default `<output>.keyframes.json`)
+    #[arg(long, requires = "keyframes")]
+    keyframes_output: Option<PathBuf>,
+
+    /// Also write one single-performance show per drone (`drone_NNN.json`) into this directory
+    #[arg(long)]
+    split_output: Option<PathBuf>,
+
+    /// With --split-output, skip the combined .vviz
+    #[arg(long, requires = "split_output")]
+    split_only: bool,
+
+    /// Split shows longer than this (`8m`, `480`) at moments every drone hovers, into
+    /// `show.part<N>.vviz` files that each start where the one before ended
+    #[arg(long, value_parser = parse_segment_length)]
+    segment: Option<f32>,
+
+    /// Convert an archive of several shows in folders (`ActOne/Drone 1.csv`, ...) into one
+    /// show playing them back to back, in archive order, instead of one .vviz per folder
+    #[arg(long)]
+    merge_acts: bool,
+
+    /// Snap positions to a grid of this size (`1cm`, `5mm`; meters without a unit) for
+    /// smaller .vviz files
+    #[arg(long, value_parser = quantize::parse_step)]
+    quantize: Option<f32>,
+
+    /// Encoding of the combined shows (`show.vviz`, `show.msgpack` with `vviz-msgpack`,
+    /// `show.pb` with `pb`); --split-output files stay JSON
+    #[arg(long, value_enum, default_value_t)]
+    format: output::ShowFormat,
+
+    /// Canonical output: byte-identical .vviz files for identical inputs
+    #[arg(long)]
+    reproducible: bool,
+
+    /// Record the tool version, input hash, transforms and conversion time in the show's metadata
+    #[arg(long)]
+    embed_provenance: bool,
+
+    /// SMPTE timecode of show time zero (`01:00:00:00`): records it in the show's metadata and
+    /// writes a `show.timecode.csv` mapping show time to timecode
+    #[arg(long)]
+    timecode_start: Option<timecode::Start>,
+
+    /// Timecode frame rate
+    #[arg(long, default_value_t = 30, requires = "timecode_start")]
+    fps: u32,
+
+    /// Write a checksum file (`show.vviz.sha256`) next to the output
+    #[arg(long, value_enum)]
+    checksum: Option<ChecksumAlgorithm>,
+
+    #[command(flatten)]
+    dialect: dialect::CsvDialect,
+
+    #[command(flatten)]
+    columns: columns::ColumnChoice,
+
+    /// List at most this many parse errors (all of them are still counted)
+    #[arg(long, default_value_t = 50)]
+    max_errors: usize,
+
+    /// Keep only every Nth sample of each drone (and its last), for a quick look at a huge
+    /// show; the output is marked as a preview
+    #[arg(long, value_parser = preview::parse_decimate)]
+    preview_decimate: Option<usize>,
+
+    /// Memory the built show may take (`2G`, `512M`) before its trajectories spill to temp
+    /// files; caps output buffering only, not the samples read. Shared between the inputs
+    /// converted at once
+    #[arg(long, value_parser = spill::parse_size)]
+    max_memory: Option<u64>,
+
+    /// Threads to use, both for inputs converted at once and for parsing each one (defaults
+    /// to one per core)
+    #[arg(short, long, value_parser = parse_jobs)]
+    jobs: Option<usize>,
+
+    /// Also write progress, warnings and the outcome to stderr as newline-delimited JSON
+    /// events, for frontends
+    #[arg(long)]
+    progress_json: bool,
+
+    /// Positions per second read from PX4 `.ulg` logs
+    #[arg(long, default_value_t = 4.0)]
+    ulog_rate: f32,
+
+    /// Skip unparsable rows and drones without samples, and hold single-sample drones in
+    /// place, instead of failing
+    #[arg(long)]
+    lenient: bool,
+
+    /// How to handle entries numbered as the same drone (`Drone 12.csv` and `Drone 012.csv`)
+    #[arg(long, value_enum, default_value_t)]
+    on_duplicate: continuity::OnDuplicate,
+
+    /// Warn unless the input holds exactly this many drones
+    #[arg(long)]
+    expect_drones: Option<usize>,
+
+    /// Convert, validate and report as usual, but write no files
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Before overwriting an existing show, move it to `<show>.bak.<UTC timestamp>`
+    #[arg(long)]
+    backup: bool,
+
+    /// Number the show's drones 0 to N-1 in drone order, for viewers and launch software that
+    /// need contiguous ids, writing the old and new ids to `<show>.renumber.csv`
+    #[arg(long)]
+    renumber: bool,
+
+    /// Write per-drone flight time and estimated energy (Wh) to this CSV
+    #[arg(long)]
+    energy_report: Option<PathBuf>,
+
+    #[command(flatten)]
+    energy_model: energy::EnergyModel,
+
+    /// Validate against a drone hardware profile; explicit limits take precedence
+    #[arg(long, value_enum)]
+    profile: Option<validate::Profile>,
+
+    #[command(flatten)]
+    limits: validate::Limits
+}
+
+/// Whether `--progress-json` is on, so that errors have already gone out as events.
+pub fn progress_json() -> bool {
+    progress::enabled()
+}
+
+/// Runs the parsed command line.
+pub fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Some(Command::Convert(args)) => convert(*args).map(|_| ()),
+        Some(Command::Align(args)) => align::align(args),
+        Some(Command::Validate(args)) => validate::run(args),
+        Some(Command::Generate(args)) => generate::generate(args),
+        Some(Command::Export(args)) => export::run(args),
+        Some(Command::Play(args)) => play::run(args),
+        Some(Command::Fix(args)) => fix::run(args),
+        Some(Command::Lint(args)) => lint::run(args),
+        Some(Command::Inspect(args)) => inspect::run(args),
+        Some(Command::Migrate(args)) => migrate::run(args),
+        Some(Command::MergeLights(args)) => merge_lights::run(args),
+        Some(Command::Resample(args)) => resample::run(args),
+        Some(Command::Transform(args)) => transform::run(args),
+        Some(Command::Extract(args)) => extract::run(args),
+        Some(Command::Verify(args)) => verify::run(*args),
+        Some(Command::Compare(args)) => compare::run(args),
+        Some(Command::Swap(args)) => swap::run(args),
+        Some(Command::Reassign(args)) => reassign::run(args),
+        None => convert(cli.convert).map(|_| ())
+    }
+}
+
+/// Runs `convert` on the inputs `0..count`, `concurrent` of them at a time, returning the
+/// results in input order up to the first failure. No input is started after one fails.
+fn convert_concurrently<T: Send>(count: usize, concurrent: usize, convert: impl Fn(usize) -> Result<T> + Sync) -> Vec<Result<T>> {
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results: Mutex<Vec<Option<Result<T>>>> = Mutex::new((0..count).map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..concurrent {
+            scope.spawn(|| {
+                while !failed.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= count {
+                        break;
+                    }
+                    let result = convert(index);
+                    if result.is_err() {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+    let mut results: Vec<_> = results.into_inner().unwrap().into_iter().map_while(|result| result).collect();
+    if let Some(failure) = results.iter().position(|result| result.is_err()) {
+        results.truncate(failure + 1);
+    }
+    results
+}
+
+/// `show.vviz`, or `show_2.vviz`, `show_3.vviz`, ... when an earlier input of the same batch
+/// already used that name.
+fn unique_output(path: PathBuf, used: &HashSet<PathBuf>) -> PathBuf {
+    if !used.contains(&path) {
+        return path;
+    }
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default().to_string();
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default().to_string();
+    (2..).map(|n| path.with_file_name(format!("{}_{}.{}", stem, n, extension)))
+        .find(|candidate| !used.contains(candidate))
+        .unwrap()
+}
+
+/// Moves an existing file at `path` aside to `path.bak.<UTC timestamp>` (with `.2`, `.3`, ...
+/// after it when a backup from the same second exists).
+fn backup(path: &std::path::Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let stamp: String = provenance::rfc3339(std::time::SystemTime::now()).chars().filter(|c| !matches!(c, '-' | ':')).collect();
+    let name = format!("{}.bak.{}", path.file_name().unwrap().to_string_lossy(), stamp);
+    let target = (1..).map(|n| path.with_file_name(if n == 1 { name.clone() } else { format!("{}.{}", name, n) }))
+        .find(|candidate| !candidate.exists())
+        .unwrap();
+    std::fs::rename(path, &target).map_err(error::io("Failed to back up the previous show"))?;
+    println!("Moved the previous {} to {}", path.display(), target.display());
+    Ok(())
+}
+
+/// Human-readable list of the conversion settings that change the output, for provenance.
+fn describe_transforms(args: &ConvertArgs) -> Vec<String> {
+    let mut transforms = vec![];
+    if args.preset != Preset::default() {
+        transforms.push(format!("preset {}", args.preset.to_possible_value().unwrap().get_name()));
+    }
+    if let Some(rotate) = &args.rotate {
+        transforms.push(format!("rotate {} {} {}", rotate.x, rotate.y, rotate.z));
+    }
+    if let Some(bearing) = args.align_heading {
+        transforms.push(format!("turn front from {}° to {}°", args.front, bearing));
+    }
+    if let Some(translate) = &args.translate {
+        transforms.push(format!("translate {} {} {}", translate.x, translate.y, translate.z));
+    }
+    if args.alt_datum != datum::AltDatum::Agl {
+        transforms.push(format!("altitude datum {}", args.alt_datum));
+    }
+    if args.alt_offset != 0.0 {
+        transforms.push(format!("altitude offset {} m", args.alt_offset));
+    }
+    let files = [
+        ("config", &args.config),
+        ("color manifest", &args.color_manifest),
+        ("lights", &args.lights),
+        ("payload events", &args.payload_events),
+        ("events", &args.events),
+        ("time shift", &args.time_shift),
+        ("script", &args.script)
+    ];
+    for (name, file) in files {
+        if let Some(file) = file {
+            transforms.push(format!("{} {}", name, file.display()));
+        }
+    }
+    if let Some(mode) = &args.time_align {
+        transforms.push(format!("time align {}", mode));
+    }
+    if let Some(every) = args.preview_decimate {
+        transforms.push(format!("preview keeping 1 in {} samples", every));
+    }
+    if let Some(max_speed) = args.clamp_speed {
+        transforms.push(format!("clamp speed {} m/s", max_speed));
+    }
+    if let Some(max_accel) = args.limit_accel {
+        transforms.push(format!("limit acceleration {} m/s²", max_accel));
+    }
+    if let Some(repair) = args.clamp_ground {
+        transforms.push(format!("{} below-ground samples", repair.to_possible_value().unwrap().get_name()));
+    }
+    if let Some(target) = args.fit_box {
+        transforms.push(format!("fit to {}", target));
+    }
+    if let (Some(crop), crop::CropMode::Drop) = (args.crop, args.crop_mode) {
+        transforms.push(format!("crop to {}", crop));
+    }
+    if let Some(stagger) = args.land_stagger {
+        transforms.push(format!("land by {} after a {} s hover at {} m/s", stagger, args.land_hover, args.land_speed));
+    }
+    if args.curve == curve::Curve::Bezier {
+        transforms.push(format!("bezier curves within {} m", args.curve_tolerance));
+    }
+    if let Some(step) = args.quantize {
+        transforms.push(format!("quantize {} m", step));
+    }
+    if let Some(length) = args.countdown {
+        transforms.push(format!("{} s {} countdown", length, args.countdown_pattern.to_possible_value().unwrap().get_name()));
+    }
+    if args.renumber {
+        transforms.push("renumber drones contiguously".to_string());
+    }
+    if let Some(length) = args.segment {
+        transforms.push(format!("segments of at most {} s", length));
+    }
+    transforms
+}
+
+fn parse_countdown(s: &str) -> std::result::Result<f32, String> {
+    timing::parse_duration(s).and_then(|length| if length > 0.0 { Ok(length) } else { Err("a countdown must last longer than 0 s".to_string()) })
+}
+
+fn parse_color(s: &str) -> std::result::Result<lights::Rgb, String> {
+    s.parse().map_err(|e: lights::ParseColorError| e.error)
+}
+
+fn parse_segment_length(s: &str) -> std::result::Result<f32, String> {
+    timing::parse_duration(s).and_then(|length| if length > 0.0 { Ok(length) } else { Err("a segment must last longer than 0 s".to_string()) })
+}
+
+fn parse_jobs(s: &str) -> std::result::Result<usize, String> {
+    s.parse::<usize>().ok().filter(|jobs| *jobs > 0).ok_or_else(|| format!("expected at least one job, got '{}'", s))
+}
+
+/// Converts every input, returning the paths of the combined shows written.
+fn convert(args: ConvertArgs) -> Result<Vec<PathBuf>> {
+    if args.progress_json {
+        progress::enable(args.filenames.len());
+    }
+    let result = convert_all(args);
+    progress::finish(&result);
+    result
+}
+
+fn convert_all(args: ConvertArgs) -> Result<Vec<PathBuf>> {
+    let options = ConvertOptions::from_args(&args)?;
+
+    if let Some(out_dir) = args.out_dir.as_ref().filter(|_| !args.dry_run) {
+        std::fs::create_dir_all(out_dir).map_err(error::io("Failed to create output directory"))?;
+    }
+
+    // Outputs are named up front, in input order, so that concurrent conversions can't race
+    // for a name.
+    let mut outputs: HashSet<PathBuf> = HashSet::new();
+    let mut new_files = vec![];
+    for filename in &args.filenames {
+        let source_name = if fetch::is_url(filename) { PathBuf::from(fetch::file_name(filename)) } else { PathBuf::from(filename) };
+        let mut new_file = archive::output_path(&source_name).with_extension(options.format.writer().extension());
+        if let Some(out_dir) = &args.out_dir {
+            new_file = out_dir.join(new_file.file_name().unwrap());
+        }
+        new_file = unique_output(new_file, &outputs);
+        outputs.insert(new_file.clone());
+        new_files.push(new_file);
+    }
+
+    // --jobs is shared between the inputs converted at once and the parsers of each.
+    let jobs = args.jobs.unwrap_or_else(pipeline::cores);
+    let concurrent = jobs.min(args.filenames.len()).max(1);
+    pipeline::set_workers(jobs / concurrent);
+    let mut options = options;
+    options.max_memory = options.max_memory.map(|limit| limit / concurrent as u64);
+
+    let convert_one = |index: usize| {
+        let filename = &args.filenames[index];
+        // Downloads are converted from a temp file, with the output named after the URL and
+        // written to the current directory unless --out-dir is given.
+        let (fname, downloaded) = if fetch::is_url(filename) {
+            (fetch::download(filename)?, true)
+        } else {
+            (PathBuf::from(filename), false)
+        };
+        progress::input(index, filename, archive::count_entries(&fname));
+
+        let result = match input::recognized(archive::Input::Path(&fname), &options) {
+            true => csv2vviz(filename, fname.clone(), new_files[index].clone(), &options),
+            false => Err(Error::Format(format!("Invalid file format: {}", filename)))
+        };
+        if downloaded {
+            fetch::cleanup(&fname);
+        }
+        result
+    };
+    let results = if concurrent == 1 {
+        // Stop at the first input that fails.
+        let mut results = vec![];
+        for index in 0..args.filenames.len() {
+            let result = convert_one(index);
+            let failed = result.is_err();
+            results.push(result);
+            if failed {
+                break;
+            }
+        }
+        results
+    } else {
+        convert_concurrently(args.filenames.len(), concurrent, convert_one)
+    };
+
+    let mut written = vec![];
+    let mut problems = 0;
+    for result in results {
+        let (shows, show_problems) = result?;
+        written.extend(shows);
+        problems += show_problems;
+    }
+
+    if args.dry_run {
+        println!("Dry run: nothing was written");
+    }
+    if problems > 0 {
+        return Err(Error::Validation(format!("Validation found {} problems", problems)));
+    }
+    Ok(written)
+}
@@ -0,0 +1,214 @@
+use std::{collections::HashMap, path::Path, str::FromStr};
+
+use regex::Regex;
+
+use crate::{error::{self, Error, Result}, field, ColorAction, DRONE_NAME_PATTERN};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseColorError {
+    pub error: String
+}
+
+impl FromStr for Rgb {
+    type Err = ParseColorError;
+
+    /// Accepts either `#RRGGBB` or three space-separated channel values (`255 0 128`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(ParseColorError { error: format!("invalid hex color '{}'", s) });
+            }
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| ParseColorError { error: format!("invalid hex color '{}'", s) });
+            return Ok(Rgb { r: channel(0)?, g: channel(2)?, b: channel(4)? });
+        }
+        let channels: Vec<&str> = s.split_whitespace().collect();
+        if channels.len() != 3 {
+            return Err(ParseColorError { error: format!("invalid color '{}'", s) });
+        }
+        let channel = |c: &str| c.parse::<u8>()
+            .map_err(|_| ParseColorError { error: format!("invalid color channel '{}'", c) });
+        Ok(Rgb { r: channel(channels[0])?, g: channel(channels[1])?, b: channel(channels[2])? })
+    }
+}
+
+impl Rgb {
+    pub fn lerp(self, other: Rgb, t: f32) -> Rgb {
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Rgb { r: mix(self.r, other.r), g: mix(self.g, other.g), b: mix(self.b, other.b) }
+    }
+}
+
+/// A per-drone light program: a single color held for the whole show, or a gradient whose
+/// stops are spread evenly across the drone's timeline (`#ff0000>#0000ff`).
+#[derive(Debug, Clone)]
+pub enum ColorSpec {
+    Solid(Rgb),
+    Gradient(Vec<Rgb>)
+}
+
+impl FromStr for ColorSpec {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let stops = s.split('>').map(Rgb::from_str).collect::<Result<Vec<Rgb>, _>>()?;
+        if stops.len() == 1 {
+            Ok(ColorSpec::Solid(stops[0]))
+        } else {
+            Ok(ColorSpec::Gradient(stops))
+        }
+    }
+}
+
+impl ColorSpec {
+
+    fn color_at(&self, t: f32) -> Rgb {
+        match self {
+            ColorSpec::Solid(color) => *color,
+            ColorSpec::Gradient(stops) => {
+                let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+                let index = (position.floor() as usize).min(stops.len() - 2);
+                stops[index].lerp(stops[index + 1], position - index as f32)
+            }
+        }
+    }
+
+    /// Renders the program as color actions covering `frames` color frames. Consecutive
+    /// frames of the same color are merged into a single action.
+    pub fn actions(&self, frames: u32) -> Vec<ColorAction> {
+        let frames = frames.max(1);
+        let mut actions: Vec<ColorAction> = vec![];
+        for frame in 0..frames {
+            let t = if frames > 1 { frame as f32 / (frames - 1) as f32 } else { 0.0 };
+            push_frame(&mut actions, self.color_at(t));
+        }
+        actions
+    }
+}
+
+/// Appends one frame of `color`, extending the previous action when the color is unchanged.
+pub fn push_frame(actions: &mut Vec<ColorAction>, color: Rgb) {
+    match actions.last_mut() {
+        Some(last) if (last.r, last.g, last.b) == (color.r, color.g, color.b) => {
+            last.frames = Some(last.frames.unwrap_or(1) + 1);
+        }
+        _ => actions.push(ColorAction { r: color.r, g: color.g, b: color.b, frames: Some(1) })
+    }
+}
+
+/// Drone number (as in the `Drone N` entry names) to light program.
+#[derive(Debug, Default)]
+pub struct ColorManifest(HashMap<usize, ColorSpec>);
+
+impl ColorManifest {
+
+    /// Reads a two-column CSV (`id,color`) with a header row.
+    pub fn load(fname: &Path) -> Result<Self> {
+        let mut reader = csv::Reader::from_path(fname)
+            .map_err(error::csv("Failed to open color manifest"))?;
+        let mut manifest = ColorManifest::default();
+        for record in reader.records() {
+            let record = record.map_err(error::csv("Failed to read color manifest"))?;
+            let file = fname.display().to_string();
+            let id = field::cell(&record, 0, "id", &file)?;
+            let id = field::parse::<usize>(id)
+                .map_err(|_| Error::Parse(format!("Invalid drone id '{}' in color manifest.", id)))?;
+            let spec = field::parse::<ColorSpec>(field::cell(&record, 1, "color", &file)?)
+                .map_err(|e| Error::Parse(format!("Invalid color for drone {}: {}", id, e.error)))?;
+            manifest.0.insert(id, spec);
+        }
+        Ok(manifest)
+    }
+
+    pub fn get(&self, drone_id: usize) -> Option<&ColorSpec> {
+        self.0.get(&drone_id)
+    }
+}
+
+/// Light samples read from a separate lights archive, keyed by drone number. Each entry is a
+/// `Drone N` CSV of `t, r, g, b` rows with `t` in milliseconds on the same clock as the
+/// trajectory files.
+#[derive(Debug, Default)]
+pub struct LightTracks(HashMap<usize, Vec<(f64, Rgb)>>);
+
+impl LightTracks {
+
+    pub fn load(fname: &Path) -> Result<Self> {
+        let name_re = Regex::new(DRONE_NAME_PATTERN).unwrap();
+
+        let mut tracks = LightTracks::default();
+        crate::archive::for_each_entry(fname, |name, file| {
+            let Some(drone_id) = name_re.captures(crate::archive::basename(name))
+                .and_then(|captures| captures[1].parse::<usize>().ok()) else {
+                return Ok(());
+            };
+
+            let mut csv_reader = csv::Reader::from_reader(file);
+            let mut samples: Vec<(f64, Rgb)> = csv_reader.records().map(|record| {
+                let record = record.map_err(error::csv("Failed to read lights archive"))?;
+                let channel = |i: usize, column: &str| {
+                    let cell = field::cell(&record, i, column, name)?;
+                    field::parse::<u8>(cell)
+                        .map_err(|_| Error::Parse(format!("Invalid color channel '{}' for drone {}.", cell, drone_id)))
+                };
+                let time = field::cell(&record, 0, "t", name)?;
+                Ok((
+                    field::parse::<f64>(time)
+                        .map_err(|_| Error::Parse(format!("Invalid light sample time '{}' for drone {}.", time, drone_id)))?,
+                    Rgb { r: channel(1, "r")?, g: channel(2, "g")?, b: channel(3, "b")? }
+                ))
+            }).collect::<Result<_>>()?;
+            samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            tracks.0.insert(drone_id, samples);
+            Ok(())
+        })?;
+        Ok(tracks)
+    }
+
+    /// Resamples the drone's light track onto `frames` color frames at `rate` frames per
+    /// second starting at `start` (ms). Each frame takes the most recent sample at or before
+    /// its time; frames before the first sample take the first sample's color.
+    pub fn actions(&self, drone_id: usize, start: f64, frames: u32, rate: f32) -> Option<Vec<ColorAction>> {
+        let samples = self.0.get(&drone_id).filter(|samples| !samples.is_empty())?;
+        let mut actions: Vec<ColorAction> = vec![];
+        let mut current = 0;
+        for frame in 0..frames.max(1) {
+            let time = start + frame as f64 * 1000.0 / rate as f64;
+            while current + 1 < samples.len() && samples[current + 1].0 <= time {
+                current += 1;
+            }
+            push_frame(&mut actions, samples[current].1);
+        }
+        Some(actions)
+    }
+
+    pub fn drone_ids(&self) -> impl Iterator<Item = &usize> {
+        self.0.keys()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rgb;
+
+    #[test]
+    fn parses_hex_and_channel_colors() {
+        assert_eq!("#ff0080".parse(), Ok(Rgb { r: 255, g: 0, b: 128 }));
+        assert_eq!(" 255 0 128 ".parse(), Ok(Rgb { r: 255, g: 0, b: 128 }));
+    }
+
+    #[test]
+    fn rejects_anything_but_hex_digits() {
+        assert!("#aébcd".parse::<Rgb>().is_err());
+        assert!("#+f+f+f".parse::<Rgb>().is_err());
+    }
+}
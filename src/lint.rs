@@ -0,0 +1,482 @@
+use std::{collections::HashMap, fmt, path::PathBuf};
+
+use clap::Args;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{archive, columns, config, continuity, dialect, encoding, error::{self, Error, Result}, track::{distance, Track}, wind::Wind, DRONE_NAME_PATTERN};
+
+#[derive(Args, Debug)]
+pub struct LintArgs {
+
+    /// Archive, directory of drone CSVs or single drone CSV to check
+    input: PathBuf,
+
+    /// TOML file turning rules on and off and setting their severities and limits
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Forecast wind, `6m/s@240deg` (speed, and the bearing it blows from): also runs the
+    /// separation and geofence rules with drones drifting downwind for --latency
+    #[arg(long)]
+    wind: Option<Wind>,
+
+    /// Control latency (s): how long a drone drifts in the wind before correcting
+    #[arg(long, default_value_t = 0.5, requires = "wind")]
+    latency: f32,
+
+    #[command(flatten)]
+    dialect: dialect::CsvDialect,
+
+    #[command(flatten)]
+    columns: columns::ColumnChoice
+}
+
+/// How a rule's findings are reported. Errors fail the lint; warnings are only printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Off,
+    Warning,
+    Error
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Off => "Off",
+            Severity::Warning => "Warning",
+            Severity::Error => "Error"
+        })
+    }
+}
+
+/// Entries are named `Drone N.csv`, numbered once each and without gaps.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Naming {
+    severity: Severity
+}
+
+impl Default for Naming {
+    fn default() -> Self {
+        Naming { severity: Severity::Error }
+    }
+}
+
+/// Every row is later than the one before it.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MonotonicTime {
+    severity: Severity
+}
+
+impl Default for MonotonicTime {
+    fn default() -> Self {
+        MonotonicTime { severity: Severity::Error }
+    }
+}
+
+/// Every drone is sampled at the show's typical spacing, within `tolerance` (relative).
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Rate {
+    severity: Severity,
+    tolerance: f32
+}
+
+impl Default for Rate {
+    fn default() -> Self {
+        Rate { severity: Severity::Warning, tolerance: 0.05 }
+    }
+}
+
+/// No two drones come closer than `min_distance` meters.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Separation {
+    severity: Severity,
+    min_distance: f32
+}
+
+impl Default for Separation {
+    fn default() -> Self {
+        Separation { severity: Severity::Error, min_distance: 1.0 }
+    }
+}
+
+/// Every sample lies inside the box from `min` to `max` (east, north, up meters). Not
+/// checked until the box is given.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Geofence {
+    severity: Severity,
+    min: Option<[f32; 3]>,
+    max: Option<[f32; 3]>
+}
+
+impl Default for Geofence {
+    fn default() -> Self {
+        Geofence { severity: Severity::Error, min: None, max: None }
+    }
+}
+
+/// The takeoff transit (until a drone first enters the show volume `show_min`–`show_max`)
+/// and the return (after it last leaves it) stay inside the corridor `corridor_min`–
+/// `corridor_max`, and at least `min_height` meters up over the audience buffer
+/// `audience_min`–`audience_max` (east, north). Boxes are east, north, up meters. Not checked
+/// until the show volume and a corridor or a buffer are given.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Transit {
+    severity: Severity,
+    show_min: Option<[f32; 3]>,
+    show_max: Option<[f32; 3]>,
+    corridor_min: Option<[f32; 3]>,
+    corridor_max: Option<[f32; 3]>,
+    audience_min: Option<[f32; 2]>,
+    audience_max: Option<[f32; 2]>,
+    min_height: f32
+}
+
+impl Default for Transit {
+    fn default() -> Self {
+        Transit {
+            severity: Severity::Error, show_min: None, show_max: None, corridor_min: None, corridor_max: None,
+            audience_min: None, audience_max: None, min_height: 0.0
+        }
+    }
+}
+
+fn inside<const N: usize>(position: &[f32], min: [f32; N], max: [f32; N]) -> bool {
+    (0..N).all(|i| position[i] >= min[i] && position[i] <= max[i])
+}
+
+/// No drone flies faster than `max` m/s between two samples.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Speed {
+    severity: Severity,
+    max: f32
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed { severity: Severity::Warning, max: 10.0 }
+    }
+}
+
+/// The `[rules]` of a `--config` file; a rule left out keeps its default.
+///
+/// ```toml
+/// [rules.separation]
+/// severity = "error"
+/// min_distance = 1.5
+///
+/// [rules.rate]
+/// severity = "off"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rules {
+    naming: Naming,
+    monotonic_time: MonotonicTime,
+    rate: Rate,
+    separation: Separation,
+    geofence: Geofence,
+    transit: Transit,
+    speed: Speed
+}
+
+/// Findings printed so far, by severity.
+#[derive(Default)]
+struct Report {
+    errors: usize,
+    warnings: usize
+}
+
+impl Report {
+    fn add(&mut self, severity: Severity, rule: &str, message: String) {
+        match severity {
+            Severity::Off => return,
+            Severity::Warning => self.warnings += 1,
+            Severity::Error => self.errors += 1
+        }
+        println!("{}: {} [{}]", severity, message, rule);
+    }
+}
+
+/// Samples of one drone CSV (seconds; east, north, up meters), with rows that don't move
+/// time forward left out.
+struct Entry {
+    drone_id: usize,
+    track: Track
+}
+
+/// Runs every enabled rule over the drone CSVs of the input, failing when any rule set to `error` finds something.
+pub fn run(args: LintArgs) -> Result<()> {
+    let config = match &args.config {
+        Some(fname) => config::Config::load(fname)?,
+        None => config::Config::default()
+    };
+    let rules = config.rules;
+    let mut report = Report::default();
+    let name_re = Regex::new(DRONE_NAME_PATTERN).unwrap();
+
+    let mut entries: Vec<Entry> = vec![];
+    let mut names: HashMap<usize, String> = HashMap::new();
+    // Tracks are in seconds from the input's first sample, as absolute (epoch) times in
+    // milliseconds don't fit an f32.
+    let mut origin: Option<f64> = None;
+    archive::for_each_entry(&args.input, |name, file| {
+        let basename = archive::basename(name);
+        if !basename.to_ascii_lowercase().ends_with(".csv") || name.starts_with("__MACOSX") {
+            return Ok(());
+        }
+        let Some(drone_id) = name_re.captures(basename).and_then(|captures| captures[1].parse::<usize>().ok()) else {
+            report.add(rules.naming.severity, "naming", format!("{} is not named 'Drone <number>' and would be skipped", name));
+            return Ok(());
+        };
+        if let Some(other) = names.insert(drone_id, name.to_string()) {
+            report.add(rules.naming.severity, "naming", format!("{} and {} are both drone {}", other, name, drone_id));
+        }
+
+        // Columns and units are found as `convert` finds them, so the same values are checked.
+        let mut reader = args.dialect.reader(encoding::Decoder::new(file));
+        let headers = reader.headers().ok().cloned();
+        let mut rows: columns::Rows = Box::new(reader.records());
+        let columns = match args.columns.choose(headers.as_ref(), &mut rows) {
+            columns::Chosen::Known(columns) | columns::Chosen::Inferred(columns) => columns,
+            columns::Chosen::Ambiguous(guess) => return Err(Error::Parse(format!(
+                "{}: the column layout is ambiguous (best guess {}); pass --columns, or --yes to use the guess",
+                name, guess
+            ))),
+            columns::Chosen::Unknown => columns::Columns::default()
+        };
+        let units = headers.as_ref().map(|headers| columns::Units::from_headers(headers, &columns).0);
+
+        let mut samples: Vec<(f32, [f32; 3])> = vec![];
+        let mut last: Option<f64> = None;
+        let (mut backwards, mut first_line) = (0, None);
+        for record in rows {
+            let record = record.map_err(error::csv(&format!("Failed to read {}", name)))?;
+            let sample = columns.sample(&record, name)?;
+            let sample = units.as_ref().map_or(sample, |units| units.apply(sample));
+            if last.is_some_and(|last| sample.t <= last) {
+                backwards += 1;
+                first_line.get_or_insert(record.position().map_or(0, |position| position.line()));
+                continue;
+            }
+            last = Some(sample.t);
+            let t = ((sample.t - *origin.get_or_insert(sample.t)) / 1000.0) as f32;
+            samples.push((t, sample.pos));
+        }
+        if let Some(line) = first_line {
+            report.add(rules.monotonic_time.severity, "monotonic_time", format!(
+                "{}: time repeats or goes back in {} rows, first at line {}", name, backwards, line
+            ));
+        }
+        if !samples.is_empty() {
+            entries.push(Entry { drone_id, track: Track { samples } });
+        }
+        Ok(())
+    })?;
+    entries.sort_by_key(|entry| entry.drone_id);
+
+    let mut ids: Vec<usize> = names.keys().copied().collect();
+    ids.sort_unstable();
+    let missing: Vec<usize> = (1..=ids.last().copied().unwrap_or(0)).filter(|id| ids.binary_search(id).is_err()).collect();
+    if !missing.is_empty() {
+        report.add(rules.naming.severity, "naming", format!("drone {} missing", continuity::ranges(&missing)));
+    }
+
+    let spacings: Vec<(usize, f32)> = entries.iter()
+        .filter_map(|entry| median_spacing(&entry.track).map(|spacing| (entry.drone_id, spacing)))
+        .collect();
+    let mut typical: Vec<f32> = spacings.iter().map(|(_, spacing)| *spacing).collect();
+    typical.sort_by(f32::total_cmp);
+    let typical = typical.get(typical.len() / 2).copied();
+
+    if let Some(typical) = typical.filter(|_| rules.rate.severity != Severity::Off) {
+        for (drone_id, spacing) in &spacings {
+            if ((spacing - typical) / typical).abs() > rules.rate.tolerance {
+                report.add(rules.rate.severity, "rate", format!(
+                    "drone {} is sampled every {:.3} s, the rest of the show every {:.3} s", drone_id, spacing, typical
+                ));
+            }
+        }
+    }
+
+    if rules.speed.severity != Severity::Off {
+        for entry in &entries {
+            let speeds: Vec<f32> = entry.track.samples.windows(2)
+                .map(|w| distance(w[0].1, w[1].1) / (w[1].0 - w[0].0))
+                .filter(|speed| *speed > rules.speed.max)
+                .collect();
+            if !speeds.is_empty() {
+                report.add(rules.speed.severity, "speed", format!(
+                    "drone {} exceeds {} m/s in {} segments (max {:.2} m/s)",
+                    entry.drone_id, rules.speed.max, speeds.len(), speeds.iter().copied().fold(0.0, f32::max)
+                ));
+            }
+        }
+    }
+
+    if let (Some(min), Some(max)) = (rules.geofence.min, rules.geofence.max) {
+        for entry in &entries {
+            let outside = outside_geofence(&entry.track, min, max, [0.0; 3]);
+            if let Some(t) = outside.first() {
+                report.add(rules.geofence.severity, "geofence", format!(
+                    "drone {} leaves the geofence at {:.3} s ({} samples outside)", entry.drone_id, t, outside.len()
+                ));
+            }
+        }
+    }
+
+    if let (Some(show_min), Some(show_max)) = (rules.transit.show_min, rules.transit.show_max) {
+        let transit = &rules.transit;
+        for entry in &entries {
+            let samples = &entry.track.samples;
+            let Some(enter) = samples.iter().position(|(_, position)| inside(position, show_min, show_max)) else {
+                report.add(transit.severity, "transit", format!("drone {} never enters the show volume", entry.drone_id));
+                continue;
+            };
+            let leave = samples.iter().rposition(|(_, position)| inside(position, show_min, show_max)).unwrap();
+            for (phase, legs) in [("takeoff", &samples[..enter]), ("return", &samples[leave + 1..])] {
+                if let (Some(min), Some(max)) = (transit.corridor_min, transit.corridor_max) {
+                    let outside: Vec<f32> = legs.iter().filter(|(_, position)| !inside(position, min, max)).map(|(t, _)| *t).collect();
+                    if let Some(t) = outside.first() {
+                        report.add(transit.severity, "transit", format!(
+                            "drone {} leaves the corridor during {} at {:.3} s ({} samples outside)", entry.drone_id, phase, t, outside.len()
+                        ));
+                    }
+                }
+                if let (Some(min), Some(max)) = (transit.audience_min, transit.audience_max) {
+                    let low = legs.iter()
+                        .filter(|(_, position)| inside(position, min, max) && position[2] < transit.min_height)
+                        .min_by(|a, b| a.1[2].total_cmp(&b.1[2]));
+                    if let Some((t, position)) = low {
+                        report.add(transit.severity, "transit", format!(
+                            "drone {} is {:.1} m up over the audience during {} at {:.3} s (minimum {} m)",
+                            entry.drone_id, position[2], phase, t, transit.min_height
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut calm = vec![];
+    if rules.separation.severity != Severity::Off {
+        calm = close_pairs(&entries, typical.unwrap_or(0.25), rules.separation.min_distance, [0.0; 3]);
+        for ((a, b), (d, t)) in &calm {
+            report.add(rules.separation.severity, "separation", format!(
+                "drones {} and {} come within {:.3} m at {:.3} s (minimum {} m)", a, b, d, t, rules.separation.min_distance
+            ));
+        }
+    }
+
+    // Whatever still holds in calm air but not once the wind pushes drones off their
+    // trajectories is margin the forecast takes away.
+    if let Some(wind) = args.wind {
+        let drift = wind.drift(args.latency);
+        let mut times = vec![];
+        if rules.separation.severity != Severity::Off {
+            for ((a, b), (d, t)) in close_pairs(&entries, typical.unwrap_or(0.25), rules.separation.min_distance, drift) {
+                if calm.iter().all(|(pair, _)| *pair != (a, b)) {
+                    report.add(rules.separation.severity, "wind", format!(
+                        "drones {} and {} come within {:.3} m at {:.3} s in the wind (minimum {} m)", a, b, d, t, rules.separation.min_distance
+                    ));
+                    times.push(t);
+                }
+            }
+        }
+        if let (Some(min), Some(max)) = (rules.geofence.min, rules.geofence.max) {
+            for entry in entries.iter().filter(|entry| outside_geofence(&entry.track, min, max, [0.0; 3]).is_empty()) {
+                let outside = outside_geofence(&entry.track, min, max, drift);
+                if let Some(t) = outside.first() {
+                    report.add(rules.geofence.severity, "wind", format!(
+                        "drone {} drifts out of the geofence at {:.3} s in the wind ({} samples outside)", entry.drone_id, t, outside.len()
+                    ));
+                    times.push(*t);
+                }
+            }
+        }
+        let drift = distance(drift, [0.0; 3]);
+        match (times.iter().copied().reduce(f32::min), times.iter().copied().reduce(f32::max)) {
+            (Some(first), Some(last)) => println!(
+                "Wind {} drifts drones {:.2} m in {} s: {} checks lose their margin between {:.3} s and {:.3} s",
+                wind, drift, args.latency, times.len(), first, last
+            ),
+            _ => println!("Wind {} drifts drones {:.2} m in {} s: no check loses its margin", wind, drift, args.latency)
+        }
+    }
+
+    if report.errors > 0 {
+        return Err(Error::Validation(format!(
+            "{} failed lint with {} errors and {} warnings", args.input.display(), report.errors, report.warnings
+        )));
+    }
+    match report.warnings {
+        0 => println!("{} drones OK", entries.len()),
+        n => println!("{} drones checked with {} warnings", entries.len(), n)
+    }
+    Ok(())
+}
+
+fn median_spacing(track: &Track) -> Option<f32> {
+    let mut spacings: Vec<f32> = track.samples.windows(2).map(|w| w[1].0 - w[0].0).collect();
+    spacings.sort_by(f32::total_cmp);
+    spacings.get(spacings.len() / 2).copied()
+}
+
+/// Times of the track's samples outside the `min`–`max` box once moved by `shift`.
+fn outside_geofence(track: &Track, min: [f32; 3], max: [f32; 3], shift: [f32; 3]) -> Vec<f32> {
+    track.samples.iter()
+        .map(|(t, position)| (*t, [0, 1, 2].map(|i| position[i] + shift[i])))
+        .filter(|(_, position)| (0..3).any(|i| position[i] < min[i] || position[i] > max[i]))
+        .map(|(t, _)| t)
+        .collect()
+}
+
+/// Every pair of drones closer than `min_distance` at some instant `step` apart, with their
+/// closest distance and when it happens, in drone order. Pairs are found by a sweep along
+/// east. With a `drift`, one drone of each pair is moved by it (either way), as when only one
+/// of them is caught by the wind.
+fn close_pairs(entries: &[Entry], step: f32, min_distance: f32, drift: [f32; 3]) -> Vec<((usize, usize), (f32, f32))> {
+    let reach = min_distance + distance(drift, [0.0; 3]);
+    let start = entries.iter().map(|entry| entry.track.samples[0].0).fold(f32::INFINITY, f32::min);
+    let end = entries.iter().map(|entry| entry.track.duration()).fold(f32::NEG_INFINITY, f32::max);
+    let mut closest: HashMap<(usize, usize), (f32, f32)> = HashMap::new();
+    let mut frame = 0;
+    loop {
+        let t = start + frame as f32 * step;
+        if t > end + step / 2.0 {
+            break;
+        }
+        let mut points: Vec<(usize, [f32; 3])> = entries.iter().map(|entry| (entry.drone_id, entry.track.position_at(t))).collect();
+        points.sort_by(|a, b| a.1[0].total_cmp(&b.1[0]));
+        for i in 0..points.len() {
+            for j in i + 1..points.len() {
+                if points[j].1[0] - points[i].1[0] >= reach {
+                    break;
+                }
+                let (a, b) = (points[i].1, points[j].1);
+                let d = distance(a, b)
+                    .min(distance([a[0] + drift[0], a[1] + drift[1], a[2] + drift[2]], b))
+                    .min(distance(a, [b[0] + drift[0], b[1] + drift[1], b[2] + drift[2]]));
+                if d < min_distance && points[i].0 != points[j].0 {
+                    let pair = (points[i].0.min(points[j].0), points[i].0.max(points[j].0));
+                    let best = closest.entry(pair).or_insert((d, t));
+                    if d < best.0 {
+                        *best = (d, t);
+                    }
+                }
+            }
+        }
+        frame += 1;
+    }
+    let mut pairs: Vec<_> = closest.into_iter().collect();
+    pairs.sort_by_key(|(pair, _)| *pair);
+    pairs
+}
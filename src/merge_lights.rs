@@ -0,0 +1,70 @@
+//! `csv2vviz merge-lights`: a new light program for a converted show, trajectories untouched.
+
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::{error::Result, lights::LightTracks, read_show, write_show, Payload, PayloadAction};
+
+#[derive(Args, Debug)]
+pub struct MergeLightsArgs {
+
+    /// Converted show to relight
+    show: PathBuf,
+
+    /// Zip of per-drone `t, r, g, b` CSVs, as for `--lights`
+    lights: PathBuf,
+
+    /// Time (ms) on the light program's clock at which each performance starts
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    start: f64,
+
+    /// Output file (defaults to `<show>.lit.vviz`)
+    #[arg(short, long)]
+    output: Option<PathBuf>
+}
+
+/// Replaces the light payload of every drone the lights archive has a track for, adding one
+/// where the drone had none. Other payloads and drones are kept as they are.
+pub fn run(args: MergeLightsArgs) -> Result<()> {
+    let mut show = read_show(&args.show)?;
+    let lights = LightTracks::load(&args.lights)?;
+
+    let (mut replaced, mut added, mut unlit) = (0, 0, 0);
+    for performance in show.performances.iter_mut() {
+        let duration: f32 = performance.description.traversals.0.iter()
+            .map(|traversal| traversal.duration(show.default_position_rate))
+            .sum();
+        let frames = (duration * show.default_color_rate).ceil() as u32;
+        let Some(actions) = lights.actions(performance.id + 1, args.start, frames, show.default_color_rate) else {
+            unlit += 1;
+            continue;
+        };
+        let actions = actions.into_iter().map(PayloadAction::Color).collect();
+        match performance.payload.iter_mut().find(|payload| payload.payload_type == "light") {
+            Some(payload) => {
+                payload.actions = actions;
+                replaced += 1;
+            }
+            None => {
+                let id = performance.payload.iter().map(|payload| payload.id + 1).max().unwrap_or(0);
+                performance.payload.push(Payload { id, payload_type: "light".into(), actions });
+                added += 1;
+            }
+        }
+    }
+
+    for drone_id in lights.drone_ids() {
+        if !show.performances.iter().any(|performance| performance.id + 1 == *drone_id) {
+            println!("Warning: light track for drone {} which is not in the show", drone_id);
+        }
+    }
+    println!("Replaced the lights of {} drones and added lights to {}", replaced, added);
+    if unlit > 0 {
+        println!("Warning: {} drones have no light track in {}; their lights are unchanged", unlit, args.lights.display());
+    }
+
+    let output = args.output.unwrap_or_else(|| args.show.with_extension("lit.vviz"));
+    println!("Generating {}", output.to_str().unwrap());
+    write_show(&show, &output)
+}
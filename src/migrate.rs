@@ -0,0 +1,90 @@
+//! `csv2vviz migrate`: rewrites a show for another vviz version.
+
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+
+use crate::{frames::{self, TraversalMode}, read_show, track::Track, write_show, AgentTraversals, Performance, Sample};
+
+#[derive(Args, Debug)]
+pub struct MigrateArgs {
+
+    /// Converted show to rewrite
+    show: PathBuf,
+
+    /// vviz version to write: `1.0` (traversal durations in seconds) or `1.1` (in frames at
+    /// the position rate)
+    #[arg(long, value_parser = parse_version)]
+    to: TraversalMode,
+
+    /// Output file (defaults to `<show>.v<version>.vviz`)
+    #[arg(short, long)]
+    output: Option<PathBuf>
+}
+
+fn parse_version(s: &str) -> Result<TraversalMode, String> {
+    TraversalMode::value_variants().iter().copied().find(|mode| mode.version() == s).ok_or_else(|| {
+        let versions: Vec<&str> = TraversalMode::value_variants().iter().map(TraversalMode::version).collect();
+        format!("unknown vviz version '{}' (supported: {})", s, versions.join(", "))
+    })
+}
+
+pub fn run(args: MigrateArgs) -> crate::error::Result<()> {
+    let mut show = read_show(&args.show)?;
+    let version = args.to.version();
+    if show.version == version {
+        println!("{} is already vviz {}", args.show.display(), version);
+        return Ok(());
+    }
+    println!("Migrating {} from vviz {} to {}", args.show.display(), show.version, version);
+
+    let rate = show.default_position_rate;
+    let mut resampled = 0;
+    for performance in show.performances.iter_mut() {
+        let traversals = std::mem::replace(&mut performance.description.traversals, AgentTraversals(vec![]));
+        performance.description.traversals = match args.to {
+            TraversalMode::Dt => to_seconds(traversals, rate),
+            TraversalMode::Frames if on_frames(&traversals, rate) => frames::to_frames(traversals, rate),
+            TraversalMode::Frames => {
+                resampled += 1;
+                resample(performance, traversals, rate)
+            }
+        };
+    }
+    if resampled > 0 {
+        println!("Resampled {} drones onto the {} /s position rate; their moves didn't start on whole frames", resampled, rate);
+    }
+    show.version = version.into();
+
+    let output = args.output.unwrap_or_else(|| args.show.with_extension(format!("v{}.vviz", version)));
+    println!("Generating {}", output.to_str().unwrap());
+    write_show(&show, &output)
+}
+
+/// Writes every traversal's duration as `dt` seconds, the 1.0 encoding.
+fn to_seconds(traversals: AgentTraversals, rate: f32) -> AgentTraversals {
+    AgentTraversals(traversals.0.into_iter().map(|mut traversal| {
+        traversal.dt = Some(traversal.duration(rate));
+        traversal.frames = None;
+        traversal
+    }).collect())
+}
+
+/// Whether every traversal lasts a whole number of frames, so frame counts keep the timing.
+fn on_frames(traversals: &AgentTraversals, rate: f32) -> bool {
+    traversals.0.iter().all(|traversal| {
+        let frames = traversal.duration(rate) * rate;
+        frames.round() >= 1.0 && (frames - frames.round()).abs() < 1e-3
+    })
+}
+
+/// Re-encodes a performance frame by frame from its positions at the position rate. Bézier
+/// traversals become the straight frames along their curve.
+fn resample(performance: &Performance, traversals: AgentTraversals, rate: f32) -> AgentTraversals {
+    let mut performance = performance.clone();
+    performance.description.traversals = traversals;
+    let samples: Vec<Sample> = Track::new(&performance, rate).samples.into_iter()
+        .map(|(t, [x, y, z])| Sample { t: t as f64 * 1000.0, pos: [x, z, y], rgb: [0; 3] })
+        .collect();
+    frames::to_frames(frames::resample(&samples, rate).into(), rate)
+}
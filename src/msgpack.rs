@@ -0,0 +1,521 @@
+//! A MessagePack encoder for serde, enough for writing shows. Structs are maps keyed by field
+//! name, so the binary show has the same shape as the JSON one; enum variants are written
+//! as in JSON too (unit variants as their name, others as a one-entry map).
+
+use std::fmt;
+
+use serde::ser::{self, Serialize};
+
+#[derive(Debug)]
+pub struct EncodeError(String);
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+impl ser::Error for EncodeError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        EncodeError(message.to_string())
+    }
+}
+
+type Result<T> = std::result::Result<T, EncodeError>;
+
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut serializer = Serializer { out: vec![] };
+    value.serialize(&mut serializer)?;
+    Ok(serializer.out)
+}
+
+struct Serializer {
+    out: Vec<u8>
+}
+
+impl Serializer {
+
+    fn uint(&mut self, n: u64) {
+        match n {
+            0..=0x7f => self.out.push(n as u8),
+            0x80..=0xff => self.out.extend([0xcc, n as u8]),
+            0x100..=0xffff => {
+                self.out.push(0xcd);
+                self.out.extend((n as u16).to_be_bytes());
+            }
+            0x1_0000..=0xffff_ffff => {
+                self.out.push(0xce);
+                self.out.extend((n as u32).to_be_bytes());
+            }
+            _ => {
+                self.out.push(0xcf);
+                self.out.extend(n.to_be_bytes());
+            }
+        }
+    }
+
+    fn int(&mut self, n: i64) {
+        match n {
+            0.. => self.uint(n as u64),
+            -32..=-1 => self.out.push(n as i8 as u8),
+            -128..=-33 => self.out.extend([0xd0, n as i8 as u8]),
+            -32768..=-129 => {
+                self.out.push(0xd1);
+                self.out.extend((n as i16).to_be_bytes());
+            }
+            -2_147_483_648..=-32769 => {
+                self.out.push(0xd2);
+                self.out.extend((n as i32).to_be_bytes());
+            }
+            _ => {
+                self.out.push(0xd3);
+                self.out.extend(n.to_be_bytes());
+            }
+        }
+    }
+
+    /// A length-prefixed header: the fix form below `fix_limit`, else 16 or 32 bits.
+    fn header(len: usize, fix: u8, fix_limit: usize, [wide16, wide32]: [u8; 2]) -> Vec<u8> {
+        if len < fix_limit {
+            vec![fix | len as u8]
+        } else if len <= 0xffff {
+            let mut bytes = vec![wide16];
+            bytes.extend((len as u16).to_be_bytes());
+            bytes
+        } else {
+            let mut bytes = vec![wide32];
+            bytes.extend((len as u32).to_be_bytes());
+            bytes
+        }
+    }
+
+    fn str(&mut self, s: &str) {
+        if s.len() < 32 {
+            self.out.push(0xa0 | s.len() as u8);
+        } else if s.len() <= 0xff {
+            self.out.extend([0xd9, s.len() as u8]);
+        } else {
+            self.out.extend(Serializer::header(s.len(), 0, 0, [0xda, 0xdb]));
+        }
+        self.out.extend(s.as_bytes());
+    }
+
+    /// Starts an array or map whose length is only known once its items are written.
+    fn open(&mut self, kind: Kind) -> Compound<'_> {
+        let start = self.out.len();
+        Compound { serializer: self, start, count: 0, kind }
+    }
+
+    /// A one-entry map from the variant name, for variants with data.
+    fn variant(&mut self, variant: &str) {
+        self.out.push(0x81);
+        self.str(variant);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Array,
+    Map
+}
+
+/// An array or map being written. Items go straight to the output and the header is
+/// inserted in front of them at the end.
+struct Compound<'a> {
+    serializer: &'a mut Serializer,
+    start: usize,
+    count: usize,
+    kind: Kind
+}
+
+impl Compound<'_> {
+
+    fn item<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.count += 1;
+        value.serialize(&mut *self.serializer)
+    }
+
+    fn entry<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<()> {
+        self.serializer.str(key);
+        self.item(value)
+    }
+
+    fn close(self) -> Result<()> {
+        let header = match self.kind {
+            Kind::Array => Serializer::header(self.count, 0x90, 16, [0xdc, 0xdd]),
+            Kind::Map => Serializer::header(self.count, 0x80, 16, [0xde, 0xdf])
+        };
+        self.serializer.out.splice(self.start..self.start, header);
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = EncodeError;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.out.push(if v { 0xc3 } else { 0xc2 });
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.int(v);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.uint(v);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.out.push(0xca);
+        self.out.extend(v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.out.push(0xcb);
+        self.out.extend(v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.str(v.encode_utf8(&mut [0; 4]));
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.str(v);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        if v.len() <= 0xff {
+            self.out.extend([0xc4, v.len() as u8]);
+        } else {
+            self.out.extend(Serializer::header(v.len(), 0, 0, [0xc5, 0xc6]));
+        }
+        self.out.extend(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.out.push(0xc0);
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<()> {
+        self.str(variant);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self, _name: &'static str, _index: u32, variant: &'static str, value: &T
+    ) -> Result<()> {
+        self.variant(variant);
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>> {
+        Ok(self.open(Kind::Array))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>> {
+        Ok(self.open(Kind::Array))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>> {
+        Ok(self.open(Kind::Array))
+    }
+
+    fn serialize_tuple_variant(
+        self, _name: &'static str, _index: u32, variant: &'static str, _len: usize
+    ) -> Result<Compound<'a>> {
+        self.variant(variant);
+        Ok(self.open(Kind::Array))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a>> {
+        Ok(self.open(Kind::Map))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>> {
+        Ok(self.open(Kind::Map))
+    }
+
+    fn serialize_struct_variant(
+        self, _name: &'static str, _index: u32, variant: &'static str, _len: usize
+    ) -> Result<Compound<'a>> {
+        self.variant(variant);
+        Ok(self.open(Kind::Map))
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.close()
+    }
+}
+
+impl ser::SerializeMap for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    /// Keys don't count as items; only the values do.
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        key.serialize(&mut *self.serializer)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.close()
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()> {
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.close()
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()> {
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Serialize, Serializer};
+
+    use super::to_vec;
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Vec<u8> {
+        to_vec(value).unwrap()
+    }
+
+    /// `prefix` followed by `times` copies of `fill`.
+    fn with_prefix(prefix: &[u8], fill: &[u8], times: usize) -> Vec<u8> {
+        let mut bytes = prefix.to_vec();
+        for _ in 0..times {
+            bytes.extend(fill);
+        }
+        bytes
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(encode(&0u8), [0x00]);
+        assert_eq!(encode(&127u32), [0x7f]);
+        assert_eq!(encode(&128u32), [0xcc, 0x80]);
+        assert_eq!(encode(&256u32), [0xcd, 0x01, 0x00]);
+        assert_eq!(encode(&65536u32), [0xce, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(encode(&(1u64 << 32)), [0xcf, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(encode(&5i32), [0x05]);
+        assert_eq!(encode(&-1i32), [0xff]);
+        assert_eq!(encode(&-32i32), [0xe0]);
+        assert_eq!(encode(&-33i32), [0xd0, 0xdf]);
+        assert_eq!(encode(&-129i32), [0xd1, 0xff, 0x7f]);
+        assert_eq!(encode(&-32769i32), [0xd2, 0xff, 0xff, 0x7f, 0xff]);
+        assert_eq!(encode(&i64::MIN), [0xd3, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(encode(&1.5f32), [0xca, 0x3f, 0xc0, 0x00, 0x00]);
+        assert_eq!(encode(&1.5f64), [0xcb, 0x3f, 0xf8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(encode(&true), [0xc3]);
+        assert_eq!(encode(&false), [0xc2]);
+        assert_eq!(encode(&None::<u8>), [0xc0]);
+        assert_eq!(encode(&Some(7u8)), [0x07]);
+        assert_eq!(encode(&()), [0xc0]);
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(encode(""), [0xa0]);
+        assert_eq!(encode("abc"), [0xa3, b'a', b'b', b'c']);
+        assert_eq!(encode(&'é'), [0xa2, 0xc3, 0xa9]);
+        assert_eq!(encode(&"x".repeat(31)), with_prefix(&[0xbf], b"x", 31));
+        assert_eq!(encode(&"x".repeat(32)), with_prefix(&[0xd9, 0x20], b"x", 32));
+        assert_eq!(encode(&"x".repeat(256)), with_prefix(&[0xda, 0x01, 0x00], b"x", 256));
+        assert_eq!(encode(&"x".repeat(65536)), with_prefix(&[0xdb, 0x00, 0x01, 0x00, 0x00], b"x", 65536));
+    }
+
+    #[test]
+    fn binary() {
+        struct Bytes(Vec<u8>);
+        impl Serialize for Bytes {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(&self.0)
+            }
+        }
+        assert_eq!(encode(&Bytes(vec![1, 2])), [0xc4, 0x02, 0x01, 0x02]);
+        assert_eq!(encode(&Bytes(vec![9; 256])), with_prefix(&[0xc5, 0x01, 0x00], &[9], 256));
+    }
+
+    #[test]
+    fn arrays() {
+        assert_eq!(encode(&Vec::<u8>::new()), [0x90]);
+        assert_eq!(encode(&[1u8, 2, 3]), [0x93, 0x01, 0x02, 0x03]);
+        assert_eq!(encode(&(1u8, "a")), [0x92, 0x01, 0xa1, b'a']);
+        assert_eq!(encode(&vec![vec![1u8], vec![]]), [0x92, 0x91, 0x01, 0x90]);
+        assert_eq!(encode(&vec![0u8; 15]), with_prefix(&[0x9f], &[0], 15));
+        assert_eq!(encode(&vec![0u8; 16]), with_prefix(&[0xdc, 0x00, 0x10], &[0], 16));
+        assert_eq!(encode(&vec![0u8; 65536]), with_prefix(&[0xdd, 0x00, 0x01, 0x00, 0x00], &[0], 65536));
+    }
+
+    #[test]
+    fn maps_and_structs() {
+        #[derive(Serialize)]
+        struct Point {
+            x: u8,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            label: Option<String>,
+            #[serde(rename = "onGround")]
+            on_ground: bool
+        }
+        assert_eq!(
+            encode(&Point { x: 1, label: None, on_ground: true }),
+            [0x82, 0xa1, b'x', 0x01, 0xa8, b'o', b'n', b'G', b'r', b'o', b'u', b'n', b'd', 0xc3]
+        );
+
+        let map: BTreeMap<String, u8> = (0..16).map(|i| (format!("{:x}", i), i)).collect();
+        let mut expected = vec![0xde, 0x00, 0x10];
+        for i in 0..16u8 {
+            expected.extend([0xa1, format!("{:x}", i).as_bytes()[0], i]);
+        }
+        assert_eq!(encode(&map), expected);
+    }
+
+    #[test]
+    fn enums() {
+        #[derive(Serialize)]
+        enum Action {
+            Stop,
+            Color([u8; 3]),
+            Move { x: i8 },
+            Pair(u8, u8)
+        }
+        assert_eq!(encode(&Action::Stop), [0xa4, b'S', b't', b'o', b'p']);
+        assert_eq!(encode(&Action::Color([255, 0, 1])), [0x81, 0xa5, b'C', b'o', b'l', b'o', b'r', 0x93, 0xcc, 0xff, 0x00, 0x01]);
+        assert_eq!(encode(&Action::Move { x: -1 }), [0x81, 0xa4, b'M', b'o', b'v', b'e', 0x81, 0xa1, b'x', 0xff]);
+        assert_eq!(encode(&Action::Pair(1, 2)), [0x81, 0xa4, b'P', b'a', b'i', b'r', 0x92, 0x01, 0x02]);
+    }
+}
@@ -0,0 +1,110 @@
+//! Writers of converted shows. Every format takes the same in-memory [`Show`], so a new one
+//! only has to lay out the bytes.
+
+use std::io::Write;
+
+use clap::ValueEnum;
+
+use crate::{error::{self, Result}, export::GeoOrigin, msgpack, protobuf, Show};
+
+/// Settings some formats need beyond the show itself.
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    /// Waypoints per drone, including the first and last position, for formats that decimate
+    /// the trajectories.
+    pub waypoints: usize,
+    /// WGS84 position of the show origin, for formats placing the show on the globe.
+    pub origin: Option<GeoOrigin>
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        OutputOptions { waypoints: 20, origin: None }
+    }
+}
+
+pub trait OutputFormat {
+
+    /// Extension of the file written, as in `show.<extension>`.
+    fn extension(&self) -> &'static str;
+
+    /// The contents of the file for `show`.
+    fn write(&self, show: &Show, options: &OutputOptions) -> Result<Vec<u8>>;
+
+    /// Writes the file for `show` to `writer`; formats that can be written as they are
+    /// serialized avoid holding the whole file in memory.
+    fn write_to(&self, show: &Show, options: &OutputOptions, writer: &mut dyn Write) -> Result<()> {
+        writer.write_all(&self.write(show, options)?).map_err(error::io("Failed to write new file"))
+    }
+}
+
+/// Encoding of the shows `convert` writes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ShowFormat {
+    /// vviz JSON
+    #[default]
+    Vviz,
+    /// The same show structure in MessagePack, smaller and faster to parse, for viewers reading it
+    VvizMsgpack,
+    /// Protocol Buffers `Show` message of `proto/show.proto` (needs the `protobuf` feature)
+    #[cfg_attr(not(feature = "protobuf"), value(skip), allow(dead_code))]
+    Pb
+}
+
+impl ShowFormat {
+
+    pub fn writer(&self) -> &'static dyn OutputFormat {
+        match self {
+            ShowFormat::Vviz => &Vviz,
+            ShowFormat::VvizMsgpack => &VvizMsgpack,
+            ShowFormat::Pb => &Protobuf
+        }
+    }
+}
+
+/// Finale 3D vviz JSON.
+pub struct Vviz;
+
+impl OutputFormat for Vviz {
+
+    fn extension(&self) -> &'static str {
+        "vviz"
+    }
+
+    fn write(&self, show: &Show, _options: &OutputOptions) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(show).expect("Failed to serialize show data."))
+    }
+
+    fn write_to(&self, show: &Show, _options: &OutputOptions, writer: &mut dyn Write) -> Result<()> {
+        serde_json::to_writer(writer, show).map_err(error::io("Failed to write new file"))
+    }
+}
+
+/// vviz shows in MessagePack: the JSON document's objects, arrays and values, binary encoded.
+pub struct VvizMsgpack;
+
+impl OutputFormat for VvizMsgpack {
+
+    fn extension(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn write(&self, show: &Show, _options: &OutputOptions) -> Result<Vec<u8>> {
+        Ok(msgpack::to_vec(show).expect("Failed to serialize show data."))
+    }
+}
+
+/// The show as a Protocol Buffers `Show` message, for backends generating their types from
+/// `proto/show.proto`.
+pub struct Protobuf;
+
+impl OutputFormat for Protobuf {
+
+    fn extension(&self) -> &'static str {
+        "pb"
+    }
+
+    fn write(&self, show: &Show, _options: &OutputOptions) -> Result<Vec<u8>> {
+        protobuf::encode(show)
+    }
+}
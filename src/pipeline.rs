@@ -0,0 +1,174 @@
+//! Reads an input's entries on several threads: one thread decompresses entries in archive
+//! order and hands them out in chunks, a pool of workers parses them, and the results come
+//! back to the caller in archive order, so everything order-dependent (messages, numbering,
+//! duplicate checks) stays sequential.
+
+use std::{collections::BTreeMap, io::{self, Read}, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, mpsc, Mutex}, thread};
+
+use crate::{archive, error::{self, Error, Result}};
+
+/// Entries handed out ahead of the workers, per worker.
+const QUEUE_DEPTH: usize = 2;
+
+/// Bytes of an entry passed to its worker at a time.
+const CHUNK: usize = 64 * 1024;
+
+/// Chunks of an entry decompressed ahead of its worker. Entries up to a megabyte are read
+/// through without waiting on the worker; larger ones are decompressed as fast as they are
+/// parsed, so no entry is ever held whole.
+const CHUNKS_AHEAD: usize = 16;
+
+/// Worker threads per input, when set by [`set_workers`].
+static WORKERS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn cores() -> usize {
+    thread::available_parallelism().map_or(1, |cores| cores.get())
+}
+
+/// Parses each input on `workers` threads from now on, instead of one per core.
+pub fn set_workers(workers: usize) {
+    WORKERS.store(workers.max(1), Ordering::Relaxed);
+}
+
+/// What [`set_workers`] last set, or 0 for one worker per core, to put back with
+/// [`restore_workers`].
+pub fn workers_set() -> usize {
+    WORKERS.load(Ordering::Relaxed)
+}
+
+pub fn restore_workers(workers: usize) {
+    WORKERS.store(workers, Ordering::Relaxed);
+}
+
+/// Worker threads to parse with: one per core unless set. A single worker parses inline
+/// instead, as on targets without threads.
+fn workers() -> usize {
+    match WORKERS.load(Ordering::Relaxed) {
+        0 => cores(),
+        workers => workers
+    }
+}
+
+/// A lone CSV or log has nothing to overlap with.
+fn is_single(input: archive::Input) -> bool {
+    matches!(input, archive::Input::Path(fname) if matches!(archive::Format::detect(fname), Some(archive::Format::Csv | archive::Format::Ulog)))
+}
+
+/// Whether [`map_entries`] parses the entries of `input` one after the other, on the calling
+/// thread, each straight after the one before has been consumed.
+pub fn is_sequential(input: archive::Input) -> bool {
+    workers() <= 1 || is_single(input)
+}
+
+/// An entry as it comes through from the reader thread.
+struct Chunks {
+    chunks: mpsc::Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    at: usize
+}
+
+impl Read for Chunks {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.at == self.chunk.len() {
+            match self.chunks.recv() {
+                Ok(chunk) => (self.chunk, self.at) = (chunk?, 0),
+                // The whole entry is through.
+                Err(_) => return Ok(0)
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.at);
+        buf[..len].copy_from_slice(&self.chunk[self.at..self.at + len]);
+        self.at += len;
+        Ok(len)
+    }
+}
+
+/// Calls `parse` with the name and a reader of the contents of every entry `wanted` by name,
+/// in parallel, and `consume` with each name and result in archive order. Other entries are
+/// passed over unread, so a zip doesn't even decompress them. Stops at the first error from
+/// reading the input or from `consume`.
+pub fn map_entries<T: Send>(
+    input: archive::Input,
+    wanted: impl Fn(&str) -> bool + Send,
+    parse: impl Fn(&str, &mut dyn Read) -> T + Sync,
+    mut consume: impl FnMut(&str, T) -> Result<()>
+) -> Result<()> {
+    let workers = workers();
+    if is_sequential(input) {
+        return input.for_each_entry(|name, file| match wanted(name) {
+            true => consume(name, parse(name, file)),
+            false => Ok(())
+        });
+    }
+
+    let (entries, queue) = mpsc::sync_channel::<(usize, String, Chunks)>(workers * QUEUE_DEPTH);
+    let queue = Mutex::new(queue);
+    let stopped = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let (results, parsed) = mpsc::channel::<(usize, String, T)>();
+
+        let stopped = &stopped;
+        let reader = scope.spawn(move || {
+            let mut index = 0;
+            input.for_each_entry(|name, file| {
+                // The consumer gave up; its error is the one reported.
+                if stopped.load(Ordering::Relaxed) {
+                    return Err(Error::Io(String::new()));
+                }
+                if !wanted(name) {
+                    return Ok(());
+                }
+                let (chunks, receiver) = mpsc::sync_channel(CHUNKS_AHEAD);
+                let entry = Chunks { chunks: receiver, chunk: vec![], at: 0 };
+                entries.send((index, name.to_string(), entry)).map_err(|_| Error::Io(String::new()))?;
+                index += 1;
+                loop {
+                    let mut chunk = Vec::with_capacity(CHUNK);
+                    match file.take(CHUNK as u64).read_to_end(&mut chunk) {
+                        Ok(0) => return Ok(()),
+                        // The worker is done with the entry, or gave up on it.
+                        Ok(_) => if chunks.send(Ok(chunk)).is_err() {
+                            return Ok(());
+                        },
+                        // The worker sees the error too, rather than the entry cut short.
+                        Err(e) => {
+                            let error = error::io(&format!("Failed to read {}", name))(&e);
+                            let _ = chunks.send(Err(e));
+                            return Err(error);
+                        }
+                    }
+                }
+            })
+        });
+        for _ in 0..workers {
+            let (queue, results, parse) = (&queue, results.clone(), &parse);
+            // Once the consumer is gone, workers keep draining the queue so the reader can't
+            // block on it.
+            scope.spawn(move || loop {
+                let entry = queue.lock().unwrap().recv();
+                let Ok((index, name, mut data)) = entry else {
+                    break;
+                };
+                if stopped.load(Ordering::Relaxed) {
+                    continue;
+                }
+                let result = parse(&name, &mut data);
+                if results.send((index, name, result)).is_err() {
+                    stopped.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+        drop(results);
+
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (index, name, result) in parsed.iter() {
+            pending.insert(index, (name, result));
+            while let Some((name, result)) = pending.remove(&next) {
+                consume(&name, result).inspect_err(|_| stopped.store(true, Ordering::Relaxed))?;
+                next += 1;
+            }
+        }
+        reader.join().unwrap()
+    })
+}
@@ -0,0 +1,103 @@
+use std::{net::IpAddr, path::PathBuf, time::{Duration, Instant}};
+
+use clap::{ArgGroup, Args};
+
+use crate::{error::Result, export::{color_at, Rgb}, read_show, track::Track, validate};
+
+mod artnet;
+mod osc;
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("outputs").args(["artnet", "osc"]).required(true).multiple(true)))]
+pub struct PlayArgs {
+
+    /// Converted show to play back
+    show: PathBuf,
+
+    /// Stream each drone's LED color as Art-Net DMX to this node or broadcast address
+    #[arg(long)]
+    artnet: Option<IpAddr>,
+
+    /// TOML file placing drones on DMX universes and channels (default: three channels per
+    /// drone, packed in drone order from universe 0)
+    #[arg(long, requires = "artnet")]
+    universe_map: Option<PathBuf>,
+
+    /// Send each drone's position and color as OSC messages to this `host:port`
+    #[arg(long)]
+    osc: Option<String>,
+
+    /// Updates sent per second
+    #[arg(long, default_value_t = 30.0)]
+    rate: f32,
+
+    /// Start this many seconds into the show
+    #[arg(long, default_value_t = 0.0)]
+    from: f32
+}
+
+/// One drone at one playback instant; the position is in the trajectory CSVs' axes (east,
+/// north, up).
+pub struct State {
+    pub drone_id: usize,
+    pub position: [f32; 3],
+    pub color: Option<Rgb>
+}
+
+/// Plays the show back in real time, sending every drone's state at `--rate` until the
+/// last drone's timeline ends.
+pub fn run(args: PlayArgs) -> Result<()> {
+    let show = read_show(&args.show)?;
+    let tracks: Vec<Track> = show.performances.iter()
+        .map(|performance| Track::new(performance, show.default_position_rate))
+        .collect();
+    let end = show.performances.iter().zip(&tracks)
+        .map(|(performance, track)| validate::timeline(performance, &show, track))
+        .fold(0.0, f32::max);
+
+    let drone_ids: Vec<usize> = show.performances.iter().map(|performance| performance.id + 1).collect();
+    let mut artnet = args.artnet
+        .map(|address| artnet::Sender::new(address, &drone_ids, args.universe_map.as_deref()))
+        .transpose()?;
+    let osc = args.osc.as_deref().map(osc::Sender::new).transpose()?;
+    println!("Playing {} drones from {:.1} s to {:.1} s", drone_ids.len(), args.from, end);
+    if let (Some(address), Some(artnet)) = (args.artnet, &artnet) {
+        println!("  Art-Net to {} ({} universes)", address, artnet.universes());
+    }
+    if let Some(address) = &args.osc {
+        println!("  OSC to {}", address);
+    }
+
+    let step = Duration::from_secs_f32(1.0 / args.rate);
+    let started = Instant::now();
+    let mut frame = 0;
+    loop {
+        let t = args.from + frame as f32 / args.rate;
+        if t > end {
+            break;
+        }
+        let states: Vec<State> = show.performances.iter().zip(&tracks)
+            .map(|(performance, track)| {
+                let [x, up, north] = track.position_at(t);
+                State {
+                    drone_id: performance.id + 1,
+                    position: [x, north, up],
+                    color: color_at(performance, show.default_color_rate, t)
+                }
+            })
+            .collect();
+        if let Some(artnet) = artnet.as_mut() {
+            artnet.send(&states)?;
+        }
+        if let Some(osc) = &osc {
+            osc.send(&states)?;
+        }
+
+        frame += 1;
+        if let Some(wait) = (started + step * frame).checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+    }
+    println!("Played {:.1} s", end - args.from);
+    Ok(())
+}
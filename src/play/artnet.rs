@@ -0,0 +1,125 @@
+use std::{collections::{BTreeMap, HashMap}, net::{IpAddr, SocketAddr, UdpSocket}, path::Path};
+
+use serde::Deserialize;
+
+use crate::{config, error::{self, Error, Result}};
+
+use super::State;
+
+const PORT: u16 = 6454;
+const CHANNELS: usize = 512;
+
+/// Where one drone's red, green and blue channels start.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Patch {
+    universe: u16,
+    /// 1-based, as on a lighting desk.
+    channel: usize
+}
+
+/// Drone placement read from `--universe-map`:
+///
+/// ```toml
+/// [drones.1]
+/// universe = 0
+/// channel = 1
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UniverseMap {
+    /// Keyed by drone number.
+    drones: HashMap<usize, Patch>
+}
+
+/// Sends drone colors as ArtDmx packets, one per universe per update.
+pub struct Sender {
+    socket: UdpSocket,
+    target: SocketAddr,
+    patches: HashMap<usize, Patch>,
+    universes: BTreeMap<u16, [u8; CHANNELS]>,
+    sequence: u8
+}
+
+impl Sender {
+
+    /// Patches `drone_ids` from the map file, or packs them in order from universe 0.
+    pub fn new(address: IpAddr, drone_ids: &[usize], map: Option<&Path>) -> Result<Self> {
+        let patches = match map {
+            Some(fname) => {
+                let map: UniverseMap = config::load_toml(fname, "universe map")?;
+                for drone_id in drone_ids.iter().filter(|drone_id| !map.drones.contains_key(drone_id)) {
+                    println!("Warning: drone {} is not in the universe map and will not be sent", drone_id);
+                }
+                map.drones
+            }
+            None => drone_ids.iter().enumerate().map(|(i, &drone_id)| {
+                let per_universe = CHANNELS / 3;
+                (drone_id, Patch { universe: (i / per_universe) as u16, channel: 1 + i % per_universe * 3 })
+            }).collect()
+        };
+
+        let mut used: HashMap<(u16, usize), usize> = HashMap::new();
+        let mut universes = BTreeMap::new();
+        let mut sorted: Vec<_> = patches.iter().collect();
+        sorted.sort_by_key(|(drone_id, _)| **drone_id);
+        for (&drone_id, patch) in sorted {
+            if patch.universe > 0x7fff || patch.channel == 0 || patch.channel + 2 > CHANNELS {
+                return Err(Error::Parse(format!(
+                    "Invalid universe map: drone {} needs channels {}-{} of universe {}, outside 1-512 of universes 0-32767",
+                    drone_id, patch.channel, patch.channel + 2, patch.universe
+                )));
+            }
+            for channel in patch.channel..patch.channel + 3 {
+                if let Some(other) = used.insert((patch.universe, channel), drone_id) {
+                    return Err(Error::Parse(format!(
+                        "Invalid universe map: drones {} and {} share channel {} of universe {}",
+                        other, drone_id, channel, patch.universe
+                    )));
+                }
+            }
+            universes.insert(patch.universe, [0; CHANNELS]);
+        }
+
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(error::io("Failed to open Art-Net socket"))?;
+        socket.set_broadcast(true).map_err(error::io("Failed to open Art-Net socket"))?;
+        Ok(Sender { socket, target: SocketAddr::new(address, PORT), patches, universes, sequence: 0 })
+    }
+
+    pub fn universes(&self) -> usize {
+        self.universes.len()
+    }
+
+    /// Sends every patched universe; drones without a light are black.
+    pub fn send(&mut self, states: &[State]) -> Result<()> {
+        for state in states {
+            if let Some(patch) = self.patches.get(&state.drone_id) {
+                let (r, g, b) = state.color.unwrap_or_default();
+                let data = self.universes.get_mut(&patch.universe).unwrap();
+                data[patch.channel - 1..patch.channel + 2].copy_from_slice(&[r, g, b]);
+            }
+        }
+        // Sequence 0 means "not sequenced", so it counts 1-255.
+        self.sequence = self.sequence % 255 + 1;
+        for (universe, data) in &self.universes {
+            self.socket.send_to(&art_dmx(*universe, self.sequence, data), self.target)
+                .map_err(error::io("Failed to send Art-Net packet"))?;
+        }
+        Ok(())
+    }
+}
+
+/// ArtDmx packet (Art-Net 4, protocol version 14) carrying a full universe.
+fn art_dmx(universe: u16, sequence: u8, data: &[u8; CHANNELS]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(18 + CHANNELS);
+    packet.extend_from_slice(b"Art-Net\0");
+    packet.extend_from_slice(&0x5000u16.to_le_bytes());
+    packet.extend_from_slice(&14u16.to_be_bytes());
+    packet.push(sequence);
+    packet.push(0);
+    // SubUni then Net: the low byte of the 15-bit port address, then its top 7 bits.
+    packet.extend_from_slice(&universe.to_le_bytes());
+    packet.extend_from_slice(&(CHANNELS as u16).to_be_bytes());
+    packet.extend_from_slice(data);
+    packet
+}
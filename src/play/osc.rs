@@ -0,0 +1,66 @@
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use crate::error::{self, Error, Result};
+
+use super::State;
+
+/// Sends `/drone/<n>/position` (three floats, meters) and `/drone/<n>/color` (three ints,
+/// only for drones with a light) for every drone on each update.
+pub struct Sender {
+    socket: UdpSocket,
+    target: SocketAddr
+}
+
+impl Sender {
+
+    pub fn new(address: &str) -> Result<Self> {
+        let target = address.to_socket_addrs()
+            .map_err(|e| Error::Io(format!("Failed to resolve OSC address '{}': {}", address, e)))?
+            .next()
+            .ok_or_else(|| Error::Io(format!("Failed to resolve OSC address '{}'", address)))?;
+        let local = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(local).map_err(error::io("Failed to open OSC socket"))?;
+        Ok(Sender { socket, target })
+    }
+
+    pub fn send(&self, states: &[State]) -> Result<()> {
+        for state in states {
+            let [x, y, z] = state.position;
+            let position = message(&format!("/drone/{}/position", state.drone_id), &[Arg::Float(x), Arg::Float(y), Arg::Float(z)]);
+            self.socket.send_to(&position, self.target).map_err(error::io("Failed to send OSC message"))?;
+            if let Some((r, g, b)) = state.color {
+                let color = message(&format!("/drone/{}/color", state.drone_id), &[Arg::Int(r as i32), Arg::Int(g as i32), Arg::Int(b as i32)]);
+                self.socket.send_to(&color, self.target).map_err(error::io("Failed to send OSC message"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Arg {
+    Int(i32),
+    Float(f32)
+}
+
+/// OSC 1.0 strings are null-terminated and padded to a multiple of four bytes.
+fn push_string(packet: &mut Vec<u8>, s: &str) {
+    packet.extend_from_slice(s.as_bytes());
+    packet.resize((packet.len() + 4) & !3, 0);
+}
+
+fn message(address: &str, args: &[Arg]) -> Vec<u8> {
+    let mut packet = vec![];
+    push_string(&mut packet, address);
+    let tags: String = std::iter::once(',').chain(args.iter().map(|arg| match arg {
+        Arg::Int(_) => 'i',
+        Arg::Float(_) => 'f'
+    })).collect();
+    push_string(&mut packet, &tags);
+    for arg in args {
+        match arg {
+            Arg::Int(value) => packet.extend_from_slice(&value.to_be_bytes()),
+            Arg::Float(value) => packet.extend_from_slice(&value.to_be_bytes())
+        }
+    }
+    packet
+}
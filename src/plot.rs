@@ -0,0 +1,203 @@
+//! Simple figures (lines, dots, rectangles and labels in pixel coordinates, y down), written
+//! as SVG or rasterized to PNG depending on the file extension.
+
+use std::{io::Write, path::Path};
+
+use flate2::{write::ZlibEncoder, Compression, Crc};
+
+use crate::{error::{self, Error, Result}, export::Rgb, generate::font};
+
+/// Pixels per font dot in PNG labels.
+const TEXT_SCALE: usize = 2;
+
+enum Shape {
+    Line { from: [f32; 2], to: [f32; 2], width: f32, color: Rgb },
+    Dot { center: [f32; 2], radius: f32, color: Rgb },
+    Rect { at: [f32; 2], size: [f32; 2], color: Rgb },
+    Text { at: [f32; 2], text: String, color: Rgb }
+}
+
+pub struct Figure {
+    width: u32,
+    height: u32,
+    shapes: Vec<Shape>
+}
+
+impl Figure {
+
+    /// An empty white figure.
+    pub fn new(width: u32, height: u32) -> Self {
+        Figure { width, height, shapes: vec![] }
+    }
+
+    pub fn line(&mut self, from: [f32; 2], to: [f32; 2], width: f32, color: Rgb) {
+        self.shapes.push(Shape::Line { from, to, width, color });
+    }
+
+    /// A line with a head at `to`, when there is room for one.
+    pub fn arrow(&mut self, from: [f32; 2], to: [f32; 2], width: f32, color: Rgb) {
+        self.line(from, to, width, color);
+        let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+        let length = dx.hypot(dy);
+        if length < 6.0 {
+            return;
+        }
+        let head = (length / 3.0).min(6.0);
+        let (ux, uy) = (dx / length * head, dy / length * head);
+        for side in [-0.5, 0.5] {
+            self.line(to, [to[0] - ux - uy * side, to[1] - uy + ux * side], width, color);
+        }
+    }
+
+    pub fn dot(&mut self, center: [f32; 2], radius: f32, color: Rgb) {
+        self.shapes.push(Shape::Dot { center, radius, color });
+    }
+
+    /// A filled rectangle with its top left corner at `at`.
+    pub fn rect(&mut self, at: [f32; 2], size: [f32; 2], color: Rgb) {
+        self.shapes.push(Shape::Rect { at, size, color });
+    }
+
+    /// A label with its top left corner at `at`. PNGs draw it in the capitals of the
+    /// formation font, about 14 px high.
+    pub fn text(&mut self, at: [f32; 2], text: &str, color: Rgb) {
+        self.shapes.push(Shape::Text { at, text: text.to_string(), color });
+    }
+
+    /// Writes the figure as SVG or PNG, after the file's extension.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let bytes = match path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("svg") => self.svg().into_bytes(),
+            Some("png") => self.png(),
+            _ => return Err(Error::Format(format!("Cannot draw {}: plots are written as .svg or .png", path.display())))
+        };
+        std::fs::write(path, bytes).map_err(error::io("Failed to write plot"))
+    }
+
+    fn svg(&self) -> String {
+        let fill = |(r, g, b): Rgb| format!("#{:02x}{:02x}{:02x}", r, g, b);
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n",
+            self.width, self.height
+        );
+        for shape in &self.shapes {
+            svg.push_str(&match shape {
+                Shape::Line { from, to, width, color } => format!(
+                    "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"round\"/>\n",
+                    from[0], from[1], to[0], to[1], fill(*color), width
+                ),
+                Shape::Dot { center, radius, color } => format!(
+                    "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{}\" fill=\"{}\"/>\n", center[0], center[1], radius, fill(*color)
+                ),
+                Shape::Rect { at, size, color } => format!(
+                    "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>\n", at[0], at[1], size[0], size[1], fill(*color)
+                ),
+                Shape::Text { at, text, color } => format!(
+                    "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"monospace\" font-size=\"14\" dominant-baseline=\"hanging\" fill=\"{}\">{}</text>\n",
+                    at[0], at[1], fill(*color), escape(text)
+                )
+            });
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    fn png(&self) -> Vec<u8> {
+        let mut canvas = Canvas { width: self.width as usize, height: self.height as usize, pixels: vec![255; (self.width * self.height * 3) as usize] };
+        for shape in &self.shapes {
+            match shape {
+                Shape::Line { from, to, width, color } => {
+                    let steps = ((to[0] - from[0]).hypot(to[1] - from[1]) * 2.0).ceil().max(1.0) as usize;
+                    for step in 0..=steps {
+                        let u = step as f32 / steps as f32;
+                        let point = [from[0] + (to[0] - from[0]) * u, from[1] + (to[1] - from[1]) * u];
+                        canvas.disc(point, width / 2.0, *color);
+                    }
+                }
+                Shape::Dot { center, radius, color } => canvas.disc(*center, *radius, *color),
+                Shape::Rect { at, size, color } => {
+                    let (x0, y0) = (at[0].round() as i64, at[1].round() as i64);
+                    let (x1, y1) = ((at[0] + size[0]).round() as i64, (at[1] + size[1]).round() as i64);
+                    for y in y0..y1 {
+                        for x in x0..x1 {
+                            canvas.set(x, y, *color);
+                        }
+                    }
+                }
+                Shape::Text { at, text, color } => {
+                    let (x0, y0) = (at[0].round() as i64, at[1].round() as i64);
+                    for (index, glyph) in text.chars().map(font::glyph).enumerate() {
+                        let left = x0 + (index * (font::WIDTH + 1) * TEXT_SCALE) as i64;
+                        for (row, bits) in glyph.unwrap_or([0; font::HEIGHT]).into_iter().enumerate() {
+                            for column in (0..font::WIDTH).filter(|column| bits & (0x10 >> column) != 0) {
+                                for (dx, dy) in (0..TEXT_SCALE).flat_map(|dx| (0..TEXT_SCALE).map(move |dy| (dx, dy))) {
+                                    canvas.set(left + (column * TEXT_SCALE + dx) as i64, y0 + (row * TEXT_SCALE + dy) as i64, *color);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        canvas.png()
+    }
+}
+
+/// 8-bit RGB pixels, row by row from the top.
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>
+}
+
+impl Canvas {
+
+    fn set(&mut self, x: i64, y: i64, (r, g, b): Rgb) {
+        if (0..self.width as i64).contains(&x) && (0..self.height as i64).contains(&y) {
+            let index = (y as usize * self.width + x as usize) * 3;
+            self.pixels[index..index + 3].copy_from_slice(&[r, g, b]);
+        }
+    }
+
+    fn disc(&mut self, [cx, cy]: [f32; 2], radius: f32, color: Rgb) {
+        let radius = radius.max(0.5);
+        for y in (cy - radius).floor() as i64..=(cy + radius).ceil() as i64 {
+            for x in (cx - radius).floor() as i64..=(cx + radius).ceil() as i64 {
+                if (x as f32 + 0.5 - cx).hypot(y as f32 + 0.5 - cy) <= radius {
+                    self.set(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// PNG (ISO/IEC 15948) with the rows unfiltered in a single IDAT chunk.
+    fn png(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits per channel, truecolor, deflate, adaptive filtering, no interlace.
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        for row in self.pixels.chunks(self.width * 3) {
+            zlib.write_all(&[0]).and_then(|_| zlib.write_all(row)).expect("writing to memory");
+        }
+        let data = zlib.finish().expect("writing to memory");
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, body) in [(b"IHDR", &header[..]), (b"IDAT", &data[..]), (b"IEND", &[][..])] {
+            png.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            let mut crc = Crc::new();
+            crc.update(kind);
+            crc.update(body);
+            png.extend_from_slice(kind);
+            png.extend_from_slice(body);
+            png.extend_from_slice(&crc.sum().to_be_bytes());
+        }
+        png
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
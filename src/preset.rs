@@ -0,0 +1,56 @@
+use std::str::FromStr;
+
+use clap::ValueEnum;
+
+use crate::{Rotation, F3D};
+
+/// Coordinate conventions of common source tools, so users don't have to work out axis
+/// swaps and signs with `--rotate`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// Blender world axes (Z up); the show is turned so Blender's front view faces the viewer
+    BlenderToVviz,
+    /// North–east–down local frame (PX4, ArduPilot logs)
+    NedToEnu,
+    /// Skybrush CSV exports: east–north–up in metres
+    #[default]
+    Skybrush
+}
+
+/// What a preset bundles: for each of east, north and up, the source column (0–2 relative
+/// to the first coordinate column) and its sign; and a rotation applied in vviz space before
+/// the user's own `--rotate`. Presets set no unit: lengths are scaled by the column headers
+/// (`x [cm]`), and taken as metres without one.
+struct Convention {
+    axes: [(usize, f32); 3],
+    rotation: Option<&'static str>
+}
+
+impl Preset {
+
+    fn convention(&self) -> Convention {
+        match self {
+            Preset::BlenderToVviz => Convention {
+                axes: [(0, 1.0), (1, 1.0), (2, 1.0)],
+                rotation: Some("0 180 0")
+            },
+            Preset::NedToEnu => Convention {
+                axes: [(1, 1.0), (0, 1.0), (2, -1.0)],
+                rotation: None
+            },
+            Preset::Skybrush => Convention {
+                axes: [(0, 1.0), (1, 1.0), (2, 1.0)],
+                rotation: None
+            }
+        }
+    }
+
+    /// Maps the three coordinate columns of a source row to east, north and up.
+    pub fn map_axes(&self, source: [f32; 3]) -> [f32; 3] {
+        self.convention().axes.map(|(column, sign)| source[column] * sign)
+    }
+
+    pub fn rotation(&self) -> Option<Rotation> {
+        self.convention().rotation.map(|rotation| F3D::from_str(rotation).unwrap().rotation())
+    }
+}
@@ -0,0 +1,28 @@
+//! `--preview-decimate`: a quick, coarse conversion of a huge show that keeps only every Nth
+//! sample, marked as a preview in the show's metadata so it isn't mistaken for the real one.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Sample;
+
+/// Recorded in the metadata of shows converted from decimated samples.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preview {
+    /// Every how many input samples one was kept.
+    pub decimate: usize
+}
+
+/// Keeps every `every`th sample, and the last one so the trajectory still ends where and when
+/// the input does.
+pub fn decimate(samples: Vec<Sample>, every: usize) -> Vec<Sample> {
+    let last = samples.len().saturating_sub(1);
+    samples.into_iter().enumerate()
+        .filter(|(index, _)| index % every == 0 || *index == last)
+        .map(|(_, sample)| sample)
+        .collect()
+}
+
+pub fn parse_decimate(s: &str) -> Result<usize, String> {
+    s.trim().parse::<usize>().ok().filter(|every| *every >= 2)
+        .ok_or_else(|| format!("expected keeping every Nth sample with N of 2 or more, got '{}'", s))
+}
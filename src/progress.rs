@@ -0,0 +1,110 @@
+//! `--progress-json`: newline-delimited JSON events on stderr, for frontends that show a
+//! conversion's progress without parsing its messages. Every event has an `event` field:
+//!
+//! - `phase`: the input `file` starts being `read`, or its show being built (`build`) or
+//!   written (`write`)
+//! - `entry`: a drone `entry` of the input was read, with the `percent` of the batch done when
+//!   the input's entries can be counted up front (zip archives and directories)
+//! - `warning`: a warning's `message` while converting `file`, also printed as usual
+//! - `done`: the conversion finished, with the shows `written`
+//! - `error`: the conversion failed with `message`, and will exit with `code`
+//!
+//! With `--jobs`, inputs are converted side by side and their events interleave; each thread
+//! tracks the input it is converting.
+
+use std::{cell::RefCell, path::PathBuf, sync::{atomic::{AtomicBool, Ordering}, Mutex}};
+
+use serde_json::{json, Value};
+
+use crate::error::Result;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Fraction of each input of the batch read so far.
+static DONE: Mutex<Vec<f64>> = Mutex::new(vec![]);
+
+thread_local! {
+    static POSITION: RefCell<Position> = const { RefCell::new(Position { file: String::new(), index: 0, entries: None, read: 0 }) };
+}
+
+/// Where this thread is in the input it converts.
+struct Position {
+    file: String,
+    index: usize,
+    entries: Option<usize>,
+    read: usize
+}
+
+fn emit(event: Value) {
+    if ENABLED.load(Ordering::Relaxed) {
+        eprintln!("{}", event);
+    }
+}
+
+/// Starts the event stream for a batch of `files` inputs.
+pub fn enable(files: usize) {
+    ENABLED.store(true, Ordering::Relaxed);
+    *DONE.lock().unwrap() = vec![0.0; files.max(1)];
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Ends the event stream started by [`enable`].
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// The `index`th input of the batch starts being read; it holds `entries` entries, if known.
+pub fn input(index: usize, file: &str, entries: Option<usize>) {
+    POSITION.with_borrow_mut(|position| *position = Position { file: file.to_string(), index, entries, read: 0 });
+    emit(json!({ "event": "phase", "phase": "read", "file": file }));
+}
+
+/// A drone entry of the current input was read.
+pub fn entry(name: &str) {
+    let event = POSITION.with_borrow_mut(|position| {
+        position.read += 1;
+        let mut event = json!({ "event": "entry", "file": position.file, "entry": name });
+        if let Some(entries) = position.entries.filter(|entries| *entries > 0) {
+            let mut done = DONE.lock().unwrap();
+            if let Some(fraction) = done.get_mut(position.index) {
+                *fraction = (position.read as f64 / entries as f64).min(1.0);
+            }
+            let percent = done.iter().sum::<f64>() / done.len().max(1) as f64 * 100.0;
+            event["percent"] = json!((percent * 10.0).round() / 10.0);
+        }
+        event
+    });
+    emit(event);
+}
+
+fn file() -> String {
+    POSITION.with_borrow(|position| position.file.clone())
+}
+
+/// The current input's show is being `built` or `written`: it has been read in full.
+pub fn phase(phase: &str) {
+    let index = POSITION.with_borrow(|position| position.index);
+    if let Some(fraction) = DONE.lock().unwrap().get_mut(index) {
+        *fraction = 1.0;
+    }
+    emit(json!({ "event": "phase", "phase": phase, "file": file() }));
+}
+
+/// Prints `Warning: <message>`, and sends it as an event.
+pub fn warning(message: String) {
+    println!("Warning: {}", message);
+    if ENABLED.load(Ordering::Relaxed) {
+        emit(json!({ "event": "warning", "file": file(), "message": message }));
+    }
+}
+
+/// Ends the stream with the outcome of the conversion.
+pub fn finish(result: &Result<Vec<PathBuf>>) {
+    emit(match result {
+        Ok(written) => json!({ "event": "done", "percent": 100.0, "written": written }),
+        Err(error) => json!({ "event": "error", "message": error.to_string(), "code": error.exit_code() })
+    });
+}
@@ -0,0 +1,283 @@
+//! Shows as Protocol Buffers, encoded by hand following `proto/show.proto`.
+
+#[cfg(not(feature = "protobuf"))]
+pub fn encode(_show: &crate::Show) -> crate::error::Result<Vec<u8>> {
+    Err(crate::error::Error::Format("Cannot write Protocol Buffers: csv2vviz was built without the `protobuf` feature.".to_string()))
+}
+
+#[cfg(feature = "protobuf")]
+pub use encoder::encode;
+
+#[cfg(feature = "protobuf")]
+mod encoder {
+    use crate::{error::Result, AgentTraversal, Payload, PayloadAction, Performance, Show};
+
+    const VARINT: u64 = 0;
+    const FIXED32: u64 = 5;
+    const LENGTH_DELIMITED: u64 = 2;
+
+    /// A message being encoded. Fields without explicit presence are left out when they
+    /// hold their default value, as protobuf encoders do.
+    #[derive(Default)]
+    struct Message(Vec<u8>);
+
+    impl Message {
+
+        fn varint(&mut self, mut n: u64) {
+            while n >= 0x80 {
+                self.0.push(n as u8 | 0x80);
+                n >>= 7;
+            }
+            self.0.push(n as u8);
+        }
+
+        fn key(&mut self, field: u64, wire_type: u64) {
+            self.varint(field << 3 | wire_type);
+        }
+
+        fn uint(&mut self, field: u64, n: u64) {
+            if n != 0 {
+                self.optional_uint(field, Some(n));
+            }
+        }
+
+        fn optional_uint(&mut self, field: u64, n: Option<u64>) {
+            if let Some(n) = n {
+                self.key(field, VARINT);
+                self.varint(n);
+            }
+        }
+
+        fn float(&mut self, field: u64, value: f32) {
+            if value != 0.0 {
+                self.optional_float(field, Some(value));
+            }
+        }
+
+        fn optional_float(&mut self, field: u64, value: Option<f32>) {
+            if let Some(value) = value {
+                self.key(field, FIXED32);
+                self.0.extend(value.to_le_bytes());
+            }
+        }
+
+        /// A packed repeated float field.
+        fn floats(&mut self, field: u64, values: &[f32]) {
+            if !values.is_empty() {
+                self.bytes(field, &values.iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<u8>>());
+            }
+        }
+
+        fn string(&mut self, field: u64, s: &str) {
+            if !s.is_empty() {
+                self.bytes(field, s.as_bytes());
+            }
+        }
+
+        fn bytes(&mut self, field: u64, bytes: &[u8]) {
+            self.key(field, LENGTH_DELIMITED);
+            self.varint(bytes.len() as u64);
+            self.0.extend(bytes);
+        }
+
+        /// An embedded message, written even when empty so its presence is kept.
+        fn message(&mut self, field: u64, message: Message) {
+            self.bytes(field, &message.0);
+        }
+    }
+
+    pub fn encode(show: &Show) -> Result<Vec<u8>> {
+        let mut message = Message::default();
+        message.string(1, &show.version);
+        message.float(2, show.default_position_rate);
+        message.float(3, show.default_color_rate);
+        for performance in &show.performances {
+            message.message(4, encode_performance(performance));
+        }
+        if let Some(metadata) = &show.metadata {
+            let mut encoded = Message::default();
+            if let Some(provenance) = &metadata.provenance {
+                let mut inner = Message::default();
+                inner.string(1, &provenance.tool);
+                inner.string(2, &provenance.input);
+                inner.string(3, &provenance.input_sha256);
+                for transform in &provenance.transforms {
+                    inner.bytes(4, transform.as_bytes());
+                }
+                if let Some(converted_at) = &provenance.converted_at {
+                    inner.bytes(5, converted_at.as_bytes());
+                }
+                encoded.message(1, inner);
+            }
+            if let Some(timecode) = &metadata.timecode {
+                let mut inner = Message::default();
+                inner.string(1, &timecode.start);
+                inner.uint(2, timecode.fps as u64);
+                encoded.message(2, inner);
+            }
+            for cue in metadata.cues.iter().flatten() {
+                let mut inner = Message::default();
+                inner.float(1, cue.time);
+                inner.string(2, &cue.label);
+                encoded.message(3, inner);
+            }
+            for act in metadata.acts.iter().flatten() {
+                let mut inner = Message::default();
+                inner.string(1, &act.name);
+                inner.float(2, act.start);
+                inner.float(3, act.end);
+                inner.string(4, &act.source);
+                encoded.message(4, inner);
+            }
+            if let Some(preview) = &metadata.preview {
+                let mut inner = Message::default();
+                inner.uint(1, preview.decimate as u64);
+                encoded.message(5, inner);
+            }
+            message.message(5, encoded);
+        }
+        Ok(message.0)
+    }
+
+    fn encode_performance(performance: &Performance) -> Message {
+        let mut message = Message::default();
+        message.uint(1, performance.id as u64);
+        let description = &performance.description;
+        let mut encoded = Message::default();
+        encoded.float(1, description.home_x);
+        encoded.float(2, description.home_y);
+        encoded.float(3, description.home_z);
+        for traversal in &description.traversals.0 {
+            encoded.message(4, encode_traversal(traversal));
+        }
+        message.message(2, encoded);
+        for payload in &performance.payload {
+            message.message(3, encode_payload(payload));
+        }
+        message
+    }
+
+    fn encode_traversal(traversal: &AgentTraversal) -> Message {
+        let mut message = Message::default();
+        message.float(1, traversal.dx);
+        message.float(2, traversal.dy);
+        message.float(3, traversal.dz);
+        message.optional_float(4, traversal.dt);
+        message.optional_uint(5, traversal.frames.map(u64::from));
+        if let Some(points) = traversal.control_points {
+            message.floats(6, &points.concat());
+        }
+        message
+    }
+
+    fn encode_payload(payload: &Payload) -> Message {
+        let mut message = Message::default();
+        message.uint(1, payload.id as u64);
+        message.string(2, &payload.payload_type);
+        for action in &payload.actions {
+            let mut encoded = Message::default();
+            match action {
+                PayloadAction::Color(color) => {
+                    let mut inner = Message::default();
+                    inner.uint(1, color.r as u64);
+                    inner.uint(2, color.g as u64);
+                    inner.uint(3, color.b as u64);
+                    inner.optional_uint(4, color.frames.map(u64::from));
+                    encoded.message(1, inner);
+                }
+                PayloadAction::Event(event) => {
+                    let mut inner = Message::default();
+                    inner.float(1, event.time);
+                    inner.string(2, &event.action);
+                    encoded.message(2, inner);
+                }
+            }
+            message.message(3, encoded);
+        }
+        message
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{encode, encode_payload, encode_traversal, Message};
+        use crate::{AgentTraversal, Payload, Show};
+
+        #[test]
+        fn varints() {
+            let encoded = |n: u64| {
+                let mut message = Message::default();
+                message.varint(n);
+                message.0
+            };
+            assert_eq!(encoded(0), [0x00]);
+            assert_eq!(encoded(1), [0x01]);
+            assert_eq!(encoded(127), [0x7f]);
+            assert_eq!(encoded(128), [0x80, 0x01]);
+            assert_eq!(encoded(300), [0xac, 0x02]);
+            assert_eq!(encoded(u64::MAX), [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        }
+
+        #[test]
+        fn show() {
+            let show: Show = serde_json::from_str(r#"{
+                "version": "1.0", "defaultPositionRate": 4.0, "defaultColorRate": 0.0,
+                "performances": [{
+                    "id": 0,
+                    "agentDescription": { "homeX": 1.0, "homeY": 0.0, "homeZ": 0.0, "agentTraversal": [{ "dx": 0.0, "dy": 0.0, "dz": 0.0, "dt": 0.0 }] },
+                    "payloadDescription": []
+                }]
+            }"#).unwrap();
+            // Zero ids and rates are left out, but an explicit zero `dt` is kept.
+            assert_eq!(encode(&show).unwrap(), [
+                0x0a, 0x03, b'1', b'.', b'0',
+                0x15, 0x00, 0x00, 0x80, 0x40,
+                0x22, 0x0e,
+                    0x12, 0x0c,
+                        0x0d, 0x00, 0x00, 0x80, 0x3f,
+                        0x22, 0x05,
+                            0x25, 0x00, 0x00, 0x00, 0x00
+            ]);
+        }
+
+        #[test]
+        fn traversal_with_frames_and_control_points() {
+            let traversal: AgentTraversal = serde_json::from_str(
+                r#"{ "dx": 0.5, "dy": 0.0, "dz": 0.0, "frames": 300, "controlPoints": [[1.0, 0.0, 0.0], [0.0, 0.0, 2.0]] }"#
+            ).unwrap();
+            assert_eq!(encode_traversal(&traversal).0, [
+                0x0d, 0x00, 0x00, 0x00, 0x3f,
+                0x28, 0xac, 0x02,
+                0x32, 0x18,
+                    0x00, 0x00, 0x80, 0x3f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40
+            ]);
+        }
+
+        #[test]
+        fn payload_actions() {
+            let payload: Payload = serde_json::from_str(
+                r#"{ "id": 1, "type": "light", "payloadActions": [{ "r": 255, "g": 0, "b": 0, "frames": 4 }, { "time": 1.5, "action": "go" }] }"#
+            ).unwrap();
+            assert_eq!(encode_payload(&payload).0, [
+                0x08, 0x01,
+                0x12, 0x05, b'l', b'i', b'g', b'h', b't',
+                0x1a, 0x07,
+                    0x0a, 0x05, 0x08, 0xff, 0x01, 0x20, 0x04,
+                0x1a, 0x0b,
+                    0x12, 0x09, 0x0d, 0x00, 0x00, 0xc0, 0x3f, 0x12, 0x02, b'g', b'o'
+            ]);
+        }
+
+        #[test]
+        fn metadata() {
+            let show: Show = serde_json::from_str(r#"{
+                "version": "", "defaultPositionRate": 0.0, "defaultColorRate": 0.0, "performances": [],
+                "metadata": { "timecode": { "start": "01:00:00:00", "fps": 25 }, "preview": { "decimate": 10 } }
+            }"#).unwrap();
+            let mut expected = vec![0x2a, 0x15, 0x12, 0x0f, 0x0a, 0x0b];
+            expected.extend(b"01:00:00:00");
+            expected.extend([0x10, 0x19, 0x2a, 0x02, 0x08, 0x0a]);
+            assert_eq!(encode(&show).unwrap(), expected);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Where a show came from, embedded with `--embed-provenance` so a stray .vviz can be
+/// traced back to its source archive and conversion settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    pub tool: String,
+    pub input: String,
+    #[serde(rename = "inputSha256")]
+    pub input_sha256: String,
+    pub transforms: Vec<String>,
+    #[serde(rename = "convertedAt", default, skip_serializing_if = "Option::is_none")]
+    pub converted_at: Option<String>
+}
+
+impl Provenance {
+
+    /// `timestamp` is left out for reproducible output.
+    pub fn new(source: &str, input_sha256: String, transforms: Vec<String>, timestamp: bool) -> Self {
+        Provenance {
+            tool: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            input: source.to_string(),
+            input_sha256,
+            transforms,
+            converted_at: timestamp.then(|| rfc3339(SystemTime::now()))
+        }
+    }
+}
+
+/// UTC timestamp such as `2024-05-01T12:30:00Z`.
+pub fn rfc3339(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()) as i64;
+    let (days, secs) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, secs / 3600, secs % 3600 / 60, secs % 60
+    )
+}
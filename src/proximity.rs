@@ -0,0 +1,57 @@
+use std::{io::Write, path::Path};
+
+use crate::{error::{self, Result}, track::{distance, Track}, Show};
+
+/// Closest pair among `points` (drone number, position) by a sweep along x.
+fn closest_pair(points: &mut [(usize, [f32; 3])]) -> Option<(f32, usize, usize)> {
+    points.sort_by(|a, b| a.1[0].total_cmp(&b.1[0]));
+    let mut best: Option<(f32, usize, usize)> = None;
+    for i in 0..points.len() {
+        for j in i + 1..points.len() {
+            if best.is_some_and(|(d, _, _)| points[j].1[0] - points[i].1[0] >= d) {
+                break;
+            }
+            let d = distance(points[i].1, points[j].1);
+            if best.is_none_or(|(best, _, _)| d < best) {
+                let (a, b) = (points[i].0.min(points[j].0), points[i].0.max(points[j].0));
+                best = Some((d, a, b));
+            }
+        }
+    }
+    best
+}
+
+/// Writes the minimum pairwise distance and the pair involved at every position frame of
+/// the show as `time,min_distance,drone_a,drone_b` (seconds, metres, drone numbers).
+pub fn write_report(show: &Show, fname: &Path) -> Result<()> {
+    let tracks: Vec<(usize, Track)> = show.performances.iter()
+        .map(|p| (p.id + 1, Track::new(p, show.default_position_rate)))
+        .collect();
+    let duration = tracks.iter().map(|(_, track)| track.duration()).fold(0.0, f32::max);
+    let step = 1.0 / show.default_position_rate;
+
+    let mut report = std::fs::File::create(fname).map_err(error::io("Failed to create proximity report"))?;
+    writeln!(report, "time,min_distance,drone_a,drone_b").map_err(error::io("Failed to write proximity report"))?;
+
+    let mut overall: Option<(f32, f32, usize, usize)> = None;
+    let mut frame = 0;
+    loop {
+        let t = frame as f32 * step;
+        if t > duration + step / 2.0 {
+            break;
+        }
+        let mut points: Vec<(usize, [f32; 3])> = tracks.iter().map(|(id, track)| (*id, track.position_at(t))).collect();
+        if let Some((d, a, b)) = closest_pair(&mut points) {
+            writeln!(report, "{:.3},{:.3},{},{}", t, d, a, b).map_err(error::io("Failed to write proximity report"))?;
+            if overall.is_none_or(|(best, _, _, _)| d < best) {
+                overall = Some((d, t, a, b));
+            }
+        }
+        frame += 1;
+    }
+
+    if let Some((d, t, a, b)) = overall {
+        println!("Closest approach {:.3} m between drones {} and {} at {:.3} s", d, a, b, t);
+    }
+    Ok(())
+}
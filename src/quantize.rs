@@ -0,0 +1,42 @@
+//! `--quantize`: positions snapped to a grid, so the show's numbers print in a few digits.
+
+use crate::{columns, Show};
+
+/// Parses the grid step (`1cm`, `5mm`, `0.01`), in meters.
+pub fn parse_step(s: &str) -> Result<f32, String> {
+    let step = columns::parse_length(s)?;
+    if step <= 0.0 || !step.is_finite() {
+        return Err(format!("the grid step must be positive, found '{}'", s));
+    }
+    Ok(step)
+}
+
+/// Snaps every position of the show to multiples of `step` meters. Each traversal moves
+/// between snapped positions along the exact trajectory, so rounding errors don't add up
+/// from one traversal to the next; Bézier control points, being relative to their
+/// traversal, are snapped on their own.
+pub fn apply(show: &mut Show, step: f32) {
+    let step = step as f64;
+    let cell = |value: f64| (value / step).round();
+    for performance in show.performances.iter_mut() {
+        let description = &mut performance.description;
+        let mut exact = [description.home_x as f64, description.home_y as f64, description.home_z as f64];
+        let mut cells = exact.map(cell);
+        description.home_x = (cells[0] * step) as f32;
+        description.home_y = (cells[1] * step) as f32;
+        description.home_z = (cells[2] * step) as f32;
+        for traversal in description.traversals.0.iter_mut() {
+            exact[0] += traversal.dx as f64;
+            exact[1] += traversal.dy as f64;
+            exact[2] += traversal.dz as f64;
+            let next = exact.map(cell);
+            traversal.dx = ((next[0] - cells[0]) * step) as f32;
+            traversal.dy = ((next[1] - cells[1]) * step) as f32;
+            traversal.dz = ((next[2] - cells[2]) * step) as f32;
+            cells = next;
+            if let Some(points) = traversal.control_points.as_mut() {
+                *points = points.map(|point| point.map(|value| (cell(value as f64) * step) as f32));
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! `csv2vviz reassign`: performances traded between drones that stay on their own pads, to
+//! spread flight hours across airframes.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use clap::Args;
+
+use crate::{error::{Error, Result}, read_show, swap::move_start, write_show};
+
+#[derive(Args, Debug)]
+pub struct ReassignArgs {
+
+    /// Converted show to change
+    show: PathBuf,
+
+    /// `A,B` drone numbers that trade performances. Repeat for several pairs
+    #[arg(long, value_parser = parse_pair)]
+    swap: Vec<(usize, usize)>,
+
+    /// Drone numbers (`1-10`, `3,7,12-15`) that pass their performances along: each drone
+    /// flies the next one's, and the last the first's. Repeat for several groups
+    #[arg(long, value_parser = parse_group)]
+    rotate_group: Vec<Vec<usize>>,
+
+    /// Output file (defaults to `<show>.reassigned.vviz`)
+    #[arg(short, long)]
+    output: Option<PathBuf>
+}
+
+fn parse_pair(s: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("expected two different drone numbers A,B, got '{}'", s);
+    let (a, b) = s.split_once(',').ok_or_else(invalid)?;
+    let number = |n: &str| n.trim().parse::<usize>().ok().filter(|n| *n > 0).ok_or_else(invalid);
+    let (a, b) = (number(a)?, number(b)?);
+    if a == b {
+        return Err(invalid());
+    }
+    Ok((a, b))
+}
+
+fn parse_group(s: &str) -> Result<Vec<usize>, String> {
+    let invalid = |part: &str| format!("invalid drone number or range '{}'", part);
+    let number = |n: &str| n.trim().parse::<usize>().ok().filter(|n| *n > 0);
+    let mut group = vec![];
+    for part in s.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => match (number(first), number(last)) {
+                (Some(first), Some(last)) if first <= last => group.extend(first..=last),
+                _ => return Err(invalid(part))
+            },
+            None => group.push(number(part).ok_or_else(|| invalid(part))?)
+        }
+    }
+    if group.len() < 2 {
+        return Err(format!("a group needs at least two drones, got '{}'", s));
+    }
+    Ok(group)
+}
+
+pub fn run(args: ReassignArgs) -> Result<()> {
+    if args.swap.is_empty() && args.rotate_group.is_empty() {
+        return Err(Error::Parse("Nothing to reassign: pass --swap or --rotate-group".to_string()));
+    }
+    let mut show = read_show(&args.show)?;
+
+    // Drone number to the drone whose performance it takes over.
+    let mut sources: HashMap<usize, usize> = HashMap::new();
+    let mut take = |drone: usize, source: usize| {
+        if sources.insert(drone, source).is_some() {
+            return Err(Error::Parse(format!("Drone {} is reassigned more than once", drone)));
+        }
+        Ok(())
+    };
+    for (a, b) in &args.swap {
+        take(*a, *b)?;
+        take(*b, *a)?;
+    }
+    for group in &args.rotate_group {
+        for (index, drone) in group.iter().enumerate() {
+            take(*drone, group[(index + 1) % group.len()])?;
+        }
+    }
+    if let Some(missing) = sources.keys().find(|drone| !show.performances.iter().any(|performance| performance.id + 1 == **drone)) {
+        return Err(Error::Parse(format!("{} has no drone {}", args.show.display(), missing)));
+    }
+
+    let originals = show.performances.clone();
+    for performance in show.performances.iter_mut() {
+        let drone = performance.id + 1;
+        let Some(source) = sources.get(&drone) else {
+            continue;
+        };
+        let description = &performance.description;
+        let home = [description.home_x, description.home_y, description.home_z];
+        *performance = originals.iter().find(|original| original.id + 1 == *source).unwrap().clone();
+        performance.id = drone - 1;
+        let moved = move_start(performance, home);
+        println!("Drone {} flies drone {}'s performance from its own home, {:.2} m from the original start", drone, source, moved);
+    }
+
+    let output = args.output.unwrap_or_else(|| args.show.with_extension("reassigned.vviz"));
+    println!("Generating {}", output.to_str().unwrap());
+    write_show(&show, &output)
+}
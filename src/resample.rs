@@ -0,0 +1,90 @@
+//! `csv2vviz resample`: a converted show re-timed for other position and color rates.
+
+use std::path::PathBuf;
+
+use clap::{ArgGroup, Args};
+
+use crate::{
+    error::{Error, Result}, frames::{self, TraversalMode}, lights::{self, Rgb}, read_show, track::Track,
+    write_show, AgentTraversals, ColorAction, PayloadAction, Performance, Sample
+};
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("rates").args(["position_rate", "color_rate"]).required(true).multiple(true)))]
+pub struct ResampleArgs {
+
+    /// Converted show to re-time
+    show: PathBuf,
+
+    /// New position samples per second
+    #[arg(long)]
+    position_rate: Option<f32>,
+
+    /// New color frames per second
+    #[arg(long)]
+    color_rate: Option<f32>,
+
+    /// Output file (defaults to `<show>.resampled.vviz`)
+    #[arg(short, long)]
+    output: Option<PathBuf>
+}
+
+pub fn run(args: ResampleArgs) -> Result<()> {
+    for (name, rate) in [("--position-rate", args.position_rate), ("--color-rate", args.color_rate)] {
+        if rate.is_some_and(|rate| !(rate > 0.0 && rate.is_finite())) {
+            return Err(Error::Parse(format!("{} must be positive", name)));
+        }
+    }
+    let mut show = read_show(&args.show)?;
+    let in_frames = show.version == TraversalMode::Frames.version();
+
+    if let Some(rate) = args.position_rate {
+        for performance in show.performances.iter_mut() {
+            performance.description.traversals = positions(performance, show.default_position_rate, rate, in_frames);
+        }
+        println!("Resampled positions from {} to {} /s", show.default_position_rate, rate);
+        show.default_position_rate = rate;
+    }
+
+    if let Some(rate) = args.color_rate {
+        for payload in show.performances.iter_mut().flat_map(|performance| performance.payload.iter_mut()) {
+            if !payload.actions.is_empty() && payload.actions.iter().all(|action| matches!(action, PayloadAction::Color(_))) {
+                payload.actions = colors(&payload.actions, show.default_color_rate, rate).into_iter().map(PayloadAction::Color).collect();
+            }
+        }
+        println!("Resampled colors from {} to {} /s", show.default_color_rate, rate);
+        show.default_color_rate = rate;
+    }
+
+    let output = args.output.unwrap_or_else(|| args.show.with_extension("resampled.vviz"));
+    println!("Generating {}", output.to_str().unwrap());
+    write_show(&show, &output)
+}
+
+/// The performance's trajectory sampled every `1 / rate` seconds, in the show's traversal
+/// encoding. Bézier traversals become straight ones along their curve.
+fn positions(performance: &Performance, old_rate: f32, rate: f32, in_frames: bool) -> AgentTraversals {
+    let samples: Vec<Sample> = Track::new(performance, old_rate).samples.into_iter()
+        .map(|(t, [x, y, z])| Sample { t: t as f64 * 1000.0, pos: [x, z, y], rgb: [0; 3] })
+        .collect();
+    let traversals = frames::resample(&samples, rate).into();
+    if in_frames { frames::to_frames(traversals, rate) } else { traversals }
+}
+
+/// Color actions re-timed onto frames at `rate`, each frame holding the color shown at its
+/// start at the old rate.
+fn colors(actions: &[PayloadAction], old_rate: f32, rate: f32) -> Vec<ColorAction> {
+    let mut old_frames = vec![];
+    for action in actions {
+        if let PayloadAction::Color(color) = action {
+            old_frames.extend(std::iter::repeat_n(Rgb { r: color.r, g: color.g, b: color.b }, color.frames.unwrap_or(1) as usize));
+        }
+    }
+    let frames = (old_frames.len() as f64 / old_rate as f64 * rate as f64 - 1e-6).ceil().max(1.0) as usize;
+    let mut resampled = vec![];
+    for frame in 0..frames {
+        let old_frame = (frame as f64 * old_rate as f64 / rate as f64 + 1e-6).floor() as usize;
+        lights::push_frame(&mut resampled, old_frames[old_frame.min(old_frames.len() - 1)]);
+    }
+    resampled
+}
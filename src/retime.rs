@@ -0,0 +1,158 @@
+use crate::{track::distance, Drone, Sample};
+
+/// A re-timing of the whole show: the sample times of every drone, merged, and how much each
+/// interval between two of them is stretched. Drones move in a straight line within an
+/// interval, so stretching it slows them all alike and keeps them in step.
+struct Warp {
+    times: Vec<f64>,
+    stretch: Vec<f64>
+}
+
+impl Warp {
+
+    fn new(drones: &[Drone]) -> Self {
+        let mut times: Vec<f64> = drones.iter().flat_map(|(_, samples)| samples.iter().map(|sample| sample.t)).collect();
+        times.sort_by(f64::total_cmp);
+        times.dedup();
+        let stretch = vec![1.0; times.len().saturating_sub(1)];
+        Warp { times, stretch }
+    }
+
+    /// Stretches the show from `start` to `end` (ms) by at least `factor`.
+    fn require(&mut self, start: f64, end: f64, factor: f64) {
+        let (from, to) = (self.times.partition_point(|t| *t < start), self.times.partition_point(|t| *t < end));
+        for stretch in &mut self.stretch[from..to] {
+            *stretch = stretch.max(factor);
+        }
+    }
+
+    /// Moves every sample to its re-timed time. Returns how many seconds longer the show is.
+    fn apply(&self, drones: &mut [Drone]) -> f64 {
+        let mut warped = Vec::with_capacity(self.times.len());
+        for (i, time) in self.times.iter().enumerate() {
+            warped.push(match i {
+                0 => *time,
+                _ => warped[i - 1] + (time - self.times[i - 1]) * self.stretch[i - 1]
+            });
+        }
+        for (_, samples) in drones.iter_mut() {
+            for sample in samples.iter_mut() {
+                sample.t = warped[self.times.partition_point(|t| *t < sample.t)];
+            }
+        }
+        match (self.times.first(), self.times.last(), warped.last()) {
+            (Some(first), Some(last), Some(end)) => (end - first - (last - first)) / 1000.0,
+            _ => 0.0
+        }
+    }
+}
+
+/// Stretches the show's timeline wherever a drone flies a segment faster than `max_speed`
+/// (m/s), so that segment is flown exactly at the limit and every other drone slows down
+/// with it. Prints each segment that was too fast and returns how many there were.
+pub fn clamp_speed(drones: &mut [Drone], max_speed: f32) -> usize {
+    let mut warp = Warp::new(drones);
+    let mut modified = 0;
+    for (drone_id, samples) in drones.iter() {
+        for (i, w) in samples.windows(2).enumerate() {
+            let dt = ((w[1].t - w[0].t) / 1000.0) as f32;
+            let length = distance(w[0].pos, w[1].pos);
+            if dt > 0.0 && length / dt > max_speed {
+                let stretched = length / max_speed;
+                println!(
+                    "Drone {} segment {} (t={:.3} s): {:.2} m/s, dt {:.3} s -> {:.3} s",
+                    drone_id, i + 1, w[0].t / 1000.0, length / dt, dt, stretched
+                );
+                warp.require(w[0].t, w[1].t, (stretched / dt) as f64);
+                modified += 1;
+            }
+        }
+    }
+    if modified > 0 {
+        println!("The show runs {:.3} s longer, with every drone slowed alike", warp.apply(drones));
+    }
+    modified
+}
+
+/// Passes of [`limit_accel`] at most: slowing the show for one drone changes the others'
+/// speeds, which may need another look.
+const ACCEL_PASSES: usize = 8;
+
+/// Stretches the show's timeline so that no drone's speed changes or turns need more than
+/// `max_accel` (m/s²), with every drone slowed alike to keep them in step. Each drone's
+/// segments are timed by a trapezoidal speed profile along its path, starting and ending at
+/// rest, and only ever slowed down. Returns how many segments were slowed.
+pub fn limit_accel(drones: &mut [Drone], max_accel: f32) -> usize {
+    let (mut modified, mut added) = (0, 0.0);
+    for _ in 0..ACCEL_PASSES {
+        let mut warp = Warp::new(drones);
+        let mut slowed = 0;
+        for (_, samples) in drones.iter() {
+            for (i, factor) in profile(samples, max_accel).into_iter().enumerate() {
+                if factor > 1.0001 {
+                    warp.require(samples[i].t, samples[i + 1].t, factor as f64);
+                    slowed += 1;
+                }
+            }
+        }
+        if slowed == 0 {
+            break;
+        }
+        modified += slowed;
+        added += warp.apply(drones);
+    }
+    if modified > 0 {
+        println!("The show runs {:.3} s longer, with every drone slowed alike", added);
+    }
+    modified
+}
+
+/// How much each segment of the drone has to be slowed down for `max_accel` (m/s²).
+fn profile(samples: &[Sample], max_accel: f32) -> Vec<f32> {
+    if samples.len() < 2 {
+        return vec![];
+    }
+    let positions: Vec<[f32; 3]> = samples.iter().map(|sample| sample.pos).collect();
+    let lengths: Vec<f32> = positions.windows(2).map(|w| distance(w[0], w[1])).collect();
+    let durations: Vec<f32> = samples.windows(2).map(|w| ((w[1].t - w[0].t) / 1000.0) as f32).collect();
+    let mut speeds: Vec<f32> = lengths.iter().zip(&durations)
+        .map(|(d, dt)| if *dt > 0.0 { d / dt } else { 0.0 })
+        .collect();
+
+    // Turning at a vertex needs a velocity change of 2 v sin(θ/2) over roughly half of
+    // each adjacent segment.
+    for i in 1..speeds.len() {
+        if lengths[i - 1] <= 0.0 || lengths[i] <= 0.0 {
+            continue;
+        }
+        let (a, b, c) = (positions[i - 1], positions[i], positions[i + 1]);
+        let (u, v) = ([b[0] - a[0], b[1] - a[1], b[2] - a[2]], [c[0] - b[0], c[1] - b[1], c[2] - b[2]]);
+        let cos = ((u[0] * v[0] + u[1] * v[1] + u[2] * v[2]) / (lengths[i - 1] * lengths[i])).clamp(-1.0, 1.0);
+        let half_turn = (cos.acos() / 2.0).sin();
+        if half_turn > 1e-6 {
+            let cap = (max_accel * (lengths[i - 1] + lengths[i]) / (4.0 * half_turn)).sqrt();
+            speeds[i - 1] = speeds[i - 1].min(cap);
+            speeds[i] = speeds[i].min(cap);
+        }
+    }
+
+    // Forward then backward pass: consecutive segment speeds may only differ by what
+    // max_accel allows over the distance between their midpoints.
+    let mut previous = (0.0f32, 0.0f32);
+    for i in 0..speeds.len() {
+        speeds[i] = speeds[i].min((previous.0.powi(2) + max_accel * (previous.1 + lengths[i])).sqrt());
+        previous = (speeds[i], lengths[i]);
+    }
+    let mut next = (0.0f32, 0.0f32);
+    for i in (0..speeds.len()).rev() {
+        speeds[i] = speeds[i].min((next.0.powi(2) + max_accel * (next.1 + lengths[i])).sqrt());
+        next = (speeds[i], lengths[i]);
+    }
+
+    lengths.iter().zip(&durations).zip(&speeds)
+        .map(|((length, dt), speed)| match *length > 0.0 && *speed > 0.0 && *dt > 0.0 {
+            true => (length / speed / dt).max(1.0),
+            false => 1.0
+        })
+        .collect()
+}
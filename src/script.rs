@@ -0,0 +1,664 @@
+//! Per-sample scripts for `--script`, in a small subset of Rhai. A script runs once for every
+//! sample with `drone_id`, `t` (seconds on the input's clock), the position `x`, `y`, `z`
+//! (east, north, up meters) and the CSV color `r`, `g`, `b`, and whatever it leaves in the
+//! position variables is kept. The color is read-only, as the .vviz light program doesn't
+//! come from the CSV colors:
+//!
+//! ```rhai
+//! // Raise drones 40 to 60 by 2 m after 90 s.
+//! if drone_id in 40..=60 && t > 90 {
+//!     z += 2;
+//! }
+//! ```
+//!
+//! Supported: `let`, `=`, `+=`, `-=`, `*=`, `/=`, `if`/`else`, arithmetic (`+ - * / %`),
+//! comparisons, `&&`, `||`, `!`, ranges (`in a..b`, `in a..=b`), `true`/`false`, `//`
+//! comments and the functions `abs`, `min`, `max`, `clamp`, `floor`, `ceil`, `round`, `sqrt`,
+//! `sin` and `cos` (radians). Every number is a float.
+
+use std::path::Path;
+
+use crate::{error::{self, Error, Result}, Drone, Sample};
+
+/// Variables a script starts with, in slot order. The first five can't be assigned.
+const INPUTS: [&str; 8] = ["drone_id", "t", "r", "g", "b", "x", "y", "z"];
+const READ_ONLY: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    Number(f64),
+    Bool(bool)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Add, Sub, Mul, Div, Rem,
+    Lt, Le, Gt, Ge, Eq, Ne,
+    And, Or
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Function {
+    Abs, Min, Max, Clamp, Floor, Ceil, Round, Sqrt, Sin, Cos
+}
+
+impl Function {
+
+    fn from_name(name: &str) -> Option<(Function, usize)> {
+        Some(match name {
+            "abs" => (Function::Abs, 1),
+            "min" => (Function::Min, 2),
+            "max" => (Function::Max, 2),
+            "clamp" => (Function::Clamp, 3),
+            "floor" => (Function::Floor, 1),
+            "ceil" => (Function::Ceil, 1),
+            "round" => (Function::Round, 1),
+            "sqrt" => (Function::Sqrt, 1),
+            "sin" => (Function::Sin, 1),
+            "cos" => (Function::Cos, 1),
+            _ => return None
+        })
+    }
+
+    fn call(self, args: &[f64]) -> f64 {
+        match self {
+            Function::Abs => args[0].abs(),
+            Function::Min => args[0].min(args[1]),
+            Function::Max => args[0].max(args[1]),
+            Function::Clamp => args[0].max(args[1]).min(args[2]),
+            Function::Floor => args[0].floor(),
+            Function::Ceil => args[0].ceil(),
+            Function::Round => args[0].round(),
+            Function::Sqrt => args[0].sqrt(),
+            Function::Sin => args[0].sin(),
+            Function::Cos => args[0].cos()
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Expr {
+    Literal(Value),
+    Variable(usize),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+    /// `value in start..end`, including `end` when the flag is set.
+    InRange(Box<Expr>, Box<Expr>, Box<Expr>, bool),
+    Call(Function, Vec<Expr>)
+}
+
+#[derive(Debug)]
+enum Statement {
+    /// `slot = value`, or `slot op= value`.
+    Assign(usize, Option<Operator>, Expr),
+    If(Expr, Vec<Statement>, Vec<Statement>)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Punct(&'static str)
+}
+
+/// Longest first, so `..=` isn't read as `..` and `=`.
+const PUNCTUATION: [&str; 27] = [
+    "..=", "..", "&&", "||", "==", "!=", "<=", ">=", "+=", "-=", "*=", "/=",
+    "+", "-", "*", "/", "%", "<", ">", "!", "=", "(", ")", "{", "}", ",", ";"
+];
+
+/// Tokens with the line each starts on.
+fn tokenize(source: &str) -> std::result::Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let (mut tokens, mut pos, mut line) = (vec![], 0, 1);
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c == '\n' {
+            line += 1;
+            pos += 1;
+        } else if c.is_whitespace() {
+            pos += 1;
+        } else if c == '/' && chars.get(pos + 1) == Some(&'/') {
+            while pos < chars.len() && chars[pos] != '\n' {
+                pos += 1;
+            }
+        } else if c.is_ascii_digit() {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '_') {
+                pos += 1;
+            }
+            // `40..60` is a range, not the number `40.`.
+            if chars.get(pos) == Some(&'.') && chars.get(pos + 1).is_some_and(char::is_ascii_digit) {
+                pos += 1;
+                while pos < chars.len() && chars[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+            }
+            if matches!(chars.get(pos), Some('e' | 'E')) {
+                let sign = usize::from(matches!(chars.get(pos + 1), Some('+' | '-')));
+                if chars.get(pos + 1 + sign).is_some_and(char::is_ascii_digit) {
+                    pos += 1 + sign;
+                    while pos < chars.len() && chars[pos].is_ascii_digit() {
+                        pos += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..pos].iter().filter(|c| **c != '_').collect();
+            let number = text.parse().map_err(|_| format!("line {}: invalid number '{}'", line, text))?;
+            tokens.push((Token::Number(number), line));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            tokens.push((Token::Ident(chars[start..pos].iter().collect()), line));
+        } else {
+            let rest: String = chars[pos..chars.len().min(pos + 3)].iter().collect();
+            let Some(punct) = PUNCTUATION.iter().find(|punct| rest.starts_with(**punct)) else {
+                return Err(format!("line {}: unexpected '{}'", line, c));
+            };
+            tokens.push((Token::Punct(punct), line));
+            pos += punct.len();
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// Variable names by slot, starting with the inputs.
+    names: Vec<String>
+}
+
+type ParseResult<T> = std::result::Result<T, String>;
+
+impl Parser {
+
+    fn error<T>(&self, message: &str) -> ParseResult<T> {
+        let line = self.tokens.get(self.pos).or(self.tokens.last()).map_or(1, |(_, line)| *line);
+        Err(format!("line {}: {}", line, message))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(known)) if *known == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(name)) if name == keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> ParseResult<()> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", punct))
+        }
+    }
+
+    fn ident(&mut self) -> ParseResult<String> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => self.error("expected a name")
+        }
+    }
+
+    fn slot(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|known| known == name)
+    }
+
+    fn statements(&mut self, until_brace: bool) -> ParseResult<Vec<Statement>> {
+        let mut statements = vec![];
+        loop {
+            match self.peek() {
+                None if until_brace => return self.error("expected '}'"),
+                None => return Ok(statements),
+                Some(Token::Punct("}")) if until_brace => {
+                    self.pos += 1;
+                    return Ok(statements);
+                }
+                Some(Token::Punct(";")) => self.pos += 1,
+                _ => statements.push(self.statement()?)
+            }
+        }
+    }
+
+    fn block(&mut self) -> ParseResult<Vec<Statement>> {
+        self.expect("{")?;
+        self.statements(true)
+    }
+
+    fn statement(&mut self) -> ParseResult<Statement> {
+        if self.eat_keyword("if") {
+            let condition = self.expr()?;
+            let then = self.block()?;
+            let otherwise = match self.eat_keyword("else") {
+                true if matches!(self.peek(), Some(Token::Ident(name)) if name == "if") => vec![self.statement()?],
+                true => self.block()?,
+                false => vec![]
+            };
+            return Ok(Statement::If(condition, then, otherwise));
+        }
+        let declare = self.eat_keyword("let");
+        let name = self.ident()?;
+        let operator = match self.peek() {
+            Some(Token::Punct("=")) => None,
+            Some(Token::Punct("+=")) if !declare => Some(Operator::Add),
+            Some(Token::Punct("-=")) if !declare => Some(Operator::Sub),
+            Some(Token::Punct("*=")) if !declare => Some(Operator::Mul),
+            Some(Token::Punct("/=")) if !declare => Some(Operator::Div),
+            _ => return self.error("expected '='")
+        };
+        self.pos += 1;
+        let slot = match (self.slot(&name), declare) {
+            (Some(slot), _) if slot < READ_ONLY => return self.error(&format!("'{}' can't be assigned", name)),
+            (Some(slot), _) => slot,
+            (None, true) => {
+                self.names.push(name);
+                self.names.len() - 1
+            }
+            (None, false) => return self.error(&format!("unknown variable '{}'", name))
+        };
+        let value = self.expr()?;
+        if !matches!(self.peek(), None | Some(Token::Punct("}"))) {
+            self.expect(";")?;
+        }
+        Ok(Statement::Assign(slot, operator, value))
+    }
+
+    fn expr(&mut self) -> ParseResult<Expr> {
+        let mut left = self.and()?;
+        while self.eat("||") {
+            left = Expr::Binary(Operator::Or, Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> ParseResult<Expr> {
+        let mut left = self.equality()?;
+        while self.eat("&&") {
+            left = Expr::Binary(Operator::And, Box::new(left), Box::new(self.equality()?));
+        }
+        Ok(left)
+    }
+
+    fn equality(&mut self) -> ParseResult<Expr> {
+        let mut left = self.comparison()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Punct("==")) => Operator::Eq,
+                Some(Token::Punct("!=")) => Operator::Ne,
+                _ => return Ok(left)
+            };
+            self.pos += 1;
+            left = Expr::Binary(operator, Box::new(left), Box::new(self.comparison()?));
+        }
+    }
+
+    fn comparison(&mut self) -> ParseResult<Expr> {
+        let left = self.additive()?;
+        if self.eat_keyword("in") {
+            let start = self.additive()?;
+            let inclusive = match self.peek() {
+                Some(Token::Punct("..")) => false,
+                Some(Token::Punct("..=")) => true,
+                _ => return self.error("expected '..' or '..='")
+            };
+            self.pos += 1;
+            let end = self.additive()?;
+            return Ok(Expr::InRange(Box::new(left), Box::new(start), Box::new(end), inclusive));
+        }
+        let operator = match self.peek() {
+            Some(Token::Punct("<")) => Operator::Lt,
+            Some(Token::Punct("<=")) => Operator::Le,
+            Some(Token::Punct(">")) => Operator::Gt,
+            Some(Token::Punct(">=")) => Operator::Ge,
+            _ => return Ok(left)
+        };
+        self.pos += 1;
+        Ok(Expr::Binary(operator, Box::new(left), Box::new(self.additive()?)))
+    }
+
+    fn additive(&mut self) -> ParseResult<Expr> {
+        let mut left = self.term()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Punct("+")) => Operator::Add,
+                Some(Token::Punct("-")) => Operator::Sub,
+                _ => return Ok(left)
+            };
+            self.pos += 1;
+            left = Expr::Binary(operator, Box::new(left), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> ParseResult<Expr> {
+        let mut left = self.unary()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Punct("*")) => Operator::Mul,
+                Some(Token::Punct("/")) => Operator::Div,
+                Some(Token::Punct("%")) => Operator::Rem,
+                _ => return Ok(left)
+            };
+            self.pos += 1;
+            left = Expr::Binary(operator, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> ParseResult<Expr> {
+        if self.eat("-") {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> ParseResult<Expr> {
+        match self.peek().cloned() {
+            Some(Token::Number(number)) => {
+                self.pos += 1;
+                Ok(Expr::Literal(Value::Number(number)))
+            }
+            Some(Token::Punct("(")) => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                match name.as_str() {
+                    "true" => return Ok(Expr::Literal(Value::Bool(true))),
+                    "false" => return Ok(Expr::Literal(Value::Bool(false))),
+                    _ => {}
+                }
+                if !self.eat("(") {
+                    return match self.slot(&name) {
+                        Some(slot) => Ok(Expr::Variable(slot)),
+                        None => self.error(&format!("unknown variable '{}'", name))
+                    };
+                }
+                let Some((function, arity)) = Function::from_name(&name) else {
+                    return self.error(&format!("unknown function '{}'", name));
+                };
+                let mut args = vec![];
+                while !self.eat(")") {
+                    if !args.is_empty() {
+                        self.expect(",")?;
+                    }
+                    args.push(self.expr()?);
+                }
+                if args.len() != arity {
+                    return self.error(&format!("{} takes {} arguments, found {}", name, arity, args.len()));
+                }
+                Ok(Expr::Call(function, args))
+            }
+            _ => self.error("expected a value")
+        }
+    }
+}
+
+type EvalResult<T> = std::result::Result<T, String>;
+
+fn number(value: Value) -> EvalResult<f64> {
+    match value {
+        Value::Number(n) => Ok(n),
+        Value::Bool(b) => Err(format!("expected a number, found {}", b))
+    }
+}
+
+fn boolean(value: Value) -> EvalResult<bool> {
+    match value {
+        Value::Bool(b) => Ok(b),
+        Value::Number(n) => Err(format!("expected true or false, found {}", n))
+    }
+}
+
+fn eval(expr: &Expr, slots: &[Option<Value>], names: &[String]) -> EvalResult<Value> {
+    let eval_number = |expr: &Expr| eval(expr, slots, names).and_then(number);
+    Ok(match expr {
+        Expr::Literal(value) => *value,
+        Expr::Variable(slot) => slots[*slot].ok_or_else(|| format!("'{}' is used before it is set", names[*slot]))?,
+        Expr::Negate(inner) => Value::Number(-eval_number(inner)?),
+        Expr::Not(inner) => Value::Bool(!eval(inner, slots, names).and_then(boolean)?),
+        Expr::Binary(Operator::And, left, right) => Value::Bool(
+            eval(left, slots, names).and_then(boolean)? && eval(right, slots, names).and_then(boolean)?
+        ),
+        Expr::Binary(Operator::Or, left, right) => Value::Bool(
+            eval(left, slots, names).and_then(boolean)? || eval(right, slots, names).and_then(boolean)?
+        ),
+        Expr::Binary(operator @ (Operator::Eq | Operator::Ne), left, right) => {
+            let equal = eval(left, slots, names)? == eval(right, slots, names)?;
+            Value::Bool(equal == (*operator == Operator::Eq))
+        }
+        Expr::Binary(operator, left, right) => apply(*operator, eval_number(left)?, eval_number(right)?),
+        Expr::InRange(value, start, end, inclusive) => {
+            let (value, start, end) = (eval_number(value)?, eval_number(start)?, eval_number(end)?);
+            Value::Bool(value >= start && (value < end || (*inclusive && value == end)))
+        }
+        Expr::Call(function, args) => {
+            let args = args.iter().map(eval_number).collect::<EvalResult<Vec<f64>>>()?;
+            Value::Number(function.call(&args))
+        }
+    })
+}
+
+/// An arithmetic or ordering operator on two numbers.
+fn apply(operator: Operator, left: f64, right: f64) -> Value {
+    match operator {
+        Operator::Add => Value::Number(left + right),
+        Operator::Sub => Value::Number(left - right),
+        Operator::Mul => Value::Number(left * right),
+        Operator::Div => Value::Number(left / right),
+        Operator::Rem => Value::Number(left % right),
+        Operator::Lt => Value::Bool(left < right),
+        Operator::Le => Value::Bool(left <= right),
+        Operator::Gt => Value::Bool(left > right),
+        Operator::Ge => Value::Bool(left >= right),
+        Operator::Eq | Operator::Ne | Operator::And | Operator::Or => unreachable!("handled by eval")
+    }
+}
+
+/// A script loaded from `--script`.
+#[derive(Debug)]
+pub struct Script {
+    name: String,
+    statements: Vec<Statement>,
+    names: Vec<String>
+}
+
+impl Script {
+
+    pub fn load(fname: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(fname).map_err(error::io("Failed to read script"))?;
+        Script::parse(fname.display().to_string(), &source)
+    }
+
+    /// The script in `source`; `name` stands for it in errors.
+    fn parse(name: String, source: &str) -> Result<Self> {
+        let invalid = |message: String| Error::Parse(format!("Invalid script {}: {}", name, message));
+        let tokens = tokenize(source).map_err(invalid)?;
+        let mut parser = Parser { tokens, pos: 0, names: INPUTS.iter().map(|name| name.to_string()).collect() };
+        let statements = parser.statements(false).map_err(invalid)?;
+        Ok(Script { name, statements, names: parser.names })
+    }
+
+    fn run(&self, statements: &[Statement], slots: &mut [Option<Value>]) -> EvalResult<()> {
+        for statement in statements {
+            match statement {
+                Statement::Assign(slot, None, value) => slots[*slot] = Some(eval(value, slots, &self.names)?),
+                Statement::Assign(slot, Some(operator), value) => {
+                    let current = slots[*slot].ok_or_else(|| format!("'{}' is used before it is set", self.names[*slot]))?;
+                    let value = eval(value, slots, &self.names)?;
+                    slots[*slot] = Some(apply(*operator, number(current)?, number(value)?));
+                }
+                Statement::If(condition, then, otherwise) => {
+                    match boolean(eval(condition, slots, &self.names)?)? {
+                        true => self.run(then, slots)?,
+                        false => self.run(otherwise, slots)?
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The sample as the script leaves it; a position that is not a finite number is an
+    /// error.
+    fn sample(&self, drone_id: usize, sample: &Sample, slots: &mut Vec<Option<Value>>) -> EvalResult<Sample> {
+        let [x, y, z] = sample.pos;
+        let [r, g, b] = sample.rgb;
+        let inputs = [drone_id as f64, sample.t / 1000.0, r as f64, g as f64, b as f64, x as f64, y as f64, z as f64];
+        slots.clear();
+        slots.extend(inputs.iter().map(|value| Some(Value::Number(*value))));
+        slots.resize(self.names.len(), None);
+        self.run(&self.statements, slots)?;
+
+        let output = |slot: usize| match slots[slot] {
+            Some(Value::Number(n)) if n.is_finite() => Ok(n),
+            Some(Value::Number(n)) => Err(format!("'{}' is left {}, not a finite number", self.names[slot], n)),
+            _ => Err(format!("'{}' must be left a number", self.names[slot]))
+        };
+        Ok(Sample {
+            pos: [output(5)? as f32, output(6)? as f32, output(7)? as f32],
+            ..*sample
+        })
+    }
+
+    /// Runs the script over every sample, returning how many samples it changed and in how
+    /// many drones.
+    pub fn apply(&self, drones: &mut [Drone]) -> Result<(usize, usize)> {
+        let (mut changed, mut touched) = (0, 0);
+        let mut slots = vec![];
+        for (drone_id, samples) in drones.iter_mut() {
+            let before = changed;
+            for sample in samples.iter_mut() {
+                let result = self.sample(*drone_id, sample, &mut slots).map_err(|e| Error::Parse(format!(
+                    "Script {} failed for drone {} at {:.3} s: {}", self.name, drone_id, sample.t / 1000.0, e
+                )))?;
+                if result != *sample {
+                    *sample = result;
+                    changed += 1;
+                }
+            }
+            touched += usize::from(changed > before);
+        }
+        Ok((changed, touched))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Script;
+    use crate::{error::Error, Sample};
+
+    fn script(source: &str) -> Script {
+        Script::parse("test.rhai".into(), source).unwrap()
+    }
+
+    fn invalid(source: &str) -> String {
+        match Script::parse("test.rhai".into(), source) {
+            Err(Error::Parse(message)) => message,
+            other => panic!("expected a parse error, got {:?}", other.map(|_| ()))
+        }
+    }
+
+    fn run(source: &str, drone_id: usize, sample: Sample) -> Result<Sample, String> {
+        script(source).sample(drone_id, &sample, &mut vec![])
+    }
+
+    fn at(t: f64) -> Sample {
+        Sample { t: t * 1000.0, pos: [1.0, 2.0, 3.0], rgb: [10, 20, 30] }
+    }
+
+    #[test]
+    fn readme_example() {
+        let source = "// Raise drones 40 to 60 by 2 m after 90 s.\nif drone_id in 40..=60 && t > 90 {\n    z += 2;\n}\n";
+        assert_eq!(run(source, 50, at(100.0)).unwrap().pos, [1.0, 2.0, 5.0]);
+        assert_eq!(run(source, 60, at(100.0)).unwrap().pos, [1.0, 2.0, 5.0]);
+        assert_eq!(run(source, 61, at(100.0)).unwrap().pos, [1.0, 2.0, 3.0]);
+        assert_eq!(run(source, 50, at(90.0)).unwrap().pos, [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let sample = run("x = 1 + 2 * 3; y = (1 + 2) * 3; z = -2 - 7 % 4 / 2", 1, at(0.0)).unwrap();
+        assert_eq!(sample.pos, [7.0, 9.0, -3.5]);
+        let sample = run("let a = 2; a *= 3; a -= 1; x = a / 2; y = 1_000; z = 1.5e1", 1, at(0.0)).unwrap();
+        assert_eq!(sample.pos, [2.5, 1000.0, 15.0]);
+    }
+
+    #[test]
+    fn functions() {
+        let sample = run("x = clamp(x * 10, 0, 4); y = max(abs(-3), min(2, 8)); z = sqrt(16) + round(0.4) + floor(-0.5) + ceil(0.2)", 1, at(0.0)).unwrap();
+        assert_eq!(sample.pos, [4.0, 3.0, 4.0]);
+        let sample = run("x = sin(0); y = cos(0)", 1, at(0.0)).unwrap();
+        assert_eq!(sample.pos, [0.0, 1.0, 3.0]);
+    }
+
+    #[test]
+    fn conditions() {
+        let source = "if drone_id in 1..3 { x = 1 } else if !(drone_id == 3) || false { x = 2 } else { x = 3 }";
+        let x = |drone_id| run(source, drone_id, at(0.0)).unwrap().pos[0];
+        assert_eq!([x(1), x(2), x(3), x(4)], [1.0, 1.0, 3.0, 2.0]);
+    }
+
+    #[test]
+    fn colors_are_read_only() {
+        assert_eq!(run("if r == 10 && b > g { x = 0 }", 1, at(0.0)).unwrap().pos, [0.0, 2.0, 3.0]);
+        assert_eq!(invalid("r = 300"), "Invalid script test.rhai: line 1: 'r' can't be assigned");
+    }
+
+    #[test]
+    fn parse_errors_name_their_line() {
+        assert_eq!(invalid("x = 1;\nt = 2;"), "Invalid script test.rhai: line 2: 't' can't be assigned");
+        assert_eq!(invalid("x = w"), "Invalid script test.rhai: line 1: unknown variable 'w'");
+        assert_eq!(invalid("x = tan(1)"), "Invalid script test.rhai: line 1: unknown function 'tan'");
+        assert_eq!(invalid("x = min(1)"), "Invalid script test.rhai: line 1: min takes 2 arguments, found 1");
+        assert_eq!(invalid("if true {\n x = 1"), "Invalid script test.rhai: line 2: expected '}'");
+        assert_eq!(invalid("x = 1 $"), "Invalid script test.rhai: line 1: unexpected '$'");
+    }
+
+    #[test]
+    fn evaluation_errors() {
+        assert_eq!(run("x = true", 1, at(0.0)).unwrap_err(), "'x' must be left a number");
+        assert_eq!(run("x = 1 / 0", 1, at(0.0)).unwrap_err(), "'x' is left inf, not a finite number");
+        assert_eq!(run("z = 0 / 0", 1, at(0.0)).unwrap_err(), "'z' is left NaN, not a finite number");
+        assert!(run("let a = 1; if false { let c = 2 } x = c", 1, at(0.0)).is_err());
+    }
+
+    #[test]
+    fn apply_counts_changes() {
+        let mut drones = vec![(1, vec![at(0.0), at(100.0)]), (2, vec![at(100.0)])];
+        let (samples, drones_changed) = script("if t > 90 { z = 0 }").apply(&mut drones).unwrap();
+        assert_eq!((samples, drones_changed), (2, 2));
+        assert_eq!(drones[0].1[0].pos, [1.0, 2.0, 3.0]);
+
+        match script("x = 1 / 0").apply(&mut drones) {
+            Err(Error::Parse(message)) => assert_eq!(message, "Script test.rhai failed for drone 1 at 0.000 s: 'x' is left inf, not a finite number"),
+            other => panic!("expected a script error, got {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! `--segment`: a long show cut where every drone hovers into shows no longer than a limit,
+//! each starting exactly where the one before ended.
+
+use crate::{error::{Error, Result}, track::distance, Drone, Sample};
+
+/// Fastest a drone may move (m/s) and still count as hovering.
+const HOVER_SPEED: f64 = 0.2;
+
+/// Cuts the show into segments of at most `length` seconds, each cut at the latest moment
+/// the segment may end at with every drone hovering. Every drone is in every segment, held
+/// in place before it starts and after it finishes, with a sample at both ends of the
+/// segment. Fails when a segment can't be ended in time.
+pub fn split(drones: Vec<Drone>, length: f32) -> Result<Vec<Vec<Drone>>> {
+    let start = drones.iter().map(|(_, samples)| samples[0].t).fold(f64::INFINITY, f64::min);
+    let end = drones.iter().map(|(_, samples)| samples[samples.len() - 1].t).fold(f64::NEG_INFINITY, f64::max);
+    let limit = length as f64 * 1000.0;
+    if end - start <= limit {
+        return Ok(vec![drones]);
+    }
+
+    let mut times: Vec<f64> = drones.iter().flat_map(|(_, samples)| samples.iter().map(|sample| sample.t)).collect();
+    times.sort_by(f64::total_cmp);
+    times.dedup();
+    let hovers: Vec<f64> = times.into_iter()
+        .filter(|t| drones.iter().all(|(_, samples)| hovering(samples, *t)))
+        .collect();
+
+    let mut cuts = vec![start];
+    while end - cuts[cuts.len() - 1] > limit {
+        let from = cuts[cuts.len() - 1];
+        let Some(cut) = hovers.iter().rev().find(|t| **t > from && **t <= from + limit) else {
+            return Err(Error::Validation(format!(
+                "No drone-wide hover within {} s of {:.3} s to end a segment at", length, from / 1000.0
+            )));
+        };
+        cuts.push(*cut);
+    }
+    cuts.push(end);
+
+    let bounds: Vec<String> = cuts.windows(2).map(|w| format!("{:.3}-{:.3} s", w[0] / 1000.0, w[1] / 1000.0)).collect();
+    println!("Segmenting at hover points into {} shows: {}", bounds.len(), bounds.join(", "));
+    Ok(cuts.windows(2)
+        .map(|w| drones.iter().map(|(drone_id, samples)| (*drone_id, window(samples, w[0], w[1]))).collect())
+        .collect())
+}
+
+fn speed(a: &Sample, b: &Sample) -> f64 {
+    if b.t > a.t { distance(a.pos, b.pos) as f64 / ((b.t - a.t) / 1000.0) } else { 0.0 }
+}
+
+/// Whether the drone is still at `t` (ms): on the segments either side of it, or held
+/// before its first sample or after its last.
+fn hovering(samples: &[Sample], t: f64) -> bool {
+    let after = samples.partition_point(|sample| sample.t <= t);
+    if after == 0 || after == samples.len() {
+        return true;
+    }
+    let at_sample = after >= 2 && samples[after - 1].t == t;
+    speed(&samples[after - 1], &samples[after]) <= HOVER_SPEED
+        && (!at_sample || speed(&samples[after - 2], &samples[after - 1]) <= HOVER_SPEED)
+}
+
+/// The drone's position (and color) at `t`, interpolated between its samples.
+fn at(samples: &[Sample], t: f64) -> Sample {
+    let after = samples.partition_point(|sample| sample.t <= t);
+    match after {
+        0 => samples[0].with_time(t),
+        n if n == samples.len() => samples[n - 1].with_time(t),
+        n => {
+            let (a, b) = (&samples[n - 1], &samples[n]);
+            let u = ((t - a.t) / (b.t - a.t)) as f32;
+            Sample { t, pos: [0, 1, 2].map(|i| a.pos[i] + (b.pos[i] - a.pos[i]) * u), rgb: a.rgb }
+        }
+    }
+}
+
+/// The drone's samples from `from` to `to` (ms), with samples at both ends.
+fn window(samples: &[Sample], from: f64, to: f64) -> Vec<Sample> {
+    let mut part = vec![at(samples, from)];
+    part.extend(samples.iter().filter(|sample| sample.t > from && sample.t < to));
+    part.push(at(samples, to));
+    part
+}
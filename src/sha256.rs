@@ -0,0 +1,190 @@
+//! SHA-256 (FIPS 180-4), used for input hashes in provenance metadata and checksum sidecars.
+
+use std::{io::Read, path::Path};
+
+use crate::error::{self, Result};
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2
+];
+
+pub struct Sha256 {
+    state: [u32; 8],
+    buffer: Vec<u8>,
+    length: u64
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Sha256 {
+            state: [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19],
+            buffer: Vec::with_capacity(64),
+            length: 0
+        }
+    }
+}
+
+impl Sha256 {
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+        if !self.buffer.is_empty() {
+            let take = (64 - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.buffer.len() == 64 {
+                let block: [u8; 64] = self.buffer[..].try_into().unwrap();
+                self.compress(&block);
+                self.buffer.clear();
+            }
+        }
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            self.compress(block.try_into().unwrap());
+        }
+        self.buffer.extend_from_slice(blocks.remainder());
+    }
+
+    pub fn finalize(mut self) -> [u8; 32] {
+        let bits = self.length.wrapping_mul(8);
+        let mut padding = vec![0x80u8];
+        let pad_zeros = (64 + 56 - (self.buffer.len() + 1) % 64) % 64;
+        padding.extend(std::iter::repeat_n(0, pad_zeros));
+        padding.extend_from_slice(&bits.to_be_bytes());
+        let length = self.length;
+        self.update(&padding);
+        self.length = length;
+        let mut digest = [0u8; 32];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+pub fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hex SHA-256 of a file's contents, read in chunks.
+pub fn file_digest(fname: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(fname)?;
+    let mut hasher = Sha256::default();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            n => hasher.update(&buffer[..n])
+        }
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+/// `show.vviz` -> `show.vviz.sha256`
+pub fn sidecar_path(fname: &Path) -> std::path::PathBuf {
+    let mut name = fname.as_os_str().to_owned();
+    name.push(".sha256");
+    name.into()
+}
+
+/// Writes a `sha256sum`-compatible checksum file next to `fname`.
+pub fn write_sidecar(fname: &Path) -> Result<()> {
+    let digest = file_digest(fname).map_err(error::io("Failed to hash output file"))?;
+    let name = fname.file_name().unwrap().to_string_lossy();
+    std::fs::write(sidecar_path(fname), format!("{}  {}\n", digest, name))
+        .map_err(error::io("Failed to write checksum file"))
+}
+
+/// Checks `fname` against its checksum sidecar. Returns `None` when there is no sidecar.
+pub fn verify_sidecar(fname: &Path) -> Result<Option<bool>> {
+    let Ok(expected) = std::fs::read_to_string(sidecar_path(fname)) else {
+        return Ok(None);
+    };
+    let expected = expected.split_whitespace().next().unwrap_or_default().to_ascii_lowercase();
+    Ok(Some(file_digest(fname).map_err(error::io("Failed to hash show file"))? == expected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hex, Sha256};
+
+    fn digest(chunks: &[&[u8]]) -> String {
+        let mut sha = Sha256::default();
+        for chunk in chunks {
+            sha.update(chunk);
+        }
+        hex(&sha.finalize())
+    }
+
+    /// The examples of FIPS 180-4 and the NIST test vectors.
+    #[test]
+    fn known_answers() {
+        assert_eq!(digest(&[b""]), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(digest(&[b"abc"]), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(
+            digest(&[b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"]),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            digest(&[b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu"]),
+            "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1"
+        );
+        assert_eq!(digest(&[&[b'a'; 1_000_000]]), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+    }
+
+    /// Padding that spills into a second block: 55, 56 and 64 bytes.
+    #[test]
+    fn block_boundaries() {
+        assert_eq!(digest(&[&[b'a'; 55]]), "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318");
+        assert_eq!(digest(&[&[b'a'; 56]]), "b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a");
+        assert_eq!(digest(&[&[b'a'; 64]]), "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb");
+    }
+
+    #[test]
+    fn split_updates_match_one_update() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let whole = digest(&[&data]);
+        for split in [1, 63, 64, 65, 500, 999] {
+            assert_eq!(digest(&[&data[..split], &data[split..]]), whole, "split at {}", split);
+        }
+    }
+}
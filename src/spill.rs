@@ -0,0 +1,228 @@
+//! `--max-memory`: performances moved out to a temp file once a show's trajectories outgrow
+//! the cap, and read back one at a time while the show is streamed to its output. Only the
+//! built show is capped: the samples they are built from are all held until then.
+
+use std::{
+    collections::{hash_map::RandomState, HashMap}, fs::File, hash::{BuildHasher, Hasher},
+    io::{BufWriter, ErrorKind, Read, Seek, SeekFrom, Write}, mem::size_of, path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering}
+};
+
+use serde::{ser::{Error as _, SerializeSeq, SerializeStruct}, Serialize, Serializer};
+
+use crate::{error::{self, Result}, AgentTraversal, Payload, PayloadAction, Performance, Sample, Show};
+
+/// Spill files created by this process so far, mixed into the next one's name.
+static SPILLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Attempts at a fresh temp file name before giving up.
+pub const ATTEMPTS: usize = 16;
+
+/// Parses a memory size: bytes, or a number with a `k`, `M`, `G` or `T` suffix (powers of
+/// 1024, optionally followed by `B` or `iB`), e.g. `2G` or `512MiB`.
+pub fn parse_size(s: &str) -> std::result::Result<u64, String> {
+    let invalid = || format!("expected a size such as 2G or 512M, got '{}'", s);
+    let trimmed = s.trim();
+    let unit_start = trimmed.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(unit_start);
+    let unit = unit.trim_end_matches("iB").trim_end_matches('B');
+    let power = match unit.to_ascii_lowercase().as_str() {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        _ => return Err(invalid())
+    };
+    let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+    let bytes = number * 1024f64.powi(power);
+    if !(bytes.is_finite() && bytes >= 1.0) {
+        return Err(invalid());
+    }
+    Ok(bytes as u64)
+}
+
+/// `1.5 GiB`, `512.0 MiB`, ...
+pub fn format_size(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", size, units[unit])
+    }
+}
+
+/// 64 random bits: std seeds the keys of every hasher it builds from the system's random
+/// source.
+pub fn random() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(SPILLS.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u32(std::process::id());
+    hasher.finish()
+}
+
+/// Bytes held by the samples of a drone not built yet.
+pub fn samples_size(samples: &[Sample]) -> usize {
+    std::mem::size_of_val(samples)
+}
+
+/// Rough bytes a performance holds in memory.
+fn performance_size(performance: &Performance) -> usize {
+    let payload: usize = performance.payload.iter()
+        .map(|payload| size_of::<Payload>() + payload.payload_type.len() + payload.actions.iter().map(|action| {
+            size_of::<PayloadAction>() + match action {
+                PayloadAction::Event(event) => event.action.len(),
+                PayloadAction::Color(_) => 0
+            }
+        }).sum::<usize>())
+        .sum();
+    size_of::<Performance>() + performance.description.traversals.0.len() * size_of::<AgentTraversal>() + payload
+}
+
+/// Where a spilled performance is in the spill file: offset and length of its JSON.
+type Slot = (u64, usize);
+
+/// The performances of one show moved to disk, removed again when dropped, which is also
+/// when a conversion fails.
+pub struct Spill {
+    limit: u64,
+    /// Bytes of the performances kept in memory.
+    held: usize,
+    path: PathBuf,
+    file: File,
+    end: u64,
+    /// Spilled performances by id while the show is built.
+    by_id: HashMap<usize, Slot>,
+    /// The same, by their place in the finished show.
+    by_index: Vec<Option<Slot>>
+}
+
+impl Spill {
+
+    /// Creates the spill file under a random name in the temp directory, so that no other
+    /// user can guess it and have it in place first.
+    pub fn new(limit: u64) -> Result<Spill> {
+        let mut attempt = 0;
+        loop {
+            let path = std::env::temp_dir().join(format!("csv2vviz-spill-{:016x}.json", random()));
+            let mut options = File::options();
+            options.read(true).write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            match options.open(&path) {
+                Ok(file) => return Ok(Spill { limit, held: 0, path, file, end: 0, by_id: HashMap::new(), by_index: vec![] }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists && attempt + 1 < ATTEMPTS => attempt += 1,
+                Err(e) => return Err(error::io("Failed to create spill file")(e))
+            }
+        }
+    }
+
+    /// Whether any performance was spilled.
+    pub fn used(&self) -> bool {
+        !self.by_id.is_empty()
+    }
+
+    /// Keeps a finished performance in memory while it fits beside those kept so far and the
+    /// `pending` bytes of drones still to build; otherwise moves its trajectory and payload
+    /// to disk, leaving the id and home in the show.
+    pub fn hold(&mut self, performance: &mut Performance, pending: usize) -> Result<()> {
+        let size = performance_size(performance);
+        if (self.held + size + pending) as u64 <= self.limit {
+            self.held += size;
+            return Ok(());
+        }
+        if !self.used() {
+            println!(
+                "The show outgrows --max-memory {}; spilling trajectories to {}",
+                format_size(self.limit), self.path.display()
+            );
+        }
+        let json = serde_json::to_vec(performance).expect("Failed to serialize show data.");
+        self.file.seek(SeekFrom::Start(self.end)).map_err(error::io("Failed to write spill file"))?;
+        self.file.write_all(&json).map_err(error::io("Failed to write spill file"))?;
+        self.by_id.insert(performance.id, (self.end, json.len()));
+        self.end += json.len() as u64;
+        performance.description.traversals.0 = vec![];
+        performance.payload = vec![];
+        Ok(())
+    }
+
+    /// Records where the spilled performances ended up once the show's performances are in
+    /// their final order; later changes may renumber them, but not reorder them.
+    pub fn seal(&mut self, performances: &[Performance]) {
+        self.by_index = performances.iter().map(|performance| self.by_id.get(&performance.id).copied()).collect();
+        if self.used() {
+            println!("Spilled {} of {} drones to disk", self.by_id.len(), performances.len());
+        }
+    }
+
+    /// The `index`th performance of the show, read back from disk if it was spilled.
+    fn load(&self, index: usize, performance: &Performance) -> Result<Performance> {
+        let Some((offset, len)) = self.by_index.get(index).copied().flatten() else {
+            return Ok(performance.clone());
+        };
+        let mut json = vec![0; len];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset)).map_err(error::io("Failed to read spill file"))?;
+        file.read_exact(&mut json).map_err(error::io("Failed to read spill file"))?;
+        let spilled: Performance = serde_json::from_slice(&json).map_err(error::io("Corrupt spill file"))?;
+        Ok(Performance { id: performance.id, ..spilled })
+    }
+
+    /// Streams `show` to `fname` as vviz JSON, reading its spilled performances back one at a
+    /// time.
+    pub fn write(&self, show: &Show, fname: &Path) -> Result<()> {
+        let file = File::create(fname).map_err(error::io("Failed to create new file"))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, &Streamed { show, spill: self }).map_err(error::io("Failed to write new file"))?;
+        writer.flush().map_err(error::io("Failed to write new file"))
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// A show with spilled performances, serialized like [`Show`] itself.
+struct Streamed<'a> {
+    show: &'a Show,
+    spill: &'a Spill
+}
+
+impl Serialize for Streamed<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        // The fields of Show, in order.
+        let show = self.show;
+        let mut state = serializer.serialize_struct("Show", 5)?;
+        state.serialize_field("version", &show.version)?;
+        state.serialize_field("defaultPositionRate", &show.default_position_rate)?;
+        state.serialize_field("defaultColorRate", &show.default_color_rate)?;
+        state.serialize_field("performances", &Performances(self))?;
+        match &show.metadata {
+            Some(metadata) => state.serialize_field("metadata", metadata)?,
+            None => state.skip_field("metadata")?
+        }
+        state.end()
+    }
+}
+
+struct Performances<'a>(&'a Streamed<'a>);
+
+impl Serialize for Performances<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let Streamed { show, spill } = self.0;
+        let mut seq = serializer.serialize_seq(Some(show.performances.len()))?;
+        for (index, performance) in show.performances.iter().enumerate() {
+            let performance = spill.load(index, performance).map_err(S::Error::custom)?;
+            seq.serialize_element(&performance)?;
+        }
+        seq.end()
+    }
+}
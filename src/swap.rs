@@ -0,0 +1,96 @@
+//! `csv2vviz swap`: a failed drone's performance handed to a spare, as done in the field.
+
+use std::{collections::HashSet, path::PathBuf};
+
+use clap::Args;
+
+use crate::{align::read_surveyed, error::{Error, Result}, read_show, track::distance, write_show, Performance};
+
+#[derive(Args, Debug)]
+pub struct SwapArgs {
+
+    /// Converted show to change
+    show: PathBuf,
+
+    /// `FAILED=SPARE` drone numbers: the spare flies the failed drone's performance. Repeat
+    /// for several; drones may trade performances (`--replace 3=4 --replace 4=3`)
+    #[arg(long, required = true, value_parser = parse_replacement)]
+    replace: Vec<(usize, usize)>,
+
+    /// CSV of `id, x, y, z` pad positions, as for `align`: each spare takes off from its own
+    /// pad and joins the performance at its second position
+    #[arg(long)]
+    pads: Option<PathBuf>,
+
+    /// Output file (defaults to `<show>.swapped.vviz`)
+    #[arg(short, long)]
+    output: Option<PathBuf>
+}
+
+fn parse_replacement(s: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("expected FAILED=SPARE drone numbers, got '{}'", s);
+    let (failed, spare) = s.split_once('=').ok_or_else(invalid)?;
+    let number = |n: &str| n.trim().parse::<usize>().ok().filter(|n| *n > 0).ok_or_else(invalid);
+    Ok((number(failed)?, number(spare)?))
+}
+
+pub fn run(args: SwapArgs) -> Result<()> {
+    let mut show = read_show(&args.show)?;
+    let pads = args.pads.as_deref().map(read_surveyed).transpose()?;
+
+    let (mut failed, mut spares) = (HashSet::new(), HashSet::new());
+    for (from, to) in &args.replace {
+        if !failed.insert(*from) {
+            return Err(Error::Parse(format!("Drone {} is replaced more than once", from)));
+        }
+        if !spares.insert(*to) {
+            return Err(Error::Parse(format!("Drone {} replaces more than one drone", to)));
+        }
+        if !show.performances.iter().any(|performance| performance.id + 1 == *from) {
+            return Err(Error::Parse(format!("{} has no drone {}", args.show.display(), from)));
+        }
+    }
+    // A spare already flying must hand its own performance on, or two drones would share it.
+    if let Some(busy) = show.performances.iter().map(|performance| performance.id + 1).find(|id| spares.contains(id) && !failed.contains(id)) {
+        return Err(Error::Parse(format!("Drone {} already flies in the show; replace it too to trade performances", busy)));
+    }
+
+    for performance in show.performances.iter_mut() {
+        let Some(&(from, to)) = args.replace.iter().find(|(from, _)| performance.id + 1 == *from) else {
+            continue;
+        };
+        performance.id = to - 1;
+        match pads.as_ref().map(|pads| pads.get(&to)) {
+            Some(Some(pad)) => {
+                let moved = move_start(performance, pad.map(|value| value as f32));
+                println!("Drone {} flies drone {}'s performance from its pad, {:.2} m from the original start", to, from, moved);
+            }
+            Some(None) => println!("Warning: no pad for drone {}; it starts where drone {} did", to, from),
+            None => println!("Drone {} flies drone {}'s performance", to, from)
+        }
+    }
+    show.performances.sort_by_key(|performance| performance.id);
+
+    let output = args.output.unwrap_or_else(|| args.show.with_extension("swapped.vviz"));
+    println!("Generating {}", output.to_str().unwrap());
+    write_show(&show, &output)
+}
+
+/// Puts the performance's home on `pad` (vviz axes), stretching the first traversal so it
+/// still ends where it did; its control points keep their positions. Returns how far the
+/// home moved.
+pub fn move_start(performance: &mut Performance, pad: [f32; 3]) -> f32 {
+    let description = &mut performance.description;
+    let home = [description.home_x, description.home_y, description.home_z];
+    let offset = [home[0] - pad[0], home[1] - pad[1], home[2] - pad[2]];
+    [description.home_x, description.home_y, description.home_z] = pad;
+    if let Some(first) = description.traversals.0.first_mut() {
+        first.dx += offset[0];
+        first.dy += offset[1];
+        first.dz += offset[2];
+        if let Some(points) = first.control_points.as_mut() {
+            *points = points.map(|point| [point[0] + offset[0], point[1] + offset[1], point[2] + offset[2]]);
+        }
+    }
+    distance(home, pad)
+}
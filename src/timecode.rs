@@ -0,0 +1,91 @@
+use std::{fmt, io::Write, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::{self, Error, Result}, track::Track, validate, Show};
+
+/// SMPTE (non-drop-frame) timecode for show time zero, counted in frames since midnight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timecode {
+    frame: u64,
+    pub fps: u32
+}
+
+/// `HH:MM:SS:FF` as given on the command line; the frame rate comes separately.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Start([u64; 4]);
+
+impl FromStr for Start {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<u64> = s.split(':').map(|field| field.parse::<u64>()).collect::<Result<_, _>>()
+            .map_err(|_| format!("expected HH:MM:SS:FF, got '{}'", s))?;
+        match fields[..] {
+            [h, m, sec, f] if h < 24 && m < 60 && sec < 60 => Ok(Start([h, m, sec, f])),
+            [_, _, _, _] => Err(format!("'{}' is not a time of day", s)),
+            _ => Err(format!("expected HH:MM:SS:FF, got '{}'", s))
+        }
+    }
+}
+
+/// How show time maps onto timecode, recorded in the show's metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mapping {
+    pub start: String,
+    pub fps: u32
+}
+
+const DAY: u64 = 24 * 60 * 60;
+
+impl Timecode {
+
+    pub fn new(start: Start, fps: u32) -> Result<Self> {
+        let [h, m, s, f] = start.0;
+        if fps == 0 {
+            return Err(Error::Parse("--fps must be at least 1".to_string()));
+        }
+        if f >= fps as u64 {
+            return Err(Error::Parse(format!("--timecode-start frame {} does not exist at {} fps", f, fps)));
+        }
+        Ok(Timecode { frame: ((h * 60 + m) * 60 + s) * fps as u64 + f, fps })
+    }
+
+    /// Timecode at `seconds` of show time, rounded down to the frame and wrapping at midnight.
+    pub fn at(&self, seconds: f32) -> String {
+        let fps = self.fps as u64;
+        let frame = (self.frame + (seconds.max(0.0) as f64 * fps as f64 + 1e-6) as u64) % (DAY * fps);
+        let (s, f) = (frame / fps, frame % fps);
+        format!("{:02}:{:02}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60, f)
+    }
+
+    pub fn mapping(&self) -> Mapping {
+        Mapping { start: self.at(0.0), fps: self.fps }
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {} fps", self.at(0.0), self.fps)
+    }
+}
+
+/// Writes `show_time,timecode` rows for every whole second of the show and its last moment,
+/// for lighting and audio desks that cue against the same clock.
+pub fn write_sidecar(show: &Show, timecode: &Timecode, fname: &Path) -> Result<()> {
+    let end = show.performances.iter()
+        .map(|performance| validate::timeline(performance, show, &Track::new(performance, show.default_position_rate)))
+        .fold(0.0, f32::max);
+    let mut text = String::from("show_time,timecode\n");
+    let mut second = 0;
+    while (second as f32) < end {
+        text.push_str(&format!("{:.3},{}\n", second as f32, timecode.at(second as f32)));
+        second += 1;
+    }
+    text.push_str(&format!("{:.3},{}\n", end, timecode.at(end)));
+
+    let mut file = std::fs::File::create(fname).map_err(error::io("Failed to create timecode file"))?;
+    file.write_all(text.as_bytes()).map_err(error::io("Failed to write timecode file"))?;
+    println!("Wrote timecode {} to {}", timecode, fname.display());
+    Ok(())
+}
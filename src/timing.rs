@@ -0,0 +1,183 @@
+use std::{collections::HashMap, path::Path, str::FromStr};
+
+use crate::{error::{self, Error, Result}, field, progress, Drone, Sample};
+
+/// How drones whose CSVs start at different times are brought onto a common t=0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeAlign {
+    /// The earliest start; later drones hold at their first position until they begin.
+    Earliest,
+    /// The latest start; earlier drones are trimmed to it.
+    Latest,
+    /// An explicit start time in milliseconds on the CSV clock.
+    Epoch(f64)
+}
+
+impl FromStr for TimeAlign {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "earliest" => Ok(TimeAlign::Earliest),
+            "latest" => Ok(TimeAlign::Latest),
+            _ => s.parse::<f64>().ok().filter(|epoch| epoch.is_finite()).map(TimeAlign::Epoch)
+                .ok_or_else(|| format!("expected 'earliest', 'latest' or a start time in ms, got '{}'", s))
+        }
+    }
+}
+
+impl std::fmt::Display for TimeAlign {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeAlign::Earliest => f.write_str("earliest"),
+            TimeAlign::Latest => f.write_str("latest"),
+            TimeAlign::Epoch(epoch) => write!(f, "{}", epoch)
+        }
+    }
+}
+
+/// Sample at `at` between `a` and `b`: positions are interpolated linearly, colors are
+/// held from `a`.
+pub fn interpolate(a: &Sample, b: &Sample, at: f64) -> Sample {
+    let f = if b.t > a.t { ((at - a.t) / (b.t - a.t)) as f32 } else { 0.0 };
+    Sample { t: at, pos: [0, 1, 2].map(|i| a.pos[i] + (b.pos[i] - a.pos[i]) * f), rgb: a.rgb }
+}
+
+/// Moves every drone's first sample to a common start time, prepending a hold at the first
+/// position where a drone starts late and trimming samples where it starts early.
+pub fn align_start(drones: &mut [Drone], mode: TimeAlign) {
+    let starts = drones.iter().filter(|(_, samples)| !samples.is_empty()).map(|(_, samples)| samples[0].t);
+    let start = match mode {
+        TimeAlign::Earliest => starts.fold(f64::INFINITY, f64::min),
+        TimeAlign::Latest => starts.fold(f64::NEG_INFINITY, f64::max),
+        TimeAlign::Epoch(epoch) => epoch
+    };
+    if !start.is_finite() {
+        return;
+    }
+
+    for (drone_id, samples) in drones.iter_mut() {
+        if samples.is_empty() {
+            continue;
+        }
+        let first = samples[0].t;
+        if first > start {
+            println!("Drone {}: holding {:.3} s before its first sample", drone_id, (first - start) / 1000.0);
+            samples.insert(0, samples[0].with_time(start));
+        } else if first < start {
+            let Some(index) = samples.iter().position(|sample| sample.t >= start) else {
+                progress::warning(format!("drone {} ends before the common start time", drone_id));
+                samples.truncate(1);
+                samples[0] = samples[0].with_time(start);
+                continue;
+            };
+            println!("Drone {}: trimming {:.3} s before the common start time", drone_id, (start - first) / 1000.0);
+            if samples[index].t > start {
+                samples[index - 1] = interpolate(&samples[index - 1], &samples[index], start);
+                samples.drain(..index - 1);
+            } else {
+                samples.drain(..index);
+            }
+        }
+    }
+}
+
+/// Per-drone time offsets in seconds (positive delays the drone), keyed by drone number.
+#[derive(Debug, Default)]
+pub struct TimeShifts(HashMap<usize, f64>);
+
+impl TimeShifts {
+
+    /// Reads a two-column CSV (`id,seconds`) with a header row.
+    pub fn load(fname: &Path) -> Result<Self> {
+        let mut reader = csv::Reader::from_path(fname)
+            .map_err(error::csv("Failed to open time shift file"))?;
+        let mut shifts = TimeShifts::default();
+        for record in reader.records() {
+            let record = record.map_err(error::csv("Failed to read time shift file"))?;
+            let file = fname.display().to_string();
+            let id = field::cell(&record, 0, "id", &file)?;
+            let id = field::parse::<usize>(id)
+                .map_err(|_| Error::Parse(format!("Invalid drone id '{}' in time shift file.", id)))?;
+            let seconds = field::cell(&record, 1, "seconds", &file)?;
+            let seconds = field::parse::<f64>(seconds)
+                .map_err(|_| Error::Parse(format!("Invalid time shift '{}' for drone {}.", seconds, id)))?;
+            shifts.0.insert(id, seconds);
+        }
+        Ok(shifts)
+    }
+
+    /// Offset for the drone in milliseconds.
+    pub fn millis(&self, drone_id: usize) -> f64 {
+        self.0.get(&drone_id).map_or(0.0, |seconds| seconds * 1000.0)
+    }
+
+    /// Offsets every listed drone's samples. Returns the earliest start before shifting so the
+    /// caller can re-align the show to it.
+    pub fn apply(&self, drones: &mut [Drone]) -> f64 {
+        let start = drones.iter().filter(|(_, samples)| !samples.is_empty())
+            .map(|(_, samples)| samples[0].t)
+            .fold(f64::INFINITY, f64::min);
+        for (drone_id, samples) in drones.iter_mut() {
+            let shift = self.millis(*drone_id);
+            if shift != 0.0 {
+                for sample in samples.iter_mut() {
+                    sample.t += shift;
+                }
+            }
+        }
+        for drone_id in self.0.keys() {
+            if !drones.iter().any(|(id, _)| id == drone_id) {
+                progress::warning(format!("time shift for drone {} which is not in the show", drone_id));
+            }
+        }
+        start
+    }
+}
+
+/// Parses a duration such as `90`, `90s`, `12m`, `1m30s` or `1.5h` into seconds. Durations
+/// are finite and never negative.
+pub fn parse_duration(s: &str) -> Result<f32, String> {
+    let invalid = || format!("invalid duration '{}'", s);
+    let s = s.trim();
+    if let Ok(seconds) = s.parse::<f32>() {
+        return Some(seconds).filter(|seconds| seconds.is_finite() && *seconds >= 0.0).ok_or_else(invalid);
+    }
+    let mut total = 0.0;
+    let mut number = String::new();
+    for c in s.chars() {
+        match c {
+            '0'..='9' | '.' => number.push(c),
+            'h' | 'm' | 's' => {
+                let value = number.parse::<f32>().map_err(|_| invalid())?;
+                total += value * match c { 'h' => 3600.0, 'm' => 60.0, _ => 1.0 };
+                number.clear();
+            }
+            _ => return Err(invalid())
+        }
+    }
+    if !number.is_empty() || !total.is_finite() {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_duration, TimeAlign};
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90"), Ok(90.0));
+        assert_eq!(parse_duration("1m30s"), Ok(90.0));
+        assert_eq!(parse_duration("1.5h"), Ok(5400.0));
+    }
+
+    #[test]
+    fn rejects_endless_and_negative_durations() {
+        for s in ["inf", "NaN", "-5", "1e40", "-1m"] {
+            assert_eq!(parse_duration(s), Err(format!("invalid duration '{}'", s)));
+        }
+        assert!("inf".parse::<TimeAlign>().is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{curve::bezier, Performance};
+
+/// Absolute positions of a performance, re-integrated from its home and traversal deltas.
+/// Times are in seconds from the start of the show; traversals given in frames, or without
+/// any duration, use the show's default position rate. Bézier traversals are sampled at that
+/// rate along the curve.
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub samples: Vec<(f32, [f32; 3])>
+}
+
+impl Track {
+
+    pub fn new(performance: &Performance, position_rate: f32) -> Self {
+        let description = &performance.description;
+        let mut t = 0.0;
+        let mut position = [description.home_x, description.home_y, description.home_z];
+        let mut samples = vec![(t, position)];
+        for traversal in &description.traversals.0 {
+            let dt = traversal.duration(position_rate);
+            let end = [position[0] + traversal.dx, position[1] + traversal.dy, position[2] + traversal.dz];
+            if let Some([c1, c2]) = traversal.control_points {
+                let steps = (dt * position_rate).ceil().max(1.0) as u32;
+                let start = position;
+                let offset = |c: [f32; 3]| [start[0] + c[0], start[1] + c[1], start[2] + c[2]];
+                for step in 1..steps {
+                    let u = step as f32 / steps as f32;
+                    samples.push((t + dt * u, bezier([start, offset(c1), offset(c2), end], u)));
+                }
+            }
+            t += dt;
+            position = end;
+            samples.push((t, position));
+        }
+        Track { samples }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.samples.last().map_or(0.0, |(t, _)| *t)
+    }
+
+    /// Linearly interpolated position at `t`, holding the first/last sample outside the
+    /// track's time range.
+    pub fn position_at(&self, t: f32) -> [f32; 3] {
+        let index = self.samples.partition_point(|(time, _)| *time <= t);
+        if index == 0 {
+            return self.samples[0].1;
+        }
+        if index == self.samples.len() {
+            return self.samples[index - 1].1;
+        }
+        let ((t0, a), (t1, b)) = (self.samples[index - 1], self.samples[index]);
+        let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 0.0 };
+        [a[0] + (b[0] - a[0]) * f, a[1] + (b[1] - a[1]) * f, a[2] + (b[2] - a[2]) * f]
+    }
+}
+
+pub fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2) + (b[2] - a[2]).powi(2)).sqrt()
+}
@@ -0,0 +1,98 @@
+//! `csv2vviz transform`: the conversion's rotation and translation applied to a converted
+//! show, for when its CSVs are gone.
+
+use std::path::PathBuf;
+
+use clap::Args;
+use euclid::{Point3D, UnknownUnit, Vector3D};
+
+use crate::{config::Config, error::{Error, Result}, read_show, write_show, Rotation, Translation, F3D};
+
+#[derive(Args, Debug)]
+pub struct TransformArgs {
+
+    /// Converted show to transform
+    show: PathBuf,
+
+    /// Euler rotation in degrees, as for the conversion
+    #[arg(short, long, allow_hyphen_values = true)]
+    rotate: Option<F3D>,
+
+    /// Offset in meters, as for the conversion
+    #[arg(short, long, allow_hyphen_values = true)]
+    translate: Option<F3D>,
+
+    /// Uniform scale about the origin, applied before rotating
+    #[arg(short, long)]
+    scale: Option<f32>,
+
+    /// TOML file with per-drone transform overrides, as for the conversion
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Output file (defaults to `<show>.transformed.vviz`)
+    #[arg(short, long)]
+    output: Option<PathBuf>
+}
+
+/// Scales, rotates and translates positions in vviz axes; directions (traversal deltas and
+/// control points) are only scaled and rotated.
+struct Transform {
+    scale: f32,
+    rotations: Vec<Rotation>,
+    translations: Vec<Translation>
+}
+
+impl Transform {
+
+    fn point(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        let mut point = Point3D::<f32, UnknownUnit>::new(x, y, z) * self.scale;
+        for (rotation, translation) in self.rotations.iter().zip(&self.translations) {
+            point = translation.transform_point3d(&rotation.transform_point3d(point));
+        }
+        point.to_array()
+    }
+
+    fn vector(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        let mut vector = Vector3D::<f32, UnknownUnit>::new(x, y, z) * self.scale;
+        for rotation in &self.rotations {
+            vector = rotation.transform_vector3d(vector);
+        }
+        vector.to_array()
+    }
+}
+
+pub fn run(args: TransformArgs) -> Result<()> {
+    if args.scale.is_some_and(|scale| !(scale > 0.0 && scale.is_finite())) {
+        return Err(Error::Parse("--scale must be positive".to_string()));
+    }
+    let mut show = read_show(&args.show)?;
+    let config = args.config.as_deref().map(Config::load).transpose()?.unwrap_or_default();
+    let identity = || F3D { x: 0.0, y: 0.0, z: 0.0 };
+
+    for performance in show.performances.iter_mut() {
+        // The global transform, then the drone's own, as in the conversion.
+        let mut transform = Transform {
+            scale: args.scale.unwrap_or(1.0),
+            rotations: vec![args.rotate.as_ref().unwrap_or(&identity()).rotation()],
+            translations: vec![args.translate.as_ref().unwrap_or(&identity()).translation()]
+        };
+        if let Some(drone_override) = config.drones.get(&(performance.id + 1)) {
+            transform.rotations.push(drone_override.rotate.as_ref().unwrap_or(&identity()).rotation());
+            transform.translations.push(drone_override.translate.as_ref().unwrap_or(&identity()).translation());
+        }
+
+        let description = &mut performance.description;
+        [description.home_x, description.home_y, description.home_z] = transform.point([description.home_x, description.home_y, description.home_z]);
+        for traversal in description.traversals.0.iter_mut() {
+            [traversal.dx, traversal.dy, traversal.dz] = transform.vector([traversal.dx, traversal.dy, traversal.dz]);
+            if let Some(points) = traversal.control_points.as_mut() {
+                *points = points.map(|point| transform.vector(point));
+            }
+        }
+    }
+
+    let output = args.output.unwrap_or_else(|| args.show.with_extension("transformed.vviz"));
+    println!("Generating {}", output.to_str().unwrap());
+    write_show(&show, &output)
+}
@@ -0,0 +1,157 @@
+//! PX4 ULog flight logs, read for the `vehicle_local_position` topic.
+
+pub fn is_ulog(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with(".ulg")
+}
+
+#[cfg(not(feature = "ulog"))]
+pub fn read(
+    _file: &mut dyn std::io::Read, _rate: f32, name: &str, _origin: &mut Option<(f64, f64)>
+) -> crate::error::Result<Vec<crate::Sample>> {
+    Err(crate::error::Error::Format(format!("Cannot read {}: csv2vviz was built without the `ulog` feature.", name)))
+}
+
+#[cfg(feature = "ulog")]
+pub use parser::read;
+
+#[cfg(feature = "ulog")]
+mod parser {
+    use std::{collections::HashMap, io::{BufReader, Read}};
+
+    use crate::{error::{Error, Result}, flightlog, Sample};
+
+    const MAGIC: &[u8] = b"ULog\x01\x12\x35";
+    const TOPIC: &str = "vehicle_local_position";
+
+    /// Byte offset and type of every field of a logged message format.
+    type Fields = HashMap<String, (usize, String)>;
+
+    fn size_of(field_type: &str, formats: &HashMap<String, Vec<(String, String)>>) -> Option<usize> {
+        if let Some((element, count)) = field_type.strip_suffix(']').and_then(|t| t.split_once('[')) {
+            return Some(size_of(element, formats)? * count.parse::<usize>().ok()?);
+        }
+        Some(match field_type {
+            "int8_t" | "uint8_t" | "bool" | "char" => 1,
+            "int16_t" | "uint16_t" => 2,
+            "int32_t" | "uint32_t" | "float" => 4,
+            "int64_t" | "uint64_t" | "double" => 8,
+            nested => formats.get(nested)?.iter().map(|(t, _)| size_of(t, formats)).sum::<Option<usize>>()?
+        })
+    }
+
+    fn layout(name: &str, formats: &HashMap<String, Vec<(String, String)>>) -> Option<Fields> {
+        let mut fields = Fields::new();
+        let mut offset = 0;
+        for (field_type, field_name) in formats.get(name)? {
+            fields.insert(field_name.clone(), (offset, field_type.clone()));
+            offset += size_of(field_type, formats)?;
+        }
+        Some(fields)
+    }
+
+    fn value(data: &[u8], fields: &Fields, name: &str) -> Option<f64> {
+        let (offset, field_type) = fields.get(name)?;
+        let bytes = |n: usize| data.get(*offset..offset + n);
+        Some(match field_type.as_str() {
+            "float" => f32::from_le_bytes(bytes(4)?.try_into().ok()?) as f64,
+            "double" => f64::from_le_bytes(bytes(8)?.try_into().ok()?),
+            "uint64_t" => u64::from_le_bytes(bytes(8)?.try_into().ok()?) as f64,
+            "bool" | "uint8_t" => bytes(1)?[0] as f64,
+            _ => return None
+        })
+    }
+
+    /// Positions of the first `vehicle_local_position` instance, at most `rate` per second,
+    /// as white samples (`t` in ms since boot, east/north/up meters). The
+    /// vehicle's local frame is placed relative to `origin` through its reference position
+    /// when it has one, so drones of one swarm share a frame.
+    pub fn read(file: &mut dyn Read, rate: f32, name: &str, origin: &mut Option<(f64, f64)>) -> Result<Vec<Sample>> {
+        let mut reader = BufReader::new(file);
+        let corrupt = |what: &str| Error::Format(format!("{} is not a valid ULog file: {}", name, what));
+
+        let mut header = [0u8; 16];
+        reader.read_exact(&mut header).map_err(|_| corrupt("truncated header"))?;
+        if &header[..7] != MAGIC {
+            return Err(corrupt("bad magic"));
+        }
+
+        let mut formats: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        let mut subscription: Option<(u16, u8, Fields)> = None;
+        let mut offset: Option<(f64, f64)> = None;
+        let mut samples = vec![];
+        let mut last_time = f64::NEG_INFINITY;
+        let interval = 1e6 / rate as f64;
+
+        let mut message_header = [0u8; 3];
+        let mut message = vec![];
+        loop {
+            match reader.read_exact(&mut message_header) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(Error::Io(format!("Failed to read {}: {}", name, e)))
+            }
+            let size = u16::from_le_bytes([message_header[0], message_header[1]]) as usize;
+            message.resize(size, 0);
+            // Logs cut short by a power loss end mid-message; keep what was complete.
+            if reader.read_exact(&mut message).is_err() {
+                break;
+            }
+            match message_header[2] {
+                b'F' => {
+                    let text = String::from_utf8_lossy(&message);
+                    let Some((format_name, fields)) = text.split_once(':') else { continue };
+                    let fields = fields.split(';')
+                        .filter_map(|field| field.trim().split_once(' '))
+                        .map(|(field_type, field_name)| (field_type.to_string(), field_name.to_string()))
+                        .collect();
+                    formats.insert(format_name.to_string(), fields);
+                }
+                b'A' if message.len() > 3 => {
+                    let multi_id = message[0];
+                    let msg_id = u16::from_le_bytes([message[1], message[2]]);
+                    let topic = String::from_utf8_lossy(&message[3..]);
+                    let better = subscription.as_ref().is_none_or(|(_, current, _)| multi_id < *current);
+                    if topic == TOPIC && better {
+                        let fields = layout(TOPIC, &formats).ok_or_else(|| corrupt("incomplete vehicle_local_position format"))?;
+                        subscription = Some((msg_id, multi_id, fields));
+                    }
+                }
+                b'D' if message.len() > 2 => {
+                    let Some((msg_id, _, fields)) = &subscription else { continue };
+                    if u16::from_le_bytes([message[0], message[1]]) != *msg_id {
+                        continue;
+                    }
+                    let data = &message[2..];
+                    let valid = |flag: &str| value(data, fields, flag) != Some(0.0);
+                    let (Some(time), Some(x), Some(y), Some(z)) = (
+                        value(data, fields, "timestamp"), value(data, fields, "x"), value(data, fields, "y"), value(data, fields, "z")
+                    ) else {
+                        return Err(corrupt("vehicle_local_position has no timestamp or position"));
+                    };
+                    if !valid("xy_valid") || !valid("z_valid") || time < last_time + interval {
+                        continue;
+                    }
+                    last_time = time;
+
+                    let (east0, north0) = *offset.get_or_insert_with(|| {
+                        match (value(data, fields, "ref_lat"), value(data, fields, "ref_lon")) {
+                            (Some(latitude), Some(longitude)) if valid("xy_global") => flightlog::local(latitude, longitude, origin),
+                            _ => (0.0, 0.0)
+                        }
+                    });
+                    // The local frame is north/east/down.
+                    samples.push(Sample {
+                        t: time / 1000.0,
+                        pos: [(east0 + y) as f32, (north0 + x) as f32, (-z) as f32],
+                        rgb: [255; 3]
+                    });
+                }
+                _ => {}
+            }
+        }
+        if subscription.is_none() {
+            return Err(Error::Parse(format!("{} has no {} data", name, TOPIC)));
+        }
+        Ok(samples)
+    }
+}
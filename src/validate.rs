@@ -0,0 +1,282 @@
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+
+use crate::{error::{Error, Result}, progress, read_show, sha256, track::Track, PayloadAction, Performance, Show};
+
+#[derive(Args, Debug)]
+pub struct ValidateArgs {
+
+    /// Converted show to check
+    show: PathBuf,
+
+    /// Validate against a drone hardware profile; explicit limits take precedence
+    #[arg(long, value_enum)]
+    profile: Option<Profile>,
+
+    #[command(flatten)]
+    limits: Limits
+}
+
+/// Verifies the show's checksum sidecar (when present) and checks it against the limits.
+pub fn run(args: ValidateArgs) -> Result<()> {
+    let checksum_ok = sha256::verify_sidecar(&args.show)?;
+    match checksum_ok {
+        Some(true) => println!("Checksum OK"),
+        Some(false) => println!("Checksum mismatch: {} does not match {}", args.show.display(), sha256::sidecar_path(&args.show).display()),
+        None => {}
+    }
+
+    let show = read_show(&args.show)?;
+    let limits = match args.profile {
+        Some(profile) => args.limits.or(profile.limits()),
+        None => args.limits
+    };
+    let problems = validate(&show, &limits, args.profile);
+
+    if checksum_ok == Some(false) {
+        return Err(Error::Validation(format!("Checksum mismatch for {}", args.show.display())));
+    }
+    if problems > 0 {
+        return Err(Error::Validation(format!("{} failed validation with {} problems", args.show.display(), problems)));
+    }
+    println!("{} performances OK", show.performances.len());
+    Ok(())
+}
+
+/// Physical limits checked after conversion. Unset limits are not checked.
+#[derive(Args, Debug, Clone, Default)]
+pub struct Limits {
+
+    /// Maximum speed (m/s)
+    #[arg(long)]
+    pub max_speed: Option<f32>,
+
+    /// Maximum acceleration (m/s²)
+    #[arg(long)]
+    pub max_accel: Option<f32>,
+
+    /// Maximum climb rate (m/s)
+    #[arg(long)]
+    pub max_climb_rate: Option<f32>,
+
+    /// Maximum descent rate (m/s)
+    #[arg(long)]
+    pub max_descent_rate: Option<f32>,
+
+    /// Maximum flight time per drone (`720`, `12m`, `11m30s`)
+    #[arg(long, value_parser = crate::timing::parse_duration)]
+    pub max_flight_time: Option<f32>,
+
+    /// Maximum length of each drone's whole timeline, from the show start to its last
+    /// movement, color change or payload event (`720`, `12m`)
+    #[arg(long, value_parser = crate::timing::parse_duration)]
+    pub max_duration: Option<f32>,
+
+    /// Largest relative difference between a drone's typical sample spacing and the show's
+    /// `defaultPositionRate` (`0.05` for 5%)
+    #[arg(long)]
+    pub rate_tolerance: Option<f32>
+}
+
+impl Limits {
+
+    /// Fills in every limit not given explicitly from `other`.
+    pub fn or(self, other: Limits) -> Limits {
+        Limits {
+            max_speed: self.max_speed.or(other.max_speed),
+            max_accel: self.max_accel.or(other.max_accel),
+            max_climb_rate: self.max_climb_rate.or(other.max_climb_rate),
+            max_descent_rate: self.max_descent_rate.or(other.max_descent_rate),
+            max_flight_time: self.max_flight_time.or(other.max_flight_time),
+            max_duration: self.max_duration.or(other.max_duration),
+            rate_tolerance: self.rate_tolerance.or(other.rate_tolerance)
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max_speed.is_none() && self.max_accel.is_none() && self.max_climb_rate.is_none()
+            && self.max_descent_rate.is_none() && self.max_flight_time.is_none()
+            && self.max_duration.is_none() && self.rate_tolerance.is_none()
+    }
+}
+
+/// Named drone hardware with its flight envelope and the payload types it carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Profile {
+    /// Outdoor show drone with light and pyro payloads
+    EaX1,
+    /// Small light-only show drone
+    GenericSmall
+}
+
+impl Profile {
+
+    pub fn limits(&self) -> Limits {
+        match self {
+            Profile::EaX1 => Limits {
+                max_speed: Some(8.0),
+                max_accel: Some(4.0),
+                max_climb_rate: Some(3.0),
+                max_descent_rate: Some(2.0),
+                max_flight_time: Some(15.0 * 60.0),
+                max_duration: None,
+                rate_tolerance: None
+            },
+            Profile::GenericSmall => Limits {
+                max_speed: Some(5.0),
+                max_accel: Some(2.5),
+                max_climb_rate: Some(2.0),
+                max_descent_rate: Some(1.5),
+                max_flight_time: Some(10.0 * 60.0),
+                max_duration: None,
+                rate_tolerance: None
+            }
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+
+    pub fn payload_types(&self) -> &'static [&'static str] {
+        match self {
+            Profile::EaX1 => &["light", "pyro"],
+            Profile::GenericSmall => &["light"]
+        }
+    }
+}
+
+/// Worst value of one limit on one drone and how many samples exceeded it.
+struct Violation {
+    worst: f32,
+    count: usize
+}
+
+impl Violation {
+    fn check(slot: &mut Option<Violation>, value: f32, limit: Option<f32>) {
+        if limit.is_some_and(|limit| value > limit) {
+            let violation = slot.get_or_insert(Violation { worst: value, count: 0 });
+            violation.worst = violation.worst.max(value);
+            violation.count += 1;
+        }
+    }
+}
+
+/// Median `dt` of the drone's straight traversals (per frame for frame counts). The median
+/// keeps occasional holds (from time alignment or shifts) from counting as a cadence change;
+/// Bézier segments span many samples by design and are left out.
+fn typical_spacing(performance: &Performance, position_rate: f32) -> Option<f32> {
+    let mut spacings: Vec<f32> = performance.description.traversals.0.iter()
+        .filter(|traversal| traversal.control_points.is_none())
+        .map(|traversal| traversal.duration(position_rate) / traversal.frames.unwrap_or(1) as f32)
+        .collect();
+    if spacings.is_empty() {
+        return None;
+    }
+    spacings.sort_by(f32::total_cmp);
+    Some(spacings[spacings.len() / 2])
+}
+
+/// End of the drone's timeline: the later of its track and its payloads. Color actions
+/// without `frames` last one frame at the show's color rate.
+pub fn timeline(performance: &Performance, show: &Show, track: &Track) -> f32 {
+    let mut end = track.duration();
+    for payload in &performance.payload {
+        let mut frames = 0;
+        for action in &payload.actions {
+            match action {
+                PayloadAction::Color(color) => frames += color.frames.unwrap_or(1),
+                PayloadAction::Event(event) => end = end.max(event.time)
+            }
+        }
+        end = end.max(frames as f32 / show.default_color_rate);
+    }
+    end
+}
+
+/// Checks every performance against `limits` (and the payload types a profile supports),
+/// printing one warning per drone and limit. Returns the number of warnings.
+pub fn validate(show: &Show, limits: &Limits, profile: Option<Profile>) -> usize {
+    let mut problems = 0;
+    for performance in &show.performances {
+        let drone_id = performance.id + 1;
+        let track = Track::new(performance, show.default_position_rate);
+        let velocities: Vec<(f32, [f32; 3])> = track.samples.windows(2)
+            .filter(|w| w[1].0 > w[0].0)
+            .map(|w| {
+                let ((t0, a), (t1, b)) = (w[0], w[1]);
+                let dt = t1 - t0;
+                (dt, [(b[0] - a[0]) / dt, (b[1] - a[1]) / dt, (b[2] - a[2]) / dt])
+            })
+            .collect();
+
+        let (mut speed, mut accel, mut climb, mut descent) = (None, None, None, None);
+        for (_, v) in &velocities {
+            Violation::check(&mut speed, (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt(), limits.max_speed);
+            Violation::check(&mut climb, v[1], limits.max_climb_rate);
+            Violation::check(&mut descent, -v[1], limits.max_descent_rate);
+        }
+        for w in velocities.windows(2) {
+            let ((dt0, a), (dt1, b)) = (w[0], w[1]);
+            let change = ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2) + (b[2] - a[2]).powi(2)).sqrt();
+            Violation::check(&mut accel, change / ((dt0 + dt1) / 2.0), limits.max_accel);
+        }
+
+        for (name, unit, violation, limit) in [
+            ("speed", "m/s", speed, limits.max_speed),
+            ("acceleration", "m/s²", accel, limits.max_accel),
+            ("climb rate", "m/s", climb, limits.max_climb_rate),
+            ("descent rate", "m/s", descent, limits.max_descent_rate)
+        ] {
+            if let (Some(violation), Some(limit)) = (violation, limit) {
+                progress::warning(format!(
+                    "drone {} exceeds the {} limit of {} {} in {} segments (max {:.2} {})",
+                    drone_id, name, limit, unit, violation.count, violation.worst, unit
+                ));
+                problems += 1;
+            }
+        }
+
+        if let Some(limit) = limits.max_flight_time {
+            if track.duration() > limit {
+                progress::warning(format!("drone {} flies for {:.1} s, longer than the {:.1} s limit", drone_id, track.duration(), limit));
+                problems += 1;
+            }
+        }
+
+        if let Some(limit) = limits.max_duration {
+            let end = timeline(performance, show, &track);
+            if end > limit {
+                progress::warning(format!("drone {}'s timeline runs for {:.1} s, longer than the {:.1} s limit", drone_id, end, limit));
+                problems += 1;
+            }
+        }
+
+        if let Some(tolerance) = limits.rate_tolerance {
+            if let Some(spacing) = typical_spacing(performance, show.default_position_rate) {
+                let expected = 1.0 / show.default_position_rate;
+                if ((spacing - expected) / expected).abs() > tolerance {
+                    progress::warning(format!(
+                        "drone {} is sampled every {:.3} s but the show declares {} positions/s ({:.3} s)",
+                        drone_id, spacing, show.default_position_rate, expected
+                    ));
+                    problems += 1;
+                }
+            }
+        }
+
+        if let Some(profile) = profile {
+            for payload in &performance.payload {
+                if !profile.payload_types().contains(&payload.payload_type.as_str()) {
+                    progress::warning(format!("drone {} uses a '{}' payload, which {} drones do not carry", drone_id, payload.payload_type, profile.name()));
+                    problems += 1;
+                }
+            }
+        }
+    }
+    if problems > 0 {
+        println!("Validation found {} problems", problems);
+    }
+    problems
+}
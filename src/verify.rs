@@ -0,0 +1,129 @@
+//! `csv2vviz verify`: converts inputs without writing them, then checks the show's positions
+//! against the samples it was built from.
+
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::{
+    acts, archive, build_show, curve::{self, bezier}, error::{Error, Result}, frames, input, make_reproducible,
+    quantize, track::distance, ConvertArgs, ConvertOptions, Drone, Performance, Sample
+};
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+
+    /// Largest distance (m) a reconstructed position may be from its sample, on top of what
+    /// --curve bezier and --quantize move positions by design
+    #[arg(long, default_value_t = 0.001)]
+    tolerance: f32,
+
+    #[command(flatten)]
+    convert: ConvertArgs
+}
+
+pub fn run(args: VerifyArgs) -> Result<()> {
+    let options = ConvertOptions::from_args(&args.convert)?;
+    let mut allowance = args.tolerance;
+    if options.curve == curve::Curve::Bezier {
+        allowance += options.curve_tolerance;
+    }
+    if let Some(step) = options.quantize {
+        allowance += step * 3f32.sqrt() / 2.0;
+    }
+
+    let mut failed = 0;
+    for filename in &args.convert.filenames {
+        let fname = PathBuf::from(filename);
+        let input = archive::Input::Path(&fname);
+        if !input::recognized(input, &options) {
+            return Err(Error::Format(format!("Invalid file format: {}", filename)));
+        }
+        for (act, drones, _) in acts::read(input, &options)? {
+            println!("Verifying {}{}", filename, if act.is_empty() { String::new() } else { format!(" ({})", act) });
+            failed += verify(drones, &options, allowance)?;
+        }
+    }
+
+    if failed > 0 {
+        return Err(Error::Validation(format!("{} drones drift more than {} m from their samples", failed, allowance)));
+    }
+    println!("Every drone within {} m of its samples", allowance);
+    Ok(())
+}
+
+/// Builds the show from `drones` as `convert` would and prints each drone's largest drift,
+/// returning the number of drones drifting more than `allowance`.
+fn verify(drones: Vec<Drone>, options: &ConvertOptions, allowance: f32) -> Result<usize> {
+    let references = drones.clone();
+    let (mut show, _) = build_show(drones, options, None)?;
+    if let Some(step) = options.quantize {
+        quantize::apply(&mut show, step);
+    }
+    if options.reproducible {
+        make_reproducible(&mut show);
+    }
+
+    let rate = show.default_position_rate;
+    let mut failed = 0;
+    for (drone_id, mut samples) in references {
+        let Some(performance) = show.performances.iter().find(|performance| performance.id + 1 == drone_id) else {
+            continue;
+        };
+        // Frame-encoded shows are built from the samples on the frame grid.
+        if options.traversal_mode == frames::TraversalMode::Frames {
+            samples = frames::resample(&samples, rate);
+        }
+        let lead = options.countdown.map_or(0.0, |countdown| countdown.lead(show.default_color_rate));
+        let (worst, at) = drift(performance, &samples, rate, lead);
+        let over = worst > allowance;
+        println!("  Drone {}: max drift {:.6} m at {:.2} s{}", drone_id, worst, at, if over { " (too far)" } else { "" });
+        failed += over as usize;
+    }
+    Ok(failed)
+}
+
+/// Largest distance between a sample and the performance's position at the sample's time,
+/// and that time in seconds from the performance start. Positions are accumulated from the
+/// traversal deltas in single precision, as viewers do. The samples start `lead` seconds into
+/// the performance, after any countdown.
+fn drift(performance: &Performance, samples: &[Sample], rate: f32, lead: f32) -> (f32, f32) {
+    let description = &performance.description;
+    let traversals = &description.traversals.0;
+    let first = samples.first().map_or(0.0, |sample| sample.t);
+    let (mut start, mut start_time, mut index) = ([description.home_x, description.home_y, description.home_z], 0.0f32, 0);
+    let mut worst = (0.0f32, 0.0f32);
+    for sample in samples {
+        let t = ((sample.t - first) / 1000.0) as f32 + lead;
+        while let Some(traversal) = traversals.get(index) {
+            let dt = traversal.duration(rate);
+            if t <= start_time + dt {
+                break;
+            }
+            start = [start[0] + traversal.dx, start[1] + traversal.dy, start[2] + traversal.dz];
+            start_time += dt;
+            index += 1;
+        }
+        let position = match traversals.get(index) {
+            Some(traversal) => {
+                let dt = traversal.duration(rate);
+                let u = if dt > 0.0 { ((t - start_time) / dt).clamp(0.0, 1.0) } else { 1.0 };
+                let end = [start[0] + traversal.dx, start[1] + traversal.dy, start[2] + traversal.dz];
+                match traversal.control_points {
+                    Some([c1, c2]) => {
+                        let offset = |c: [f32; 3]| [start[0] + c[0], start[1] + c[1], start[2] + c[2]];
+                        bezier([start, offset(c1), offset(c2), end], u)
+                    }
+                    None => [0, 1, 2].map(|axis| start[axis] + (end[axis] - start[axis]) * u)
+                }
+            }
+            None => start
+        };
+        let [east, north, up] = sample.pos;
+        let drift = distance(position, [east, up, north]);
+        if drift > worst.0 {
+            worst = (drift, t);
+        }
+    }
+    worst
+}
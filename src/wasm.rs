@@ -0,0 +1,55 @@
+//! Raw WebAssembly exports behind the browser converter in `web/`, built with
+//! `cargo build --release --lib --target wasm32-unknown-unknown`. `web/csv2vviz.js` wraps
+//! them in a `convert(bytes, options)` call.
+//!
+//! Results are returned as one buffer: a little-endian `u32` status (0, or the
+//! command-line exit code on failure), a `u32` body length, then the body: vviz JSON on
+//! success or the error message. Free it with `csv2vviz_free(ptr, 8 + length)`.
+
+use std::slice;
+
+/// Allocates `len` bytes for the caller to copy an input into.
+#[no_mangle]
+pub extern "C" fn csv2vviz_alloc(len: usize) -> *mut u8 {
+    Box::into_raw(vec![0u8; len].into_boxed_slice()) as *mut u8
+}
+
+/// Frees a buffer from [`csv2vviz_alloc`] or [`csv2vviz_convert_bytes`].
+///
+/// # Safety
+///
+/// `ptr` and `len` must describe a buffer returned by this module that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn csv2vviz_free(ptr: *mut u8, len: usize) {
+    drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)));
+}
+
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 { &[] } else { slice::from_raw_parts(ptr, len) }
+}
+
+/// Converts the archive in `archive[..archive_len]` with the JSON options object in
+/// `options[..options_len]` (empty for the defaults).
+///
+/// # Safety
+///
+/// Both pointers must be valid for their lengths.
+#[no_mangle]
+pub unsafe extern "C" fn csv2vviz_convert_bytes(
+    archive: *const u8, archive_len: usize, options: *const u8, options_len: usize
+) -> *mut u8 {
+    let archive = bytes(archive, archive_len);
+    let result = std::str::from_utf8(bytes(options, options_len))
+        .map_err(|_| crate::error::Error::Parse("Options are not valid UTF-8.".into()))
+        .and_then(|options| crate::convert_bytes(archive, options));
+    let (status, body) = match result {
+        Ok(json) => (0, json),
+        Err(e) => (e.exit_code() as u32, e.to_string())
+    };
+
+    let mut buffer = Vec::with_capacity(8 + body.len());
+    buffer.extend_from_slice(&status.to_le_bytes());
+    buffer.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(body.as_bytes());
+    Box::into_raw(buffer.into_boxed_slice()) as *mut u8
+}
@@ -0,0 +1,53 @@
+use std::{fmt, str::FromStr};
+
+use crate::heading;
+
+/// Forecast wind: its speed (m/s) and the compass bearing (degrees) it blows from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wind {
+    speed: f32,
+    from: f32
+}
+
+impl FromStr for Wind {
+    type Err = String;
+
+    /// `6m/s@240deg`; the speed may also be in `kn` or `km/h`, and units may be left out.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected SPEED@BEARING such as 6m/s@240deg, got '{}'", s);
+        let (speed, bearing) = s.split_once('@').ok_or_else(invalid)?;
+        let (speed, unit) = split_unit(speed.trim());
+        let scale = match unit {
+            "" | "m/s" => 1.0,
+            "kn" => 1852.0 / 3600.0,
+            "km/h" => 1.0 / 3.6,
+            _ => return Err(format!("unknown wind speed unit '{}' (m/s, kn or km/h)", unit))
+        };
+        let speed = speed.parse::<f32>().ok().filter(|speed| speed.is_finite() && *speed >= 0.0).ok_or_else(invalid)? * scale;
+        let from = heading::parse_bearing(bearing).map_err(|_| invalid())?;
+        Ok(Wind { speed, from })
+    }
+}
+
+/// The number at the start of `s` and the unit after it.
+fn split_unit(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+')).unwrap_or(s.len());
+    (&s[..end], s[end..].trim())
+}
+
+impl fmt::Display for Wind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1} m/s from {:.0}°", self.speed, self.from)
+    }
+}
+
+impl Wind {
+
+    /// How far downwind (east, north, up meters) a drone drifts in `latency` seconds before
+    /// its controller corrects.
+    pub fn drift(&self, latency: f32) -> [f32; 3] {
+        let toward = (self.from + 180.0).to_radians();
+        let distance = self.speed * latency;
+        [toward.sin() * distance, toward.cos() * distance, 0.0]
+    }
+}
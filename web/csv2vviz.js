@@ -0,0 +1,43 @@
+// Browser wrapper around csv2vviz.wasm, built with
+//   cargo build --release --lib --target wasm32-unknown-unknown
+// and copied next to this file. Conversion runs entirely client-side.
+
+export async function load(url = new URL('csv2vviz.wasm', import.meta.url)) {
+    const { instance } = await WebAssembly.instantiateStreaming(fetch(url), {});
+    const wasm = instance.exports;
+    const encoder = new TextEncoder();
+    const decoder = new TextDecoder();
+
+    function copyIn(bytes) {
+        const ptr = wasm.csv2vviz_alloc(bytes.length);
+        new Uint8Array(wasm.memory.buffer, ptr, bytes.length).set(bytes);
+        return ptr;
+    }
+
+    return {
+        // Converts a zip/tar archive (Uint8Array) and returns the vviz JSON text. `options`
+        // takes the `convert` flag names, e.g. { rotate: '0 0 90', reproducible: true }.
+        // Throws an Error with `exitCode` set on failure.
+        convert(archive, options = {}) {
+            const json = encoder.encode(JSON.stringify(options));
+            const archivePtr = copyIn(archive);
+            const optionsPtr = copyIn(json);
+            const result = wasm.csv2vviz_convert_bytes(archivePtr, archive.length, optionsPtr, json.length);
+            wasm.csv2vviz_free(archivePtr, archive.length);
+            wasm.csv2vviz_free(optionsPtr, json.length);
+
+            const header = new DataView(wasm.memory.buffer, result, 8);
+            const status = header.getUint32(0, true);
+            const length = header.getUint32(4, true);
+            const text = decoder.decode(new Uint8Array(wasm.memory.buffer, result + 8, length));
+            wasm.csv2vviz_free(result, 8 + length);
+
+            if (status !== 0) {
+                const error = new Error(text);
+                error.exitCode = status;
+                throw error;
+            }
+            return text;
+        }
+    };
+}